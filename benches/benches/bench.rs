@@ -5,7 +5,7 @@ use jsonrpsee_http_client::{HttpClient, HttpConfig};
 use jsonrpsee_http_server::HttpServer;
 use jsonrpsee_types::jsonrpc::{JsonValue, Params};
 use jsonrpsee_ws_client::{WsClient, WsConfig};
-use jsonrpsee_ws_server::WsServer;
+use jsonrpsee_ws_server::{WsServer, WsServerConfig};
 use std::net::SocketAddr;
 use std::sync::Arc;
 
@@ -28,7 +28,7 @@ async fn http_server(tx: Sender<SocketAddr>) {
 }
 
 async fn ws_server(tx: Sender<SocketAddr>) {
-	let server = WsServer::new("127.0.0.1:0").await.unwrap();
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
 	let mut say_hello = server.register_method("say_hello".to_string()).unwrap();
 	tx.send(*server.local_addr()).unwrap();
 	loop {
@@ -28,7 +28,7 @@ use async_std::task;
 use futures::channel::oneshot::{self, Sender};
 use jsonrpsee::client::{WsClient, WsConfig};
 use jsonrpsee::types::jsonrpc::{JsonValue, Params};
-use jsonrpsee::ws::WsServer;
+use jsonrpsee::ws::{WsServer, WsServerConfig};
 
 const SOCK_ADDR: &str = "127.0.0.1:9944";
 const SERVER_URI: &str = "ws://localhost:9944";
@@ -51,7 +51,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 }
 
 async fn run_server(server_started_tx: Sender<()>, url: &str) {
-	let server = WsServer::new(url).await.unwrap();
+	let server = WsServer::new(url, WsServerConfig::default()).await.unwrap();
 	let mut say_hello = server.register_method("say_hello".to_string()).unwrap();
 
 	server_started_tx.send(()).unwrap();
@@ -1,9 +1,12 @@
 use crate::transport::HttpTransportClient;
+use futures::future::BoxFuture;
 use jsonrpsee_types::{
+	client::{batch_results, parse_batch_results, BatchEntry, Client},
 	error::Error,
 	http::HttpConfig,
 	jsonrpc::{self, JsonValue},
 };
+use jsonrpsee_utils::tls::TlsClientConfig;
 
 use std::convert::TryInto;
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -27,6 +30,17 @@ impl HttpClient {
 		Ok(Self { transport, request_id: AtomicU64::new(0) })
 	}
 
+	/// Initializes a new HTTP client that uses the given TLS configuration for `https://` URLs,
+	/// for example in order to trust additional root certificates or to present a client
+	/// certificate.
+	///
+	/// Fails when the URL is invalid.
+	pub fn with_tls_config(target: impl AsRef<str>, config: HttpConfig, tls: &TlsClientConfig) -> Result<Self, Error> {
+		let transport = HttpTransportClient::with_tls_config(target, config, tls)
+			.map_err(|e| Error::TransportError(Box::new(e)))?;
+		Ok(Self { transport, request_id: AtomicU64::new(0) })
+	}
+
 	/// Send a notification to the server.
 	///
 	/// WARNING: This method must be executed on [Tokio 1.0](https://docs.rs/tokio/1.0.1/tokio).
@@ -47,11 +61,14 @@ impl HttpClient {
 	/// Perform a request towards the server.
 	///
 	/// WARNING: This method must be executed on [Tokio 1.0](https://docs.rs/tokio/1.0.1/tokio).
-	pub async fn request(
+	pub async fn request<Ret>(
 		&self,
 		method: impl Into<String>,
 		params: impl Into<jsonrpc::Params>,
-	) -> Result<JsonValue, Error> {
+	) -> Result<Ret, Error>
+	where
+		Ret: jsonrpc::DeserializeOwned,
+	{
 		// NOTE: `fetch_add` wraps on overflow which is intended.
 		let id = self.request_id.fetch_add(1, Ordering::SeqCst);
 		let request = jsonrpc::Request::Single(jsonrpc::Call::MethodCall(jsonrpc::MethodCall {
@@ -67,17 +84,60 @@ impl HttpClient {
 			.await
 			.map_err(|e| Error::TransportError(Box::new(e)))?;
 
-		match response {
-			jsonrpc::Response::Single(rp) => Self::process_response(rp, id),
+		let json_value = match response {
+			jsonrpc::Response::Single(rp) => Self::process_response(rp, id)?,
 			// Server should not send batch response to a single request.
 			jsonrpc::Response::Batch(_rps) => {
-				Err(Error::Custom("Server replied with batch response to a single request".to_string()))
+				return Err(Error::Custom("Server replied with batch response to a single request".to_string()))
 			}
 			// Server should not reply to a Notification.
-			jsonrpc::Response::Notif(_notif) => {
-				Err(Error::Custom(format!("Server replied with notification response to request ID: {}", id)))
+			jsonrpc::Response::Notif(_) | jsonrpc::Response::SubscriptionClosed(_) => {
+				return Err(Error::Custom(format!("Server replied with notification response to request ID: {}", id)))
 			}
+		};
+		jsonrpc::from_value(json_value).map_err(Error::ParseError)
+	}
+
+	/// Send a batch of method calls and notifications to the server in a single HTTP request.
+	///
+	/// The results are the undecoded `JsonValue`s, see [`Client::batch_request`] for how they
+	/// relate to the entries of `batch`.
+	///
+	/// WARNING: This method must be executed on [Tokio 1.0](https://docs.rs/tokio/1.0.1/tokio).
+	pub async fn batch_request(&self, batch: Vec<BatchEntry>) -> Result<Vec<Result<JsonValue, Error>>, Error> {
+		// An empty batch is an invalid request.
+		if batch.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		let mut ids = Vec::new();
+		let calls: Vec<_> = batch
+			.into_iter()
+			.map(|entry| {
+				if let BatchEntry::Notification { .. } = entry {
+					return entry.into_call(0);
+				}
+				// NOTE: `fetch_add` wraps on overflow which is intended.
+				let id = self.request_id.fetch_add(1, Ordering::SeqCst);
+				ids.push(id);
+				entry.into_call(id)
+			})
+			.collect();
+		let request = jsonrpc::Request::Batch(calls);
+
+		// The server doesn't answer a batch made only of notifications.
+		if ids.is_empty() {
+			self.transport.send_notification(request).await.map_err(|e| Error::TransportError(Box::new(e)))?;
+			return Ok(Vec::new());
 		}
+
+		let response = self
+			.transport
+			.send_request_and_wait_for_response(request)
+			.await
+			.map_err(|e| Error::TransportError(Box::new(e)))?;
+
+		batch_results(ids, response)
 	}
 
 	fn process_response(response: jsonrpc::Output, expected_id: u64) -> Result<JsonValue, Error> {
@@ -87,3 +147,33 @@ impl HttpClient {
 		}
 	}
 }
+
+impl Client for HttpClient {
+	fn notification<'a>(
+		&'a self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> BoxFuture<'a, Result<(), Error>> {
+		let (method, params) = (method.into(), params.into());
+		Box::pin(HttpClient::notification(self, method, params))
+	}
+
+	fn request<'a, T>(
+		&'a self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> BoxFuture<'a, Result<T, Error>>
+	where
+		T: jsonrpc::DeserializeOwned + Send + 'a,
+	{
+		let (method, params) = (method.into(), params.into());
+		Box::pin(HttpClient::request(self, method, params))
+	}
+
+	fn batch_request<'a, T>(&'a self, batch: Vec<BatchEntry>) -> BoxFuture<'a, Result<Vec<Result<T, Error>>, Error>>
+	where
+		T: jsonrpc::DeserializeOwned + Send + 'a,
+	{
+		Box::pin(async move { HttpClient::batch_request(self, batch).await.map(parse_batch_results) })
+	}
+}
@@ -0,0 +1,120 @@
+//! Connector that opens plain TCP connections for `http://` URLs and TLS connections for
+//! `https://` URLs.
+
+use hyper::client::connect::{Connected, Connection, HttpConnector};
+use hyper::service::Service;
+use hyper::Uri;
+use jsonrpsee_utils::tls::{TlsClientConfig, ALPN_HTTP1};
+use std::{
+	future::Future,
+	io,
+	pin::Pin,
+	task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt as _, TokioAsyncReadCompatExt as _};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Connector passed to the `hyper` client.
+#[derive(Clone)]
+pub(crate) struct HttpsConnector {
+	/// Opens the TCP connections.
+	http: HttpConnector,
+	/// Performs the TLS handshake on the connections to `https://` URLs.
+	tls: async_tls::TlsConnector,
+}
+
+/// Connection opened by an [`HttpsConnector`].
+pub(crate) enum MaybeTlsStream {
+	Plain(TcpStream),
+	Tls(Box<Compat<async_tls::client::TlsStream<Compat<TcpStream>>>>),
+}
+
+impl HttpsConnector {
+	/// Creates a connector that uses the given configuration for `https://` URLs.
+	///
+	/// Only HTTP/1.1 is offered through ALPN.
+	pub(crate) fn new(tls: &TlsClientConfig) -> Self {
+		let mut http = HttpConnector::new();
+		// Allows the `https` scheme, which is then handled by this connector.
+		http.enforce_http(false);
+		HttpsConnector {
+			http,
+			tls: async_tls::TlsConnector::from(std::sync::Arc::new(tls.client_config(&[ALPN_HTTP1]))),
+		}
+	}
+}
+
+impl std::fmt::Debug for HttpsConnector {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		f.debug_struct("HttpsConnector").finish()
+	}
+}
+
+impl Service<Uri> for HttpsConnector {
+	type Response = MaybeTlsStream;
+	type Error = BoxError;
+	type Future = Pin<Box<dyn Future<Output = Result<MaybeTlsStream, BoxError>> + Send>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), BoxError>> {
+		self.http.poll_ready(cx).map_err(Into::into)
+	}
+
+	fn call(&mut self, uri: Uri) -> Self::Future {
+		let is_https = uri.scheme_str() == Some("https");
+		let host = uri.host().unwrap_or_default().to_owned();
+		let connecting = self.http.call(uri);
+		let tls = self.tls.clone();
+		Box::pin(async move {
+			let socket = connecting.await?;
+			if !is_https {
+				return Ok(MaybeTlsStream::Plain(socket));
+			}
+			let socket = tls.connect(host, socket.compat()).await?;
+			Ok(MaybeTlsStream::Tls(Box::new(socket.compat())))
+		})
+	}
+}
+
+impl Connection for MaybeTlsStream {
+	fn connected(&self) -> Connected {
+		match self {
+			MaybeTlsStream::Plain(socket) => socket.connected(),
+			MaybeTlsStream::Tls(_) => Connected::new(),
+		}
+	}
+}
+
+impl AsyncRead for MaybeTlsStream {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+		match self.get_mut() {
+			MaybeTlsStream::Plain(socket) => Pin::new(socket).poll_read(cx, buf),
+			MaybeTlsStream::Tls(socket) => Pin::new(socket).poll_read(cx, buf),
+		}
+	}
+}
+
+impl AsyncWrite for MaybeTlsStream {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+		match self.get_mut() {
+			MaybeTlsStream::Plain(socket) => Pin::new(socket).poll_write(cx, buf),
+			MaybeTlsStream::Tls(socket) => Pin::new(socket).poll_write(cx, buf),
+		}
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		match self.get_mut() {
+			MaybeTlsStream::Plain(socket) => Pin::new(socket).poll_flush(cx),
+			MaybeTlsStream::Tls(socket) => Pin::new(socket).poll_flush(cx),
+		}
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		match self.get_mut() {
+			MaybeTlsStream::Plain(socket) => Pin::new(socket).poll_shutdown(cx),
+			MaybeTlsStream::Tls(socket) => Pin::new(socket).poll_shutdown(cx),
+		}
+	}
+}
@@ -1,9 +1,12 @@
 mod client;
+mod connector;
 mod transport;
 
 #[cfg(test)]
 mod tests;
 
 pub use client::HttpClient;
+pub use jsonrpsee_types::client::{BatchEntry, Client};
 pub use jsonrpsee_types::http::HttpConfig;
+pub use jsonrpsee_utils::tls::{TlsClientConfig, TlsConfigError};
 pub use transport::HttpTransportClient;
@@ -1,5 +1,6 @@
 use crate::client::HttpClient;
 use jsonrpsee_types::{
+	client::BatchEntry,
 	error::Error,
 	http::HttpConfig,
 	jsonrpc::{self, ErrorCode, JsonValue, Params},
@@ -84,3 +85,56 @@ fn assert_jsonrpc_error_response(response: Error, code: ErrorCode, message: Stri
 		e @ _ => panic!("Expected error: \"{}\", got: {:?}", expected, e),
 	};
 }
+
+#[tokio::test]
+async fn batch_request_works() {
+	// The responses are out of order and one of the calls failed.
+	let response = format!(
+		"[{},{},{}]",
+		ok_response("two".into(), Id::Num(2)),
+		method_not_found(Id::Num(1)),
+		ok_response("zero".into(), Id::Num(0))
+	);
+	let results = run_batch_request_with_response(response).await.unwrap();
+	assert_eq!(results.len(), 3);
+	assert_eq!(results[0].as_ref().unwrap(), &JsonValue::String("zero".into()));
+	assert!(matches!(&results[1], Err(Error::Request(err)) if err.code == ErrorCode::MethodNotFound));
+	assert_eq!(results[2].as_ref().unwrap(), &JsonValue::String("two".into()));
+}
+
+#[tokio::test]
+async fn batch_request_with_missing_response() {
+	let response = format!("[{},{}]", ok_response("two".into(), Id::Num(2)), ok_response("zero".into(), Id::Num(0)));
+	let results = run_batch_request_with_response(response).await.unwrap();
+	assert!(results[0].is_ok());
+	assert!(matches!(results[1], Err(Error::Custom(_))));
+	assert!(results[2].is_ok());
+}
+
+#[tokio::test]
+async fn batch_request_with_invalid_response() {
+	let unknown_id = format!(
+		"[{},{},{}]",
+		ok_response("zero".into(), Id::Num(0)),
+		ok_response("one".into(), Id::Num(1)),
+		ok_response("three".into(), Id::Num(3))
+	);
+	let err = run_batch_request_with_response(unknown_id).await.unwrap_err();
+	assert!(matches!(err, Error::InvalidRequestId));
+
+	let err = run_batch_request_with_response(invalid_request(Id::Null)).await.unwrap_err();
+	assert_jsonrpc_error_response(err, ErrorCode::InvalidRequest, INVALID_REQUEST.into());
+}
+
+async fn run_batch_request_with_response(response: String) -> Result<Vec<Result<JsonValue, Error>>, Error> {
+	let server_addr = http_server_with_hardcoded_response(response).await;
+	let uri = format!("http://{}", server_addr);
+	let client = HttpClient::new(&uri, HttpConfig::default())?;
+	let batch = vec![
+		BatchEntry::call("say_hello", Params::None),
+		BatchEntry::call("say_hello", Params::None),
+		BatchEntry::notification("notif", Params::None),
+		BatchEntry::call("say_hello", Params::None),
+	];
+	client.batch_request(batch).await
+}
@@ -6,8 +6,9 @@
 // that we need to be guaranteed that hyper doesn't re-use an existing connection if we ever reset
 // the JSON-RPC request id to a value that might have already been used.
 
+use crate::connector::HttpsConnector;
 use jsonrpsee_types::{error::GenericTransportError, http::HttpConfig, jsonrpc};
-use jsonrpsee_utils::http::hyper_helpers;
+use jsonrpsee_utils::{http::hyper_helpers, tls::TlsClientConfig};
 use thiserror::Error;
 
 const CONTENT_TYPE_JSON: &str = "application/json";
@@ -18,19 +19,27 @@ pub struct HttpTransportClient {
 	/// Target to connect to.
 	target: url::Url,
 	/// HTTP client,
-	client: hyper::Client<hyper::client::HttpConnector>,
+	client: hyper::Client<HttpsConnector>,
 	/// Configurable max request body size
 	config: HttpConfig,
 }
 
 impl HttpTransportClient {
 	/// Initializes a new HTTP client.
+	///
+	/// The certificates of `https://` servers are checked against the Mozilla root certificates.
 	pub fn new(target: impl AsRef<str>, config: HttpConfig) -> Result<Self, Error> {
+		Self::with_tls_config(target, config, &TlsClientConfig::default())
+	}
+
+	/// Initializes a new HTTP client that uses the given TLS configuration for `https://` URLs.
+	pub fn with_tls_config(target: impl AsRef<str>, config: HttpConfig, tls: &TlsClientConfig) -> Result<Self, Error> {
 		let target = url::Url::parse(target.as_ref()).map_err(|e| Error::Url(format!("Invalid URL: {}", e)))?;
-		if target.scheme() == "http" {
-			Ok(HttpTransportClient { client: hyper::Client::new(), target, config })
+		if target.scheme() == "http" || target.scheme() == "https" {
+			let client = hyper::Client::builder().build(HttpsConnector::new(tls));
+			Ok(HttpTransportClient { client, target, config })
 		} else {
-			Err(Error::Url("URL scheme not supported, expects 'http'".into()))
+			Err(Error::Url("URL scheme not supported, expects 'http' or 'https'".into()))
 		}
 	}
 
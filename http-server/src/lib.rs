@@ -32,8 +32,11 @@ mod transport;
 mod tests;
 
 pub use jsonrpsee_types::http::HttpConfig;
+pub use jsonrpsee_types::server::{RegisteredMethod, RegisteredNotification};
+pub use jsonrpsee_utils::http::access_control::{AccessControl, AccessControlBuilder};
+pub use jsonrpsee_utils::tls::{TlsConfigError, TlsServerConfig};
 pub use raw::RawServer as HttpRawServer;
 pub use raw::RawServerEvent as HttpRawServerEvent;
 pub use raw::TypedResponder as HttpTypedResponder;
-pub use server::{RegisteredMethod, RegisteredNotification, Server as HttpServer};
-pub use transport::HttpTransportServer;
+pub use server::{Builder as HttpServerBuilder, Server as HttpServer};
+pub use transport::{HttpTransportServer, HttpTransportServerBuilder, StopHandle};
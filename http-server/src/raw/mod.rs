@@ -1,8 +1,15 @@
-mod core;
 mod typed_rp;
 
 #[cfg(test)]
 mod tests;
 
-pub use self::core::{RawServer, RawServerEvent, RawServerRequest, RawServerRequestId, RawServerSubscriptionId};
+use crate::transport::{HttpTransportServer, RequestId};
+
 pub use self::typed_rp::TypedResponder;
+
+/// [`RawServer`](jsonrpsee_types::server::RawServer) over HTTP.
+pub type RawServer = jsonrpsee_types::server::RawServer<HttpTransportServer>;
+/// Event generated by a [`RawServer`].
+pub type RawServerEvent<'a> = jsonrpsee_types::server::RawServerEvent<'a, RequestId>;
+/// Request received by a [`RawServer`].
+pub type RawServerRequest<'a> = jsonrpsee_types::server::RawServerRequest<'a, RequestId>;
@@ -24,117 +24,70 @@
 // IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use crate::raw::{RawServer, RawServerEvent, RawServerRequestId};
-use crate::transport::HttpTransportServer;
+use crate::transport::{HttpTransportServer, StopHandle};
 use jsonrpsee_types::{
 	error::Error,
 	http::HttpConfig,
-	jsonrpc::{self, JsonValue},
+	jsonrpc::{self, DeserializeOwned, Serialize},
+	server::{
+		Frontend, MethodConfig, Middleware, RegisteredMethod, RegisteredNotification, RequestContext, RpcModule,
+		ServerMetrics, Spawner,
+	},
 };
+use jsonrpsee_utils::{http::access_control::AccessControl, tls::TlsServerConfig};
 
-use futures::{channel::mpsc, future::Either, pin_mut, prelude::*};
-use parking_lot::Mutex;
-use std::{
-	collections::{HashMap, HashSet},
-	error,
-	net::SocketAddr,
-	sync::{atomic, Arc},
-};
+use futures::prelude::*;
+use std::{error, net::SocketAddr, sync::Arc, time::Duration};
 
 /// Server that can be cloned.
 ///
 /// > **Note**: This struct is designed to be easy to use, but it works by maintaining a background
 /// >           task running in parallel. If this is not desirable, you are encouraged to use the
-/// >           [`RawServer`] struct instead.
+/// >           [`RawServer`](crate::HttpRawServer) struct instead.
 #[derive(Clone)]
 pub struct Server {
 	/// Local socket address of the transport server.
 	local_addr: SocketAddr,
-	/// Channel to send requests to the background task.
-	to_back: mpsc::UnboundedSender<FrontToBack>,
-	/// List of methods (for RPC queries and notifications) that have been
-	/// registered. Serves no purpose except to check for duplicates.
-	registered_methods: Arc<Mutex<HashSet<String>>>,
-	/// Next unique ID used when registering a subscription.
-	next_subscription_unique_id: Arc<atomic::AtomicUsize>,
-}
-
-/// Notification method that's been registered.
-pub struct RegisteredNotification {
-	/// Receives notifications that the client sent to us.
-	queries_rx: mpsc::Receiver<jsonrpc::Params>,
-}
-
-/// Method that's been registered.
-pub struct RegisteredMethod {
-	/// Clone of [`Server::to_back`].
-	to_back: mpsc::UnboundedSender<FrontToBack>,
-	/// Receives requests that the client sent to us.
-	queries_rx: mpsc::Receiver<(RawServerRequestId, jsonrpc::Params)>,
+	/// Handle to the background task.
+	frontend: Frontend,
+	/// Handle to stop the transport server.
+	stop_handle: StopHandle,
+	/// Metrics of the server.
+	metrics: ServerMetrics,
 }
 
-/// Active request that needs to be answered.
-pub struct IncomingRequest {
-	/// Clone of [`Server::to_back`].
-	to_back: mpsc::UnboundedSender<FrontToBack>,
-	/// Identifier of the request towards the server.
-	request_id: RawServerRequestId,
-	/// Parameters of the request.
-	params: jsonrpc::Params,
-}
-
-/// Message that the [`Server`] can send to the background task.
-enum FrontToBack {
-	/// Registers a notifications endpoint.
-	RegisterNotifications {
-		/// Name of the method.
-		name: String,
-		/// Where to send incoming notifications.
-		handler: mpsc::Sender<jsonrpc::Params>,
-		/// See the documentation of [`Server::register_notifications`].
-		allow_losses: bool,
-	},
-
-	/// Registers a method. The server will then handle requests using this method.
-	RegisterMethod {
-		/// Name of the method.
-		name: String,
-		/// Where to send requests.
-		handler: mpsc::Sender<(RawServerRequestId, jsonrpc::Params)>,
-	},
-
-	/// Send a response to a request that a client made.
-	AnswerRequest {
-		/// Request to answer.
-		request_id: RawServerRequestId,
-		/// Response to send back.
-		answer: Result<JsonValue, jsonrpc::Error>,
-	},
+/// Builder for a [`Server`].
+pub struct Builder {
+	/// Address to listen on.
+	url: String,
+	/// Configuration of the server.
+	config: HttpConfig,
+	/// Runtime to run the server on, if any.
+	tokio_handle: Option<tokio::runtime::Handle>,
+	/// Path on which to serve the metrics, if any.
+	metrics_path: Option<String>,
+	/// Certificate and key to encrypt the connections with, if any.
+	tls: Option<TlsServerConfig>,
+	/// Allowed hosts and CORS policy.
+	access_control: AccessControl,
 }
 
 impl Server {
 	/// Initializes a new server based upon this raw server.
 	pub async fn new(url: impl AsRef<str>, config: HttpConfig) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
-		let sockaddr = url.as_ref().parse()?;
-		let transport_server = HttpTransportServer::new(&sockaddr, config).await?;
-		let local_addr = *transport_server.local_addr();
-
-		// We use an unbounded channel because the only exchanged messages concern registering
-		// methods. The volume of messages is therefore very low and it doesn't make sense to have
-		// a backpressure mechanism.
-		// TODO: that's not true anymore ^
-		let (to_back, from_front) = mpsc::unbounded();
-
-		async_std::task::spawn(async move {
-			background_task(transport_server.into(), from_front).await;
-		});
+		Server::builder(url).config(config).build().await
+	}
 
-		Ok(Server {
-			local_addr,
-			to_back,
-			registered_methods: Arc::new(Mutex::new(HashSet::new())),
-			next_subscription_unique_id: Arc::new(atomic::AtomicUsize::new(0)),
-		})
+	/// Creates a new [`Builder`] that listens on the given address.
+	pub fn builder(url: impl AsRef<str>) -> Builder {
+		Builder {
+			url: url.as_ref().to_owned(),
+			config: HttpConfig::default(),
+			tokio_handle: None,
+			metrics_path: None,
+			tls: None,
+			access_control: AccessControl::default(),
+		}
 	}
 
 	/// Local socket address of the transport server.
@@ -142,170 +95,194 @@ impl Server {
 		&self.local_addr
 	}
 
-	/// Registers a notification method name towards the server.
+	/// Returns the metrics of the server.
+	pub fn metrics(&self) -> &ServerMetrics {
+		&self.metrics
+	}
+
+	/// Stops the server.
 	///
-	/// Clients will then be able to call this method.
-	/// The returned object allows you to process incoming notifications.
+	/// The server immediately stops accepting new connections. Requests that are already being
+	/// processed have until `deadline` to be answered, after which their connections are closed.
+	/// Once the returned `Future` has completed, the port has been released and the background
+	/// thread has been joined. Registered methods stop receiving requests.
 	///
-	/// If `allow_losses` is true, then the server is allowed to drop notifications if the
-	/// notifications handler (i.e. the code that uses [`RegisteredNotifications`]) is too slow
-	/// to process notifications.
+	/// Returns an error if the server was already stopped.
+	pub async fn stop(&self, deadline: Duration) -> Result<(), Error> {
+		self.stop_handle.stop(deadline).await?;
+		// No request can reach the background task anymore, so it can be terminated without
+		// waiting for the requests that are still pending.
+		self.frontend.stop(future::ready(())).await
+	}
+
+	/// Adds a middleware, called around every method call made to the server.
+	///
+	/// See [`Frontend::add_middleware`] for more information.
+	pub fn add_middleware(&self, middleware: impl Middleware) -> Result<(), Error> {
+		self.frontend.add_middleware(middleware)
+	}
+
+	/// Registers a notification method name towards the server.
 	///
-	/// Returns an error if the method name was already registered.
+	/// See [`Frontend::register_notification`] for more information.
 	pub fn register_notification(
 		&self,
 		method_name: String,
 		allow_losses: bool,
 	) -> Result<RegisteredNotification, Error> {
-		if !self.registered_methods.lock().insert(method_name.clone()) {
-			return Err(Error::MethodAlreadyRegistered(method_name));
-		}
-
-		log::trace!("[frontend]: register_notification={}", method_name);
-		let (tx, rx) = mpsc::channel(32);
-
-		self.to_back
-			.unbounded_send(FrontToBack::RegisterNotifications { name: method_name, handler: tx, allow_losses })
-			.map_err(|e| Error::Internal(e.into_send_error()))?;
-
-		Ok(RegisteredNotification { queries_rx: rx })
+		self.frontend.register_notification(method_name, allow_losses)
 	}
 
 	/// Registers a method towards the server.
 	///
-	/// Clients will then be able to call this method.
-	/// The returned object allows you to handle incoming requests.
+	/// See [`Frontend::register_method`] for more information.
+	pub fn register_method(&self, method_name: String) -> Result<RegisteredMethod, Error> {
+		self.frontend.register_method(method_name)
+	}
+
+	/// Registers a method towards the server, with a custom configuration for its queue of
+	/// requests.
 	///
-	/// Contrary to [`register_notifications`](Server::register_notifications), there is no
-	/// `allow_losses` parameter here. If the handler is too slow to process requests, then the
-	/// server automatically returns an "internal error" to the client.
+	/// See [`Frontend::register_method_with_config`] for more information.
+	pub fn register_method_with_config(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+	) -> Result<RegisteredMethod, Error> {
+		self.frontend.register_method_with_config(method_name, config)
+	}
+
+	/// Registers a method towards the server and lets the server drive its handler.
 	///
-	/// Returns an error if the method name was already registered.
-	pub fn register_method(&self, method_name: String) -> Result<RegisteredMethod, Error> {
-		if !self.registered_methods.lock().insert(method_name.clone()) {
-			return Err(Error::MethodAlreadyRegistered(method_name));
-		}
+	/// See [`Frontend::register_async_method`] for more information.
+	pub fn register_async_method<F, Fut, P, T, E>(&self, method_name: String, callback: F) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.frontend.register_async_method(method_name, callback)
+	}
 
-		log::trace!("[frontend]: register_method={}", method_name);
-		let (tx, rx) = mpsc::channel(32);
+	/// Registers a method towards the server and lets the server drive its handler, with a custom
+	/// configuration for its queue of requests.
+	///
+	/// See [`Frontend::register_async_method_with_config`] for more information.
+	pub fn register_async_method_with_config<F, Fut, P, T, E>(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.frontend.register_async_method_with_config(method_name, config, callback)
+	}
 
-		self.to_back
-			.unbounded_send(FrontToBack::RegisterMethod { name: method_name, handler: tx })
-			.map_err(|e| Error::Internal(e.into_send_error()))?;
+	/// Same as [`register_async_method`](Server::register_async_method), except that `callback`
+	/// is also passed the [`RequestContext`] of each request.
+	pub fn register_async_method_with_context<F, Fut, P, T, E>(
+		&self,
+		method_name: String,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P, RequestContext) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.frontend.register_async_method_with_context(method_name, callback)
+	}
 
-		Ok(RegisteredMethod { to_back: self.to_back.clone(), queries_rx: rx })
+	/// Registers all the methods of `module` towards the server.
+	///
+	/// The subscriptions of the module are ignored, as they aren't supported over HTTP. See
+	/// [`Frontend::register_module`] for more information.
+	pub fn register_module(&self, module: &RpcModule) -> Result<(), Error> {
+		self.frontend.register_module(module)
 	}
 }
 
-impl RegisteredNotification {
-	/// Returns the next notification.
-	pub async fn next(&mut self) -> jsonrpc::Params {
-		loop {
-			match self.queries_rx.next().await {
-				Some(v) => break v,
-				None => futures::pending!(),
-			}
-		}
+impl Builder {
+	/// Sets the configuration of the server.
+	pub fn config(mut self, config: HttpConfig) -> Self {
+		self.config = config;
+		self
 	}
-}
 
-impl RegisteredMethod {
-	/// Returns the next request.
-	pub async fn next(&mut self) -> IncomingRequest {
-		let (request_id, params) = loop {
-			match self.queries_rx.next().await {
-				Some(v) => break v,
-				None => futures::pending!(),
-			}
-		};
-		IncomingRequest { to_back: self.to_back.clone(), request_id, params }
+	/// Runs the server on the given tokio runtime.
+	///
+	/// Both hyper and the tasks that process the requests are spawned on this runtime. By
+	/// default, hyper runs on a dedicated thread and the requests are processed by `async-std`.
+	pub fn tokio_handle(mut self, handle: tokio::runtime::Handle) -> Self {
+		self.tokio_handle = Some(handle);
+		self
 	}
-}
 
-impl IncomingRequest {
-	/// Returns the parameters of the request.
-	pub fn params(&self) -> &jsonrpc::Params {
-		&self.params
+	/// Serves the metrics of the server, in the Prometheus text format, to HTTP `GET` requests
+	/// on the given path (for example `/metrics`).
+	///
+	/// By default, the metrics are only available through [`Server::metrics`].
+	pub fn metrics_path(mut self, path: impl Into<String>) -> Self {
+		self.metrics_path = Some(path.into());
+		self
 	}
 
-	/// Respond to the request.
-	pub async fn respond(mut self, response: impl Into<Result<JsonValue, jsonrpc::Error>>) -> Result<(), Error> {
-		self.to_back
-			.send(FrontToBack::AnswerRequest { request_id: self.request_id, answer: response.into() })
-			.await
-			.map_err(Error::Internal)
+	/// Serves HTTPS instead of HTTP, using the given certificate and key. HTTP/2 is negotiated
+	/// through ALPN with the clients that support it.
+	pub fn tls(mut self, config: TlsServerConfig) -> Self {
+		self.tls = Some(config);
+		self
 	}
-}
 
-/// Function being run in the background that processes messages from the frontend.
-async fn background_task(mut server: RawServer, mut from_front: mpsc::UnboundedReceiver<FrontToBack>) {
-	// List of notifications methods that the user has registered, and the channels to dispatch
-	// incoming notifications.
-	let mut registered_notifications: HashMap<String, (mpsc::Sender<_>, bool)> = HashMap::new();
-	// List of methods that the user has registered, and the channels to dispatch incoming
-	// requests.
-	let mut registered_methods: HashMap<String, mpsc::Sender<_>> = HashMap::new();
+	/// Sets the hosts allowed to reach the server and its CORS policy.
+	///
+	/// By default, any host and any origin are allowed. Browsers are sent the CORS headers
+	/// required to call the server from any origin allowed by `access_control`.
+	pub fn access_control(mut self, access_control: AccessControl) -> Self {
+		self.access_control = access_control;
+		self
+	}
 
-	loop {
-		// We need to do a little transformation in order to destroy the borrow to `client`
-		// and `from_front`.
-		let outcome = {
-			let next_message = from_front.next();
-			let next_event = server.next_event();
-			pin_mut!(next_message);
-			pin_mut!(next_event);
-			match future::select(next_message, next_event).await {
-				Either::Left((v, _)) => Either::Left(v),
-				Either::Right((v, _)) => Either::Right(v),
-			}
-		};
+	/// Starts the server.
+	pub async fn build(self) -> Result<Server, Box<dyn error::Error + Send + Sync>> {
+		let sockaddr = self.url.parse()?;
+		let mut transport_server =
+			HttpTransportServer::builder(sockaddr, self.config).access_control(self.access_control);
+		if let Some(handle) = self.tokio_handle.clone() {
+			transport_server = transport_server.tokio_handle(handle);
+		}
+		if let Some(path) = self.metrics_path {
+			transport_server = transport_server.metrics_path(path);
+		}
+		if let Some(tls) = self.tls {
+			transport_server = transport_server.tls(tls);
+		}
+		let transport_server = transport_server.build().await?;
+		let local_addr = *transport_server.local_addr();
+		let metrics = transport_server.metrics().clone();
+		let stop_handle = transport_server.stop_handle();
 
-		match outcome {
-			Either::Left(None) => {
-				log::trace!("[backend]: background_task terminated");
-				return;
-			}
-			Either::Left(Some(FrontToBack::AnswerRequest { request_id, answer })) => {
-				log::trace!("[backend]: answer_request: {:?} id: {:?}", answer, request_id);
-				server.request_by_id(&request_id).unwrap().respond(answer);
-			}
-			Either::Left(Some(FrontToBack::RegisterNotifications { name, handler, allow_losses })) => {
-				log::trace!("[backend]: register_notification: {:?}", name);
-				registered_notifications.insert(name, (handler, allow_losses));
-			}
-			Either::Left(Some(FrontToBack::RegisterMethod { name, handler })) => {
-				log::trace!("[backend]: register_method: {:?}", name);
-				registered_methods.insert(name, handler);
+		// The tasks of the server run on the given tokio runtime, or on the `async-std` one.
+		let tokio_handle = self.tokio_handle;
+		let spawner: Spawner = Arc::new(move |future| match &tokio_handle {
+			Some(handle) => {
+				handle.spawn(future);
 			}
-			Either::Right(RawServerEvent::Notification(notification)) => {
-				log::trace!("[backend]: received notification: {:?}", notification);
-				if let Some((handler, allow_losses)) = registered_notifications.get_mut(notification.method()) {
-					let params: &jsonrpc::Params = notification.params().into();
-					// Note: we just ignore errors. It doesn't make sense logically speaking to
-					// unregister the notification here.
-					if *allow_losses {
-						let _ = handler.send(params.clone()).now_or_never();
-					} else {
-						let _ = handler.send(params.clone()).await;
-					}
-				}
+			None => {
+				async_std::task::spawn(future);
 			}
-			Either::Right(RawServerEvent::Request(request)) => {
-				log::trace!("[backend]: received request: {:?}", request);
-				if let Some(handler) = registered_methods.get_mut(request.method()) {
-					let params: &jsonrpc::Params = request.params().into();
-					match handler.send((request.id(), params.clone())).now_or_never() {
-						Some(Ok(())) => {}
-						Some(Err(_)) | None => {
-							request.respond(Err(From::from(jsonrpc::ErrorCode::ServerError(0))));
-						}
-					}
-				} else {
-					// TODO: we assert that the request is valid because the parsing succeeded but
-					// not registered.
-					request.respond(Err(From::from(jsonrpc::ErrorCode::MethodNotFound)));
-				}
-			}
-		}
+		});
+
+		Ok(Server { local_addr, frontend: Frontend::start(transport_server, spawner), stop_handle, metrics })
 	}
 }
@@ -1,13 +1,20 @@
 #![cfg(test)]
 
-use crate::{HttpConfig, HttpServer};
+use crate::{AccessControlBuilder, HttpConfig, HttpServer, TlsServerConfig};
 use futures::channel::oneshot::{self, Sender};
 use futures::future::FutureExt;
 use futures::{pin_mut, select};
 use jsonrpsee_test_utils::helpers::*;
-use jsonrpsee_test_utils::types::{Id, StatusCode};
-use jsonrpsee_types::jsonrpc::JsonValue;
+use jsonrpsee_test_utils::tls::{https_request, negotiated_alpn, LOCALHOST_CERT, LOCALHOST_KEY};
+use jsonrpsee_test_utils::types::{Body, HttpResponse, Id, Method, StatusCode};
+use jsonrpsee_types::{
+	error::Error,
+	jsonrpc::{self, JsonValue},
+	server::{MethodConfig, Middleware, OverflowPolicy, RequestContext},
+};
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 async fn server(server_started_tx: Sender<SocketAddr>) {
 	let server = HttpServer::new("127.0.0.1:0", HttpConfig::default()).await.unwrap();
@@ -47,6 +54,46 @@ async fn server(server_started_tx: Sender<SocketAddr>) {
 	}
 }
 
+/// Method, outcome and duration of a call seen by a [`Recorder`].
+type RecordedCall = (String, Result<JsonValue, jsonrpc::ErrorCode>, Duration);
+
+/// Middleware that records the answered calls and rejects the calls to `secret`.
+#[derive(Clone, Default)]
+struct Recorder {
+	calls: Arc<Mutex<Vec<RecordedCall>>>,
+}
+
+impl Middleware for Recorder {
+	fn on_request(&self, method: &str, _: &jsonrpc::Params, _: &RequestContext) -> Result<(), jsonrpc::Error> {
+		if method == "secret" {
+			return Err(jsonrpc::Error::invalid_request());
+		}
+		Ok(())
+	}
+
+	fn on_response(
+		&self,
+		method: &str,
+		_: &jsonrpc::Params,
+		_: &RequestContext,
+		result: &JsonValue,
+		elapsed: Duration,
+	) {
+		self.calls.lock().unwrap().push((method.to_owned(), Ok(result.clone()), elapsed));
+	}
+
+	fn on_error(
+		&self,
+		method: &str,
+		_: &jsonrpc::Params,
+		_: &RequestContext,
+		error: &jsonrpc::Error,
+		elapsed: Duration,
+	) {
+		self.calls.lock().unwrap().push((method.to_owned(), Err(error.code.clone()), elapsed));
+	}
+}
+
 #[tokio::test]
 async fn single_method_call_works() {
 	let (server_started_tx, server_started_rx) = oneshot::channel::<SocketAddr>();
@@ -109,3 +156,311 @@ async fn invalid_request_object() {
 	assert_eq!(response.status, StatusCode::OK);
 	assert_eq!(response.body, invalid_request(Id::Num(1)));
 }
+
+#[tokio::test]
+async fn async_method_call_works() {
+	let server = HttpServer::new("127.0.0.1:0", HttpConfig::default()).await.unwrap();
+	server
+		.register_async_method("add".to_owned(), |(a, b): (u64, u64)| async move { Ok::<_, jsonrpc::Error>(a + b) })
+		.unwrap();
+	server
+		.register_async_method(
+			"fail".to_owned(),
+			|_: ()| async move { Err::<(), _>(jsonrpc::ErrorCode::InternalError) },
+		)
+		.unwrap();
+	assert!(server.register_async_method("add".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>(()) }).is_err());
+	let uri = to_http_uri(*server.local_addr());
+
+	let req = r#"{"jsonrpc":"2.0","method":"add","params":[1, 2],"id":1}"#;
+	let response = http_request(req.into(), uri.clone()).await.unwrap();
+	assert_eq!(response.status, StatusCode::OK);
+	assert_eq!(response.body, ok_response(JsonValue::Number(3.into()), Id::Num(1)));
+
+	let req = r#"{"jsonrpc":"2.0","method":"fail","id":2}"#;
+	let response = http_request(req.into(), uri.clone()).await.unwrap();
+	assert_eq!(response.body, internal_error(Id::Num(2)));
+
+	let req = r#"{"jsonrpc":"2.0","method":"add","params":["1", 2],"id":3}"#;
+	let response = http_request(req.into(), uri).await.unwrap();
+	assert_eq!(
+		response.body,
+		r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params: invalid type: string \"1\", expected u64."},"id":3}"#
+	);
+}
+
+#[tokio::test]
+async fn request_context_works() {
+	let server = HttpServer::new("127.0.0.1:0", HttpConfig::default()).await.unwrap();
+	server
+		.register_async_method_with_context("context".to_owned(), |_: (), context: RequestContext| async move {
+			let content_type = context.headers().and_then(|headers| headers.get("content-type").cloned());
+			Ok::<_, jsonrpc::Error>((
+				context.id().clone(),
+				context.remote_addr().map(|addr| addr.ip().to_string()),
+				content_type.and_then(|value| value.to_str().ok().map(str::to_owned)),
+				context.connection_id(),
+			))
+		})
+		.unwrap();
+	let uri = to_http_uri(*server.local_addr());
+
+	let req = r#"{"jsonrpc":"2.0","method":"context","id":"abc"}"#;
+	let response = http_request(req.into(), uri).await.unwrap();
+	assert_eq!(
+		response.body,
+		ok_response(serde_json::json!(["abc", "127.0.0.1", "application/json", null]), Id::Str("abc".to_owned()))
+	);
+}
+
+#[tokio::test]
+async fn middleware_works() {
+	let server = HttpServer::new("127.0.0.1:0", HttpConfig::default()).await.unwrap();
+	let recorder = Recorder::default();
+	server.add_middleware(recorder.clone()).unwrap();
+	server
+		.register_async_method("sleep".to_owned(), |(ms,): (u64,)| async move {
+			async_std::task::sleep(Duration::from_millis(ms)).await;
+			Ok::<_, jsonrpc::Error>(ms)
+		})
+		.unwrap();
+	server.register_async_method("secret".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>(42) }).unwrap();
+	let uri = to_http_uri(*server.local_addr());
+
+	let response = http_request(r#"{"jsonrpc":"2.0","method":"sleep","params":[100],"id":1}"#.into(), uri.clone())
+		.await
+		.unwrap()
+		.body;
+	assert_eq!(response, ok_response(JsonValue::Number(100.into()), Id::Num(1)));
+	let response =
+		http_request(r#"{"jsonrpc":"2.0","method":"secret","id":2}"#.into(), uri.clone()).await.unwrap().body;
+	assert_eq!(response, invalid_request(Id::Num(2)));
+	let response =
+		http_request(r#"{"jsonrpc":"2.0","method":"unknown","id":3}"#.into(), uri.clone()).await.unwrap().body;
+	assert_eq!(response, method_not_found(Id::Num(3)));
+
+	let calls = recorder.calls.lock().unwrap().clone();
+	assert_eq!(calls.len(), 3);
+	assert_eq!((calls[0].0.as_str(), &calls[0].1), ("sleep", &Ok(JsonValue::Number(100.into()))));
+	assert!(calls[0].2 >= Duration::from_millis(100));
+	assert_eq!((calls[1].0.as_str(), &calls[1].1), ("secret", &Err(jsonrpc::ErrorCode::InvalidRequest)));
+	assert_eq!((calls[2].0.as_str(), &calls[2].1), ("unknown", &Err(jsonrpc::ErrorCode::MethodNotFound)));
+}
+
+#[tokio::test]
+async fn metrics_path_works() {
+	let server = HttpServer::builder("127.0.0.1:0").metrics_path("/metrics").build().await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let uri = to_http_uri(*server.local_addr());
+
+	let req = r#"[{"jsonrpc":"2.0","method":"say_hello","id":1},{"jsonrpc":"2.0","method":"unknown","id":2}]"#;
+	http_request(req.into(), uri.clone()).await.unwrap();
+
+	let metrics_uri = format!("http://{}/metrics", server.local_addr()).parse().unwrap();
+	let response = hyper::Client::new().get(metrics_uri).await.unwrap();
+	assert_eq!(response.status(), StatusCode::OK);
+	let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+	let encoded = String::from_utf8(body.to_vec()).unwrap();
+	assert_eq!(encoded, server.metrics().encode());
+	for line in &[
+		"jsonrpsee_http_requests_total{method=\"say_hello\"} 1",
+		"jsonrpsee_http_errors_total{method=\"unknown\",code=\"-32601\"} 1",
+		"jsonrpsee_http_request_duration_seconds_count{method=\"say_hello\"} 1",
+		"jsonrpsee_http_batch_size_count 1",
+		"jsonrpsee_http_batch_size_sum 2",
+	] {
+		assert!(encoded.lines().any(|l| l == *line), "missing {:?} in:\n{}", line, encoded);
+	}
+	// HTTP connections aren't tracked.
+	assert!(!encoded.contains("jsonrpsee_http_connections"));
+
+	// Other paths and methods are still rejected.
+	let other_uri = format!("http://{}/other", server.local_addr()).parse().unwrap();
+	let response = hyper::Client::new().get(other_uri).await.unwrap();
+	assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn cors_works() {
+	let access_control = AccessControlBuilder::new()
+		.cors_allow_origin("http://allowed.com".into())
+		.cors_allow_header("x-api-key".to_owned())
+		.cors_max_age(600)
+		.build();
+	let server = HttpServer::builder("127.0.0.1:0").access_control(access_control).build().await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let uri = to_http_uri(*server.local_addr());
+	let header =
+		|response: &HttpResponse, name: &str| response.header.get(name).map(|value| value.to_str().unwrap().to_owned());
+
+	// Preflight request.
+	let preflight = [
+		("origin", "http://allowed.com"),
+		("access-control-request-method", "POST"),
+		("access-control-request-headers", "content-type, x-api-key"),
+	];
+	let response = http_request_with_headers(Method::OPTIONS, &preflight, Body::empty(), uri.clone()).await.unwrap();
+	assert_eq!(response.status, StatusCode::OK);
+	assert_eq!(response.body, "");
+	assert_eq!(header(&response, "access-control-allow-origin").as_deref(), Some("http://allowed.com"));
+	assert_eq!(header(&response, "access-control-allow-methods").as_deref(), Some("OPTIONS, POST"));
+	assert_eq!(header(&response, "access-control-allow-headers").as_deref(), Some("content-type, x-api-key"));
+	assert_eq!(header(&response, "access-control-max-age").as_deref(), Some("600"));
+	assert_eq!(header(&response, "vary").as_deref(), Some("origin"));
+
+	// Actual request.
+	let req = r#"{"jsonrpc":"2.0","method":"say_hello","id":1}"#;
+	let headers = [("content-type", "application/json"), ("origin", "http://allowed.com"), ("x-api-key", "secret")];
+	let response = http_request_with_headers(Method::POST, &headers, req.into(), uri.clone()).await.unwrap();
+	assert_eq!(response.body, ok_response(JsonValue::String("hello".to_owned()), Id::Num(1)));
+	assert_eq!(header(&response, "access-control-allow-origin").as_deref(), Some("http://allowed.com"));
+	assert_eq!(header(&response, "access-control-allow-methods"), None);
+
+	// Requests without an origin don't get CORS headers.
+	let response = http_request(req.into(), uri.clone()).await.unwrap();
+	assert_eq!(response.status, StatusCode::OK);
+	assert_eq!(header(&response, "access-control-allow-origin"), None);
+
+	// Other origins and headers are rejected.
+	let headers = [("content-type", "application/json"), ("origin", "http://denied.com")];
+	let response = http_request_with_headers(Method::POST, &headers, req.into(), uri.clone()).await.unwrap();
+	assert_eq!(response.status, StatusCode::FORBIDDEN);
+	let preflight = [("origin", "http://allowed.com"), ("access-control-request-headers", "x-other")];
+	let response = http_request_with_headers(Method::OPTIONS, &preflight, Body::empty(), uri).await.unwrap();
+	assert_eq!(response.status, StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn tls_works() {
+	let tls = TlsServerConfig::from_pem(LOCALHOST_CERT, LOCALHOST_KEY).unwrap();
+	let server = HttpServer::builder("127.0.0.1:0").tls(tls).build().await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let addr = *server.local_addr();
+
+	let req = r#"{"jsonrpc":"2.0","method":"say_hello","id":1}"#;
+	for http2 in &[false, true] {
+		let response = https_request(req.into(), addr, *http2).await.unwrap();
+		assert_eq!(response.status, StatusCode::OK);
+		assert_eq!(response.body, ok_response(JsonValue::String("hello".to_owned()), Id::Num(1)));
+	}
+
+	// HTTP/2 is preferred when offered, and HTTP/1.1 is used otherwise.
+	let alpn = tokio::task::spawn_blocking(move || {
+		(negotiated_alpn(addr, &[b"http/1.1", b"h2"]).unwrap(), negotiated_alpn(addr, &[b"http/1.1"]).unwrap())
+	})
+	.await
+	.unwrap();
+	assert_eq!(alpn, (Some(b"h2".to_vec()), Some(b"http/1.1".to_vec())));
+
+	// Plain text requests are rejected.
+	assert!(http_request(req.into(), to_http_uri(addr)).await.is_err());
+}
+
+#[tokio::test]
+async fn stop_works() {
+	let server = HttpServer::new("127.0.0.1:0", HttpConfig::default()).await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let addr = *server.local_addr();
+
+	let req = r#"{"jsonrpc":"2.0","method":"say_hello","id":1}"#;
+	let response = http_request(req.into(), to_http_uri(addr)).await.unwrap();
+	assert_eq!(response.body, ok_response(JsonValue::String("hello".to_owned()), Id::Num(1)));
+
+	server.stop(Duration::from_secs(5)).await.unwrap();
+	assert!(matches!(server.stop(Duration::from_secs(5)).await, Err(Error::AlreadyStopped)));
+	assert!(http_request(req.into(), to_http_uri(addr)).await.is_err());
+	// The port must have been released.
+	std::net::TcpListener::bind(addr).unwrap();
+}
+
+#[tokio::test]
+async fn stop_waits_for_in_flight_requests() {
+	let server = HttpServer::new("127.0.0.1:0", HttpConfig::default()).await.unwrap();
+	server
+		.register_async_method("sleep".to_owned(), |(ms,): (u64,)| async move {
+			async_std::task::sleep(Duration::from_millis(ms)).await;
+			Ok::<_, jsonrpc::Error>(ms)
+		})
+		.unwrap();
+	let uri = to_http_uri(*server.local_addr());
+
+	let short =
+		tokio::spawn(http_request(r#"{"jsonrpc":"2.0","method":"sleep","params":[200],"id":1}"#.into(), uri.clone()));
+	let long = tokio::spawn(http_request(r#"{"jsonrpc":"2.0","method":"sleep","params":[60000],"id":2}"#.into(), uri));
+	tokio::time::sleep(Duration::from_millis(50)).await;
+
+	server.stop(Duration::from_secs(1)).await.unwrap();
+	let response = short.await.unwrap().unwrap();
+	assert_eq!(response.body, ok_response(JsonValue::Number(200.into()), Id::Num(1)));
+	// The second request exceeds the deadline and its connection is closed.
+	assert!(long.await.unwrap().is_err());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn tokio_handle_works() {
+	let server =
+		HttpServer::builder("127.0.0.1:0").tokio_handle(tokio::runtime::Handle::current()).build().await.unwrap();
+	// Handlers run on the provided runtime rather than on `async-std`.
+	server
+		.register_async_method("on_tokio".to_owned(), |_: ()| async {
+			Ok::<_, jsonrpc::Error>(tokio::runtime::Handle::try_current().is_ok())
+		})
+		.unwrap();
+	let addr = *server.local_addr();
+
+	let req = r#"{"jsonrpc":"2.0","method":"on_tokio","id":1}"#;
+	let response = http_request(req.into(), to_http_uri(addr)).await.unwrap();
+	assert_eq!(response.body, ok_response(JsonValue::Bool(true), Id::Num(1)));
+
+	server.stop(Duration::from_secs(5)).await.unwrap();
+	assert!(http_request(req.into(), to_http_uri(addr)).await.is_err());
+	std::net::TcpListener::bind(addr).unwrap();
+}
+
+#[tokio::test]
+async fn method_queue_overflow_policies() {
+	let server = HttpServer::new("127.0.0.1:0", HttpConfig::default()).await.unwrap();
+	let config = |overflow_policy| MethodConfig { queue_capacity: 1, overflow_policy };
+	let mut reject = server.register_method_with_config("reject".to_owned(), config(OverflowPolicy::Reject)).unwrap();
+	let mut shed = server.register_method_with_config("shed".to_owned(), config(OverflowPolicy::DropOldest)).unwrap();
+	let mut wait = server.register_method_with_config("wait".to_owned(), config(OverflowPolicy::Wait)).unwrap();
+	let uri = to_http_uri(*server.local_addr());
+	let call = |method: &str, id: u64| {
+		let req = format!(r#"{{"jsonrpc":"2.0","method":"{}","id":{}}}"#, method, id);
+		tokio::spawn(http_request(req.into(), uri.clone()))
+	};
+	let busy = |id: u64| {
+		format!(
+			r#"{{"jsonrpc":"2.0","error":{{"code":-32002,"message":"Server is busy, try again later"}},"id":{}}}"#,
+			id
+		)
+	};
+
+	// The second request is rejected because the first one fills the queue.
+	let first = call("reject", 1);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	assert_eq!(call("reject", 2).await.unwrap().unwrap().body, busy(2));
+	reject.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(first.await.unwrap().unwrap().body, ok_response(JsonValue::Null, Id::Num(1)));
+
+	// The first request is shed to make room for the second one.
+	let first = call("shed", 1);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	let second = call("shed", 2);
+	assert_eq!(first.await.unwrap().unwrap().body, busy(1));
+	shed.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(second.await.unwrap().unwrap().body, ok_response(JsonValue::Null, Id::Num(2)));
+
+	// Both requests are processed, and the other methods are served while the second one waits.
+	let first = call("wait", 1);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	let second = call("wait", 2);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	let other = call("reject", 3);
+	reject.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(other.await.unwrap().unwrap().body, ok_response(JsonValue::Null, Id::Num(3)));
+	wait.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	wait.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(first.await.unwrap().unwrap().body, ok_response(JsonValue::Null, Id::Num(1)));
+	assert_eq!(second.await.unwrap().unwrap().body, ok_response(JsonValue::Null, Id::Num(2)));
+}
@@ -26,16 +26,83 @@
 
 use crate::transport::response;
 use futures::{channel::mpsc, channel::oneshot, prelude::*};
+use hyper::server::{accept::Accept, conn::AddrIncoming, conn::AddrStream};
 use hyper::service::{make_service_fn, service_fn};
 use hyper::Error;
-use jsonrpsee_types::{error::GenericTransportError, http::HttpConfig, jsonrpc};
-use jsonrpsee_utils::http::{access_control::AccessControl, hyper_helpers};
-use std::{error, net::SocketAddr, thread};
+use jsonrpsee_types::{
+	error::GenericTransportError,
+	http::HttpConfig,
+	jsonrpc,
+	server::{ConnectionExtensions, RequestContext, ServerMetrics},
+};
+use jsonrpsee_utils::{
+	http::{access_control::AccessControl, hyper_helpers},
+	tls::{TlsServerConfig, ALPN_HTTP1, ALPN_HTTP2},
+};
+use parking_lot::Mutex;
+use std::{
+	error, io,
+	net::SocketAddr,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	thread,
+	time::Duration,
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_util::compat::{Compat, FuturesAsyncReadCompatExt as _, TokioAsyncReadCompatExt as _};
 
-/// Background thread that serves HTTP requests.
+/// Capacity of the channel that transmits the requests from hyper to the frontend.
+const REQUESTS_CHANNEL_CAPACITY: usize = 64;
+
+/// Maximum number of TLS handshakes that can be in progress at the same time. No new connection
+/// is accepted while this limit is reached.
+const MAX_PENDING_HANDSHAKES: usize = 64;
+
+/// Time after which a TLS handshake that hasn't completed is aborted.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Background thread (or task, if a tokio runtime has been provided) that serves HTTP requests.
 pub(super) struct BackgroundHttp {
 	/// Receiver for requests coming from the background thread.
 	rx: stream::Fuse<mpsc::Receiver<Request>>,
+	/// Handle to stop the background thread.
+	stop_handle: StopHandle,
+}
+
+/// Handle that allows to stop an HTTP server running in the background.
+///
+/// Can be cloned. Only the first call to [`StopHandle::stop`] has an effect.
+#[derive(Clone)]
+pub struct StopHandle {
+	inner: Arc<Mutex<Option<StopHandleInner>>>,
+}
+
+struct StopHandleInner {
+	/// Sends the deadline for in-flight requests to the background thread, which then stops
+	/// accepting new connections. Dropping it without sending stops the server immediately.
+	stop_tx: oneshot::Sender<Duration>,
+	/// Resolves once the server has stopped, and the tokio runtime of the background thread (if
+	/// any) has been dropped.
+	stopped_rx: oneshot::Receiver<()>,
+	/// The background thread itself. `None` if the server runs on a tokio runtime provided by
+	/// the user.
+	thread: Option<thread::JoinHandle<()>>,
+}
+
+/// Path on which the metrics of the server are served to HTTP `GET` requests.
+#[derive(Clone)]
+pub(super) struct MetricsEndpoint {
+	/// Path of the endpoint, for example `/metrics`.
+	pub path: String,
+	/// Metrics to serve.
+	pub metrics: ServerMetrics,
+}
+
+/// Connection accepted by the server, either in plain text or after a TLS handshake.
+enum Connection {
+	Plain(AddrStream),
+	Tls(Box<Compat<async_tls::server::TlsStream<Compat<AddrStream>>>>, SocketAddr),
 }
 
 /// Request generated from the background thread.
@@ -44,6 +111,8 @@ pub(super) struct Request {
 	pub send_back: oneshot::Sender<hyper::Response<hyper::Body>>,
 	/// The JSON body that was sent by the client.
 	pub request: jsonrpc::Request,
+	/// Information about the HTTP request and the connection it was received on.
+	pub context: RequestContext,
 }
 
 impl BackgroundHttp {
@@ -56,60 +125,142 @@ impl BackgroundHttp {
 		addr: &SocketAddr,
 		config: HttpConfig,
 	) -> Result<(BackgroundHttp, SocketAddr), Box<dyn error::Error + Send + Sync>> {
-		Self::bind_with_acl(addr, AccessControl::default(), config).await
+		Self::bind_with_acl(addr, AccessControl::default(), config, None, None, None).await
 	}
 
+	/// Same as [`BackgroundHttp::bind`], but with an access control list.
+	///
+	/// If `tokio_handle` is `Some`, the server is spawned as a task on the corresponding runtime.
+	/// Otherwise, it runs on a dedicated background thread. If `metrics` is `Some`, the metrics
+	/// are served on the given path. If `tls` is `Some`, the connections are encrypted and
+	/// HTTP/2 is offered through ALPN.
 	pub async fn bind_with_acl(
 		addr: &SocketAddr,
 		access_control: AccessControl,
 		config: HttpConfig,
+		tokio_handle: Option<tokio::runtime::Handle>,
+		metrics: Option<MetricsEndpoint>,
+		tls: Option<TlsServerConfig>,
 	) -> Result<(BackgroundHttp, SocketAddr), Box<dyn error::Error + Send + Sync>> {
-		let (tx, rx) = mpsc::channel(4);
+		let (tx, rx) = mpsc::channel(REQUESTS_CHANNEL_CAPACITY);
 
-		let make_service = make_service_fn(move |_| {
+		let make_service = make_service_fn(move |conn: &Connection| {
 			let tx = tx.clone();
 			let access_control = access_control.clone();
+			let metrics = metrics.clone();
+			let remote_addr = conn.remote_addr();
+			// Shared by all the requests of the connection.
+			let extensions = ConnectionExtensions::default();
 			async move {
 				Ok::<_, Error>(service_fn(move |req| {
 					let mut tx = tx.clone();
 					let access_control = access_control.clone();
-					async move { Ok::<_, Error>(process_request(req, &mut tx, &access_control, config).await) }
+					let metrics = metrics.clone();
+					let context = RequestContext::new(extensions.clone()).with_remote_addr(remote_addr);
+					async move {
+						let response =
+							process_request(req, context, &mut tx, &access_control, metrics.as_ref(), config).await;
+						Ok::<_, Error>(response)
+					}
 				}))
 			}
 		});
 
 		let (addr_tx, addr_rx) = oneshot::channel();
+		let (stop_tx, stop_rx) = oneshot::channel::<Duration>();
+		let (stopped_tx, stopped_rx) = oneshot::channel();
 		let addr = *addr;
 
-		// Because hyper can only be polled through tokio, we spawn it in a background thread.
-		thread::Builder::new().name("jsonrpsee-hyper-server".to_string()).spawn(move || {
-			let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
-				Ok(r) => r,
+		let acceptor = tls.map(|tls| async_tls::TlsAcceptor::from(tls.server_config(&[ALPN_HTTP2, ALPN_HTTP1])));
+
+		let serve = async move {
+			match AddrIncoming::bind(&addr) {
+				Ok(mut incoming) => {
+					let _ = addr_tx.send(Ok(incoming.local_addr()));
+					let connections = stream::poll_fn(move |cx| Pin::new(&mut incoming).poll_accept(cx))
+						.map(move |stream| accept_connection(stream, acceptor.clone()))
+						.buffer_unordered(MAX_PENDING_HANDSHAKES)
+						.filter_map(|connection| async move {
+							match connection {
+								Ok(connection) => Some(Ok::<_, io::Error>(connection)),
+								Err(err) => {
+									log::debug!("Failed to accept HTTP JSON-RPC connection: {}", err);
+									None
+								}
+							}
+						});
+					let server =
+						hyper::Server::builder(hyper::server::accept::from_stream(connections)).serve(make_service);
+
+					// Once a stop is requested, hyper stops accepting new connections and
+					// waits for the in-flight requests to be answered. We additionally race
+					// this against the deadline passed to `StopHandle::stop`.
+					let (deadline_tx, deadline_rx) = oneshot::channel();
+					let server = server.with_graceful_shutdown(async move {
+						let deadline = stop_rx.await.unwrap_or_else(|_| Duration::from_secs(0));
+						let _ = deadline_tx.send(deadline);
+					});
+					let deadline = async move {
+						match deadline_rx.await {
+							Ok(deadline) => tokio::time::sleep(deadline).await,
+							Err(_) => future::pending().await,
+						}
+					};
+
+					futures::pin_mut!(server, deadline);
+					match future::select(server, deadline).await {
+						future::Either::Left((Ok(()), _)) => {}
+						future::Either::Left((Err(err), _)) => {
+							log::error!("HTTP JSON-RPC server closed with an error: {}", err);
+						}
+						future::Either::Right(_) => {
+							log::warn!("HTTP JSON-RPC server shutdown deadline reached; closing connections");
+						}
+					}
+				}
 				Err(err) => {
-					log::error!("Failed to initialize tokio runtime in HTTP JSON-RPC server: {}", err);
-					return;
+					log::error!("Failed to bind to address {}: {}", addr, err);
+					let _ = addr_tx.send(Err(err));
 				}
 			};
+		};
 
-			runtime.block_on(async move {
-				match hyper::Server::try_bind(&addr) {
-					Ok(builder) => {
-						let server = builder.serve(make_service);
-						let _ = addr_tx.send(Ok(server.local_addr()));
-						if let Err(err) = server.await {
-							log::error!("HTTP JSON-RPC server closed with an error: {}", err);
+		let thread = match tokio_handle {
+			Some(handle) => {
+				handle.spawn(async move {
+					serve.await;
+					let _ = stopped_tx.send(());
+				});
+				None
+			}
+			// Because hyper can only be polled through tokio, we spawn it in a background thread.
+			None => {
+				let thread = thread::Builder::new().name("jsonrpsee-hyper-server".to_string()).spawn(move || {
+					let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+						Ok(r) => r,
+						Err(err) => {
+							log::error!("Failed to initialize tokio runtime in HTTP JSON-RPC server: {}", err);
+							return;
 						}
-					}
-					Err(err) => {
-						log::error!("Failed to bind to address {}: {}", addr, err);
-						let _ = addr_tx.send(Err(err));
-					}
-				};
-			});
-		})?;
+					};
+					runtime.block_on(serve);
+					// Dropping the runtime cancels the tasks of the connections that are still open.
+					drop(runtime);
+					let _ = stopped_tx.send(());
+				})?;
+				Some(thread)
+			}
+		};
 
 		let local_addr = addr_rx.await??;
-		Ok((BackgroundHttp { rx: rx.fuse() }, local_addr))
+		let stop_handle =
+			StopHandle { inner: Arc::new(Mutex::new(Some(StopHandleInner { stop_tx, stopped_rx, thread }))) };
+		Ok((BackgroundHttp { rx: rx.fuse(), stop_handle }, local_addr))
+	}
+
+	/// Returns a handle that allows to stop the server.
+	pub fn stop_handle(&self) -> StopHandle {
+		self.stop_handle.clone()
 	}
 
 	/// Returns the next request, or an error if the background thread has unexpectedly closed.
@@ -118,6 +269,94 @@ impl BackgroundHttp {
 	}
 }
 
+impl StopHandle {
+	/// Stops the server.
+	///
+	/// The server immediately stops accepting new connections. Requests that are already being
+	/// processed have until `deadline` to be answered, after which their connections are closed.
+	/// Once the returned `Future` has completed, the background thread has been joined and the
+	/// listening socket has been released.
+	///
+	/// > **Note**: If the server runs on a tokio runtime provided by the user, the connections
+	/// >           that are still open once `deadline` has passed can't be cancelled. They are
+	/// >           closed after their pending requests have been answered with an error.
+	///
+	/// Returns an error if the server was already stopped.
+	pub async fn stop(&self, deadline: Duration) -> Result<(), jsonrpsee_types::error::Error> {
+		let inner = self.inner.lock().take().ok_or(jsonrpsee_types::error::Error::AlreadyStopped)?;
+		let _ = inner.stop_tx.send(deadline);
+		// An error means that the background thread has exited early, which is fine as well.
+		let _ = inner.stopped_rx.await;
+		if let Some(thread) = inner.thread {
+			if thread.join().is_err() {
+				log::error!("HTTP JSON-RPC server background thread panicked");
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Performs the TLS handshake on a newly-accepted connection if `acceptor` is `Some`.
+async fn accept_connection(
+	stream: Result<AddrStream, io::Error>,
+	acceptor: Option<async_tls::TlsAcceptor>,
+) -> Result<Connection, io::Error> {
+	let stream = stream?;
+	let acceptor = match acceptor {
+		Some(acceptor) => acceptor,
+		None => return Ok(Connection::Plain(stream)),
+	};
+
+	let remote_addr = stream.remote_addr();
+	match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream.compat())).await {
+		Ok(Ok(stream)) => Ok(Connection::Tls(Box::new(stream.compat()), remote_addr)),
+		Ok(Err(err)) => Err(err),
+		Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "TLS handshake timed out")),
+	}
+}
+
+impl Connection {
+	/// Returns the address of the client.
+	fn remote_addr(&self) -> SocketAddr {
+		match self {
+			Connection::Plain(stream) => stream.remote_addr(),
+			Connection::Tls(_, remote_addr) => *remote_addr,
+		}
+	}
+}
+
+impl AsyncRead for Connection {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut ReadBuf<'_>) -> Poll<io::Result<()>> {
+		match self.get_mut() {
+			Connection::Plain(stream) => Pin::new(stream).poll_read(cx, buf),
+			Connection::Tls(stream, _) => Pin::new(stream).poll_read(cx, buf),
+		}
+	}
+}
+
+impl AsyncWrite for Connection {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+		match self.get_mut() {
+			Connection::Plain(stream) => Pin::new(stream).poll_write(cx, buf),
+			Connection::Tls(stream, _) => Pin::new(stream).poll_write(cx, buf),
+		}
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		match self.get_mut() {
+			Connection::Plain(stream) => Pin::new(stream).poll_flush(cx),
+			Connection::Tls(stream, _) => Pin::new(stream).poll_flush(cx),
+		}
+	}
+
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		match self.get_mut() {
+			Connection::Plain(stream) => Pin::new(stream).poll_shutdown(cx),
+			Connection::Tls(stream, _) => Pin::new(stream).poll_shutdown(cx),
+		}
+	}
+}
+
 /// Process an HTTP request and sends back a response.
 ///
 /// This function is the main method invoked whenever we receive an HTTP request.
@@ -126,8 +365,10 @@ impl BackgroundHttp {
 /// channel will be dispatched to the user.
 async fn process_request(
 	request: hyper::Request<hyper::Body>,
+	context: RequestContext,
 	fg_process_tx: &mut mpsc::Sender<Request>,
 	access_control: &AccessControl,
+	metrics: Option<&MetricsEndpoint>,
 	config: HttpConfig,
 ) -> hyper::Response<hyper::Body> {
 	// Process access control
@@ -141,12 +382,38 @@ async fn process_request(
 		return response::invalid_allow_headers();
 	}
 
+	let cors_headers = access_control.cors_headers(&request);
+	let mut response = match *request.method() {
+		// Answer CORS preflight requests
+		hyper::Method::OPTIONS => response::cors_preflight(),
+		_ => process_call(request, context, fg_process_tx, metrics, config).await,
+	};
+	response.headers_mut().extend(cors_headers);
+	response
+}
+
+/// Processes a request that has passed the access control.
+async fn process_call(
+	request: hyper::Request<hyper::Body>,
+	context: RequestContext,
+	fg_process_tx: &mut mpsc::Sender<Request>,
+	metrics: Option<&MetricsEndpoint>,
+	config: HttpConfig,
+) -> hyper::Response<hyper::Body> {
+	// Serve the metrics, if enabled
+	if let Some(metrics) = metrics {
+		if request.method() == hyper::Method::GET && request.uri().path() == metrics.path {
+			return response::metrics(metrics.metrics.encode());
+		}
+	}
+
 	// Proceed
 	match *request.method() {
 		// Validate the ContentType header
 		// to prevent Cross-Origin XHRs with text/plain
 		hyper::Method::POST if is_json(request.headers().get("content-type")) => {
 			let (parts, body) = request.into_parts();
+			let context = context.with_headers(parts.headers.clone());
 			let request = match hyper_helpers::read_response_to_body(&parts.headers, body, config).await {
 				Ok(body) => match jsonrpc::from_slice(&body) {
 					Ok(response) => response,
@@ -158,7 +425,7 @@ async fn process_request(
 
 			let (tx, rx) = oneshot::channel();
 			log::debug!("recv: {}", request);
-			let user_facing_rq = Request { send_back: tx, request };
+			let user_facing_rq = Request { send_back: tx, request, context };
 			if fg_process_tx.send(user_facing_rq).await.is_err() {
 				return response::internal_error("JSON requests processing channel has shut down");
 			}
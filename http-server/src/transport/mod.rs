@@ -28,37 +28,22 @@ mod background;
 #[allow(unused)]
 mod response;
 
-use jsonrpsee_types::{http::HttpConfig, jsonrpc};
-use jsonrpsee_utils::http::access_control::AccessControl;
+use jsonrpsee_types::{
+	http::HttpConfig,
+	jsonrpc,
+	server::{RequestContext, ServerMetrics, TransportServer, TransportServerEvent},
+};
+use jsonrpsee_utils::{http::access_control::AccessControl, tls::TlsServerConfig};
 
 use fnv::FnvHashMap;
 use futures::{channel::oneshot, prelude::*};
 use std::{error, net::SocketAddr, pin::Pin};
 
-pub type RequestId = u64;
+pub use background::StopHandle;
 
-/// Event that the [`TransportServer`] can generate.
-#[derive(Debug, PartialEq)]
-pub enum TransportServerEvent<T> {
-	/// A new request has arrived on the wire.
-	///
-	/// This generates a new "request object" within the state of the [`TransportServer`] that is
-	/// identified through the returned `id`. You can then use the other methods of the
-	/// [`TransportServer`] trait in order to manipulate that request.
-	Request {
-		/// Identifier of the request within the state of the [`TransportServer`].
-		id: T,
-		/// Body of the request.
-		request: jsonrpc::Request,
-	},
-
-	/// A request has been cancelled, most likely because the client has closed the connection.
-	///
-	/// The corresponding request is no longer valid to manipulate.
-	Closed(T),
-}
+pub type RequestId = u64;
 
-/// Implementation of the [`TransportServer`](crate::transport::TransportServer) trait for HTTP.
+/// Implementation of the [`TransportServer`] trait for HTTP.
 pub struct HttpTransportServer {
 	/// Background thread that processes HTTP requests.
 	background_thread: background::BackgroundHttp,
@@ -66,14 +51,37 @@ pub struct HttpTransportServer {
 	/// Local address of the server.
 	local_addr: SocketAddr,
 
+	/// Metrics of the server.
+	metrics: ServerMetrics,
+
 	/// Next identifier to use when inserting an element in `requests`.
 	next_request_id: u64,
 
 	/// The identifier is linearly increasing and is never leaked on the wire or outside of this
 	/// module. Therefore there is no risk of hash collision and using a `FnvHashMap` is safe.
-	requests: FnvHashMap<u64, oneshot::Sender<hyper::Response<hyper::Body>>>,
+	/// Each request is associated with where to send its response and with its context.
+	requests: FnvHashMap<u64, (oneshot::Sender<hyper::Response<hyper::Body>>, RequestContext)>,
 }
 
+/// Builder for a [`HttpTransportServer`].
+pub struct HttpTransportServerBuilder {
+	/// IP address to try to bind to.
+	bind: SocketAddr,
+	/// Configuration of the server.
+	config: HttpConfig,
+	/// Access control list.
+	access_control: AccessControl,
+	/// Runtime to run the server on, if any.
+	tokio_handle: Option<tokio::runtime::Handle>,
+	/// Path on which to serve the metrics, if any.
+	metrics_path: Option<String>,
+	/// Certificate and key to encrypt the connections with, if any.
+	tls: Option<TlsServerConfig>,
+}
+
+/// Prefix of the names of the metrics of the HTTP server.
+const METRICS_NAMESPACE: &str = "jsonrpsee_http";
+
 impl HttpTransportServer {
 	/// Tries to start an HTTP server that listens on the given address.
 	///
@@ -89,7 +97,13 @@ impl HttpTransportServer {
 		config: HttpConfig,
 	) -> Result<HttpTransportServer, Box<dyn error::Error + Send + Sync>> {
 		let (background_thread, local_addr) = background::BackgroundHttp::bind(addr, config).await?;
-		Ok(HttpTransportServer { background_thread, local_addr, requests: FnvHashMap::default(), next_request_id: 0 })
+		Ok(HttpTransportServer {
+			background_thread,
+			local_addr,
+			metrics: ServerMetrics::new(METRICS_NAMESPACE),
+			requests: FnvHashMap::default(),
+			next_request_id: 0,
+		})
 	}
 
 	/// Tries to start an HTTP server that listens on the given address with an access control list.
@@ -98,9 +112,19 @@ impl HttpTransportServer {
 		access_control: AccessControl,
 		config: HttpConfig,
 	) -> Result<HttpTransportServer, Box<dyn error::Error + Send + Sync>> {
-		let (background_thread, local_addr) =
-			background::BackgroundHttp::bind_with_acl(addr, access_control, config).await?;
-		Ok(HttpTransportServer { background_thread, local_addr, requests: Default::default(), next_request_id: 0 })
+		HttpTransportServer::builder(*addr, config).access_control(access_control).build().await
+	}
+
+	/// Creates a new [`HttpTransportServerBuilder`] containing the given address and configuration.
+	pub fn builder(bind: SocketAddr, config: HttpConfig) -> HttpTransportServerBuilder {
+		HttpTransportServerBuilder {
+			bind,
+			config,
+			access_control: AccessControl::default(),
+			tokio_handle: None,
+			metrics_path: None,
+			tls: None,
+		}
 	}
 
 	/// Returns the address we are actually listening on, which might be different from the one
@@ -108,14 +132,82 @@ impl HttpTransportServer {
 	pub fn local_addr(&self) -> &SocketAddr {
 		&self.local_addr
 	}
+
+	/// Returns a handle that allows to stop the server.
+	pub fn stop_handle(&self) -> StopHandle {
+		self.background_thread.stop_handle()
+	}
+
+	/// Returns the metrics of the server.
+	pub fn metrics(&self) -> &ServerMetrics {
+		&self.metrics
+	}
 }
 
-// former `TransportServer trait impl`
-impl HttpTransportServer {
+impl HttpTransportServerBuilder {
+	/// Sets the access control list of the server.
+	pub fn access_control(mut self, access_control: AccessControl) -> Self {
+		self.access_control = access_control;
+		self
+	}
+
+	/// Serves the requests as tasks of the given tokio runtime.
+	///
+	/// By default, the server spawns a dedicated thread running a single-threaded tokio runtime.
+	pub fn tokio_handle(mut self, handle: tokio::runtime::Handle) -> Self {
+		self.tokio_handle = Some(handle);
+		self
+	}
+
+	/// Serves the metrics of the server, in the Prometheus text format, to HTTP `GET` requests
+	/// on the given path (for example `/metrics`).
+	///
+	/// By default, the metrics aren't served.
+	pub fn metrics_path(mut self, path: impl Into<String>) -> Self {
+		self.metrics_path = Some(path.into());
+		self
+	}
+
+	/// Only accepts TLS connections, using the given certificate and key. HTTP/2 and HTTP/1.1
+	/// are offered to the clients through ALPN.
+	///
+	/// By default, the server accepts plain text connections.
+	pub fn tls(mut self, config: TlsServerConfig) -> Self {
+		self.tls = Some(config);
+		self
+	}
+
+	/// Tries to start the server.
+	///
+	/// Returns an error if we fail to start listening, which generally happens if the port is
+	/// already occupied.
+	pub async fn build(self) -> Result<HttpTransportServer, Box<dyn error::Error + Send + Sync>> {
+		let metrics = ServerMetrics::new(METRICS_NAMESPACE);
+		let endpoint = self.metrics_path.map(|path| background::MetricsEndpoint { path, metrics: metrics.clone() });
+		let (background_thread, local_addr) = background::BackgroundHttp::bind_with_acl(
+			&self.bind,
+			self.access_control,
+			self.config,
+			self.tokio_handle,
+			endpoint,
+			self.tls,
+		)
+		.await?;
+		Ok(HttpTransportServer {
+			background_thread,
+			local_addr,
+			metrics,
+			requests: Default::default(),
+			next_request_id: 0,
+		})
+	}
+}
+
+impl TransportServer for HttpTransportServer {
+	type RequestId = RequestId;
+
 	/// Returns the next event that the raw server wants to notify us.
-	pub fn next_request<'a>(
-		&'a mut self,
-	) -> Pin<Box<dyn Future<Output = TransportServerEvent<RequestId>> + Send + 'a>> {
+	fn next_request<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = TransportServerEvent<RequestId>> + Send + 'a>> {
 		Box::pin(async move {
 			let request = match self.background_thread.next().await {
 				Ok(r) => r,
@@ -138,7 +230,7 @@ impl HttpTransportServer {
 				id
 			};
 
-			self.requests.insert(request_id, request.send_back);
+			self.requests.insert(request_id, (request.send_back, request.context));
 
 			// Every 128 requests, we call `shrink_to_fit` on the list for a general cleanup.
 			if request_id % 128 == 0 {
@@ -164,14 +256,14 @@ impl HttpTransportServer {
 	/// >           use this `Future` to send back a TCP message, because if the remote is
 	/// >           unresponsive and the buffers full, the `Future` would then wait for a long time.
 	///
-	pub fn finish<'a>(
+	fn finish<'a>(
 		&'a mut self,
 		request_id: &'a RequestId,
 		response: Option<&'a jsonrpc::Response>,
 	) -> Pin<Box<dyn Future<Output = Result<(), ()>> + Send + 'a>> {
 		Box::pin(async move {
 			let send_back = match self.requests.remove(request_id) {
-				Some(rq) => rq,
+				Some((rq, _)) => rq,
 				None => return Err(()),
 			};
 
@@ -198,6 +290,18 @@ impl HttpTransportServer {
 			Ok(())
 		})
 	}
+
+	/// Returns the context of a request that hasn't been answered yet.
+	///
+	/// The id of the returned context is always `Null`, as a single HTTP request might contain a
+	/// batch of JSON-RPC requests.
+	fn request_context(&self, request_id: &RequestId) -> Option<&RequestContext> {
+		self.requests.get(request_id).map(|(_, context)| context)
+	}
+
+	fn metrics(&self) -> &ServerMetrics {
+		&self.metrics
+	}
 }
 
 #[cfg(test)]
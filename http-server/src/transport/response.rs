@@ -71,6 +71,24 @@ pub fn parse_error() -> hyper::Response<hyper::Body> {
 		.expect("Unable to parse response body for type conversion")
 }
 
+/// Create a response containing metrics in the Prometheus text exposition format.
+pub fn metrics(encoded: String) -> hyper::Response<hyper::Body> {
+	hyper::Response::builder()
+		.status(hyper::StatusCode::OK)
+		.header("Content-Type", hyper::header::HeaderValue::from_static("text/plain; version=0.0.4"))
+		.body(hyper::Body::from(encoded))
+		.expect("Unable to parse response body for type conversion")
+}
+
+/// Create an empty response to a CORS preflight request.
+pub fn cors_preflight() -> hyper::Response<hyper::Body> {
+	hyper::Response::builder()
+		.status(hyper::StatusCode::OK)
+		.header("Allow", hyper::header::HeaderValue::from_static("OPTIONS, POST"))
+		.body(hyper::Body::empty())
+		.expect("Unable to parse response body for type conversion")
+}
+
 /// Create a response for disallowed method used.
 pub fn method_not_allowed() -> hyper::Response<hyper::Body> {
 	from_template(
@@ -0,0 +1,160 @@
+use crate::transport::IpcTransportClient;
+use futures::future::BoxFuture;
+use jsonrpsee_types::{
+	client::{batch_results, parse_batch_results, BatchEntry, Client},
+	error::Error,
+	ipc::IpcConfig,
+	jsonrpc::{self, JsonValue},
+};
+
+use std::convert::TryInto;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// JSON-RPC IPC Client that provides functionality to perform method calls and notifications
+/// through a Unix domain socket.
+pub struct IpcClient {
+	/// IPC transport client.
+	transport: IpcTransportClient,
+	/// Request ID that wraps around when overflowing.
+	request_id: AtomicU64,
+}
+
+impl IpcClient {
+	/// Connects to the server listening on the given socket file.
+	///
+	/// Fails when the server can't be reached.
+	pub async fn new(path: impl AsRef<Path>, config: IpcConfig) -> Result<Self, Error> {
+		let transport = IpcTransportClient::new(path, config).await.map_err(|e| Error::TransportError(Box::new(e)))?;
+		Ok(Self { transport, request_id: AtomicU64::new(0) })
+	}
+
+	/// Send a notification to the server.
+	pub async fn notification(
+		&self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> Result<(), Error> {
+		let request = jsonrpc::Request::Single(jsonrpc::Call::Notification(jsonrpc::Notification {
+			jsonrpc: jsonrpc::Version::V2,
+			method: method.into(),
+			params: params.into(),
+		}));
+
+		self.transport.send_notification(request).await.map_err(|e| Error::TransportError(Box::new(e)))
+	}
+
+	/// Perform a request towards the server.
+	pub async fn request<Ret>(
+		&self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> Result<Ret, Error>
+	where
+		Ret: jsonrpc::DeserializeOwned,
+	{
+		// NOTE: `fetch_add` wraps on overflow which is intended.
+		let id = self.request_id.fetch_add(1, Ordering::SeqCst);
+		let request = jsonrpc::Request::Single(jsonrpc::Call::MethodCall(jsonrpc::MethodCall {
+			jsonrpc: jsonrpc::Version::V2,
+			method: method.into(),
+			params: params.into(),
+			id: jsonrpc::Id::Num(id),
+		}));
+
+		let response = self
+			.transport
+			.send_request_and_wait_for_response(request)
+			.await
+			.map_err(|e| Error::TransportError(Box::new(e)))?;
+
+		let json_value = match response {
+			jsonrpc::Response::Single(rp) => Self::process_response(rp, id)?,
+			// Server should not send batch response to a single request.
+			jsonrpc::Response::Batch(_rps) => {
+				return Err(Error::Custom("Server replied with batch response to a single request".to_string()))
+			}
+			// Server should not reply to a Notification.
+			jsonrpc::Response::Notif(_) | jsonrpc::Response::SubscriptionClosed(_) => {
+				return Err(Error::Custom(format!("Server replied with notification response to request ID: {}", id)))
+			}
+		};
+		jsonrpc::from_value(json_value).map_err(Error::ParseError)
+	}
+
+	/// Send a batch of method calls and notifications to the server in a single message.
+	///
+	/// The results are the undecoded `JsonValue`s, see [`Client::batch_request`] for how they
+	/// relate to the entries of `batch`.
+	pub async fn batch_request(&self, batch: Vec<BatchEntry>) -> Result<Vec<Result<JsonValue, Error>>, Error> {
+		// An empty batch is an invalid request.
+		if batch.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		let mut ids = Vec::new();
+		let calls: Vec<_> = batch
+			.into_iter()
+			.map(|entry| {
+				if let BatchEntry::Notification { .. } = entry {
+					return entry.into_call(0);
+				}
+				// NOTE: `fetch_add` wraps on overflow which is intended.
+				let id = self.request_id.fetch_add(1, Ordering::SeqCst);
+				ids.push(id);
+				entry.into_call(id)
+			})
+			.collect();
+		let request = jsonrpc::Request::Batch(calls);
+
+		// The server doesn't answer a batch made only of notifications.
+		if ids.is_empty() {
+			self.transport.send_notification(request).await.map_err(|e| Error::TransportError(Box::new(e)))?;
+			return Ok(Vec::new());
+		}
+
+		let response = self
+			.transport
+			.send_request_and_wait_for_response(request)
+			.await
+			.map_err(|e| Error::TransportError(Box::new(e)))?;
+		batch_results(ids, response)
+	}
+
+	fn process_response(response: jsonrpc::Output, expected_id: u64) -> Result<JsonValue, Error> {
+		match response.id() {
+			jsonrpc::Id::Num(n) if n == &expected_id => response.try_into().map_err(Error::Request),
+			_ => Err(Error::InvalidRequestId),
+		}
+	}
+}
+
+impl Client for IpcClient {
+	fn notification<'a>(
+		&'a self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> BoxFuture<'a, Result<(), Error>> {
+		let (method, params) = (method.into(), params.into());
+		Box::pin(IpcClient::notification(self, method, params))
+	}
+
+	fn request<'a, T>(
+		&'a self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> BoxFuture<'a, Result<T, Error>>
+	where
+		T: jsonrpc::DeserializeOwned + Send + 'a,
+	{
+		let (method, params) = (method.into(), params.into());
+		Box::pin(IpcClient::request(self, method, params))
+	}
+
+	fn batch_request<'a, T>(&'a self, batch: Vec<BatchEntry>) -> BoxFuture<'a, Result<Vec<Result<T, Error>>, Error>>
+	where
+		T: jsonrpc::DeserializeOwned + Send + 'a,
+	{
+		Box::pin(async move { IpcClient::batch_request(self, batch).await.map(parse_batch_results) })
+	}
+}
@@ -0,0 +1,13 @@
+// Unix domain sockets only exist on Unix platforms.
+#![cfg(unix)]
+
+mod client;
+mod transport;
+
+#[cfg(test)]
+mod tests;
+
+pub use client::IpcClient;
+pub use jsonrpsee_types::client::{BatchEntry, Client};
+pub use jsonrpsee_types::ipc::{Framing, IpcConfig};
+pub use transport::IpcTransportClient;
@@ -0,0 +1,102 @@
+use crate::client::IpcClient;
+use crate::transport::Error as TransportError;
+use jsonrpsee_types::{
+	error::Error,
+	ipc::IpcConfig,
+	jsonrpc::{self, ErrorCode, JsonValue, Params},
+};
+
+use jsonrpsee_test_utils::helpers::*;
+use jsonrpsee_test_utils::ipc::{ipc_server_with_hardcoded_response, temp_socket_path};
+use jsonrpsee_test_utils::types::Id;
+
+use async_std::os::unix::net::UnixListener;
+use futures::future;
+use std::time::Duration;
+
+#[tokio::test]
+async fn method_call_works() {
+	let result = run_request_with_response("method_call_works", ok_response("hello".into(), Id::Num(0))).await.unwrap();
+	assert_eq!(JsonValue::String("hello".into()), result);
+}
+
+#[tokio::test]
+async fn notification_works() {
+	let path = temp_socket_path("client_notification_works");
+	ipc_server_with_hardcoded_response(&path, ok_response("hello".into(), Id::Num(0))).await;
+	let client = IpcClient::new(&path, IpcConfig::default()).await.unwrap();
+	client
+		.notification("i_dont_care_about_the_response_because_the_server_should_not_respond", Params::None)
+		.await
+		.unwrap();
+	// The notification doesn't leave a response behind for the next request.
+	assert_eq!(
+		client.request::<JsonValue>("say_hello", Params::None).await.unwrap(),
+		JsonValue::String("hello".into())
+	);
+}
+
+#[tokio::test]
+async fn connection_refused() {
+	let path = temp_socket_path("client_connection_refused");
+	assert!(matches!(IpcClient::new(&path, IpcConfig::default()).await, Err(Error::TransportError(_))));
+}
+
+#[tokio::test]
+async fn response_with_wrong_id() {
+	let err = run_request_with_response("response_with_wrong_id", ok_response("hello".into(), Id::Num(99)))
+		.await
+		.unwrap_err();
+	assert!(matches!(err, Error::InvalidRequestId));
+}
+
+#[tokio::test]
+async fn response_method_not_found() {
+	let err = run_request_with_response("response_method_not_found", method_not_found(Id::Num(0))).await.unwrap_err();
+	assert_jsonrpc_error_response(err, ErrorCode::MethodNotFound, METHOD_NOT_FOUND.into());
+}
+
+#[tokio::test]
+async fn response_too_large() {
+	let path = temp_socket_path("client_response_too_large");
+	ipc_server_with_hardcoded_response(&path, ok_response("a".repeat(128).into(), Id::Num(0))).await;
+	let config = IpcConfig { max_message_size: 100, ..Default::default() };
+	let client = IpcClient::new(&path, config).await.unwrap();
+	assert!(matches!(client.request::<JsonValue>("say_hello", Params::None).await, Err(Error::TransportError(_))));
+}
+
+#[tokio::test]
+async fn cancelled_request_poisons_the_connection() {
+	let path = temp_socket_path("client_cancelled_request_poisons_the_connection");
+	let listener = UnixListener::bind(&path).await.unwrap();
+	async_std::task::spawn(async move {
+		// Accepts the connection and never answers.
+		let (_socket, _) = listener.accept().await.unwrap();
+		future::pending::<()>().await;
+	});
+
+	let client = IpcClient::new(&path, IpcConfig::default()).await.unwrap();
+	let cancelled =
+		async_std::future::timeout(Duration::from_millis(100), client.request::<JsonValue>("say_hello", Params::None));
+	assert!(cancelled.await.is_err());
+
+	let err = client.request::<JsonValue>("say_hello", Params::None).await.unwrap_err();
+	assert!(matches!(err, Error::TransportError(e) if matches!(e.downcast_ref(), Some(TransportError::Poisoned))));
+}
+
+async fn run_request_with_response(name: &str, response: String) -> Result<JsonValue, Error> {
+	let path = temp_socket_path(&format!("client_{}", name));
+	ipc_server_with_hardcoded_response(&path, response).await;
+	let client = IpcClient::new(&path, IpcConfig::default()).await?;
+	client.request("say_hello", Params::None).await
+}
+
+fn assert_jsonrpc_error_response(response: Error, code: ErrorCode, message: String) {
+	let expected = jsonrpc::Error { code, message, data: None };
+	match response {
+		Error::Request(err) => {
+			assert_eq!(err, expected);
+		}
+		e => panic!("Expected error: \"{}\", got: {:?}", expected, e),
+	};
+}
@@ -0,0 +1,144 @@
+use jsonrpsee_types::{error::GenericTransportError, ipc::IpcConfig, jsonrpc};
+use jsonrpsee_utils::ipc::{read_message, write_message};
+
+use async_std::os::unix::net::UnixStream;
+use futures::{
+	io::BufReader,
+	lock::{Mutex, MutexGuard},
+};
+use std::{io, path::Path};
+use thiserror::Error;
+
+/// Client connected to a JSON-RPC server through a Unix domain socket.
+///
+/// Requests are sent one after the other on the same connection, each of them waiting for the
+/// response of the previous one.
+///
+/// If a call is cancelled while it is writing the request or reading the response, the position
+/// of the next message on the socket is lost. The connection is then unusable and all later calls
+/// fail with [`Error::Poisoned`].
+#[derive(Debug)]
+pub struct IpcTransportClient {
+	/// Connection to the server.
+	connection: Mutex<Connection>,
+	/// Configuration of the client.
+	config: IpcConfig,
+}
+
+#[derive(Debug)]
+struct Connection {
+	/// Reading side of the socket.
+	reader: BufReader<UnixStream>,
+	/// Writing side of the socket.
+	writer: UnixStream,
+	/// True while a message is being written or read, and afterwards if that has been
+	/// interrupted.
+	poisoned: bool,
+}
+
+impl IpcTransportClient {
+	/// Connects to the server listening on the given socket file.
+	pub async fn new(path: impl AsRef<Path>, config: IpcConfig) -> Result<Self, Error> {
+		let socket = UnixStream::connect(path.as_ref()).await.map_err(Error::Io)?;
+		let connection = Connection { reader: BufReader::new(socket.clone()), writer: socket, poisoned: false };
+		Ok(IpcTransportClient { connection: Mutex::new(connection), config })
+	}
+
+	/// Send notification.
+	pub async fn send_notification(&self, request: jsonrpc::Request) -> Result<(), Error> {
+		let mut connection = self.lock_connection().await?;
+		self.send_request(&mut connection, &request).await
+	}
+
+	/// Send request and wait for response.
+	pub async fn send_request_and_wait_for_response(
+		&self,
+		request: jsonrpc::Request,
+	) -> Result<jsonrpc::Response, Error> {
+		let mut connection = self.lock_connection().await?;
+		self.send_request(&mut connection, &request).await?;
+		let body = self.read_response(&mut connection).await?;
+
+		let response: jsonrpc::Response = jsonrpc::from_slice(&body).map_err(Error::Parse)?;
+		log::debug!("recv: {}", jsonrpc::to_string(&response).expect("request valid JSON; qed"));
+		Ok(response)
+	}
+
+	/// Waits for the previous calls to finish, and checks that none of them has left the
+	/// connection in the middle of a message.
+	async fn lock_connection(&self) -> Result<MutexGuard<'_, Connection>, Error> {
+		let connection = self.connection.lock().await;
+		if connection.poisoned {
+			return Err(Error::Poisoned);
+		}
+		Ok(connection)
+	}
+
+	/// Serializes and sends a request.
+	async fn send_request(&self, connection: &mut Connection, request: &jsonrpc::Request) -> Result<(), Error> {
+		let body = jsonrpc::to_vec(request).map_err(Error::Serialization)?;
+		log::debug!("send: {}", request);
+
+		if body.len() > self.config.max_message_size as usize {
+			return Err(Error::RequestTooLarge);
+		}
+
+		// Stays set if this future is dropped or fails before the request has been written entirely.
+		connection.poisoned = true;
+		write_message(&mut connection.writer, self.config.framing, &body).await.map_err(Error::Io)?;
+		connection.poisoned = false;
+		Ok(())
+	}
+
+	/// Reads the next message sent by the server.
+	async fn read_response(&self, connection: &mut Connection) -> Result<Vec<u8>, Error> {
+		// Stays set if this future is dropped or fails before the response has been read entirely.
+		connection.poisoned = true;
+		let body = read_message(&mut connection.reader, self.config.framing, self.config.max_message_size)
+			.await?
+			.ok_or(Error::Closed)?;
+		connection.poisoned = false;
+		Ok(body)
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+	/// Error while reading from or writing to the socket.
+	#[error("Error on the IPC socket")]
+	Io(#[source] io::Error),
+
+	/// The server has closed the connection.
+	#[error("The server has closed the connection")]
+	Closed,
+
+	/// A previous call has been cancelled or has failed in the middle of a message, and the
+	/// connection can't be used anymore.
+	#[error("The connection has been left in an inconsistent state by a previous call")]
+	Poisoned,
+
+	/// Error while serializing the request.
+	#[error("Error while serializing the request")]
+	Serialization(#[source] serde_json::error::Error),
+
+	/// Failed to parse the JSON returned by the server into a JSON-RPC response.
+	#[error("Error while parsing the response")]
+	Parse(#[source] serde_json::error::Error),
+
+	/// Request too large.
+	#[error("The request was too large")]
+	RequestTooLarge,
+
+	/// Response too large.
+	#[error("The response was too large")]
+	ResponseTooLarge,
+}
+
+impl From<GenericTransportError<io::Error>> for Error {
+	fn from(err: GenericTransportError<io::Error>) -> Self {
+		match err {
+			GenericTransportError::TooLarge => Self::ResponseTooLarge,
+			GenericTransportError::Inner(e) => Self::Io(e),
+		}
+	}
+}
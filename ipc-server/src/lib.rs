@@ -0,0 +1,42 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+// Unix domain sockets only exist on Unix platforms.
+#![cfg(unix)]
+
+mod raw;
+mod server;
+mod transport;
+
+#[cfg(test)]
+mod tests;
+
+pub use jsonrpsee_types::ipc::{Framing, IpcConfig};
+pub use jsonrpsee_types::server::{RegisteredMethod, RegisteredNotification};
+pub use raw::RawServer as IpcRawServer;
+pub use raw::RawServerEvent as IpcRawServerEvent;
+pub use server::{Builder as IpcServerBuilder, Server as IpcServer};
+pub use transport::{IpcTransportServer, IpcTransportServerBuilder};
@@ -0,0 +1,6 @@
+use crate::transport::{IpcRequestId, IpcTransportServer};
+
+/// [`RawServer`](jsonrpsee_types::server::RawServer) over IPC.
+pub type RawServer = jsonrpsee_types::server::RawServer<IpcTransportServer>;
+/// Event generated by a [`RawServer`].
+pub type RawServerEvent<'a> = jsonrpsee_types::server::RawServerEvent<'a, IpcRequestId>;
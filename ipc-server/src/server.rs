@@ -0,0 +1,267 @@
+// Copyright 2019-2020 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use crate::transport::IpcTransportServer;
+use jsonrpsee_types::{
+	error::Error,
+	ipc::IpcConfig,
+	jsonrpc::{self, DeserializeOwned, Serialize},
+	server::{
+		Frontend, MethodConfig, Middleware, RegisteredMethod, RegisteredNotification, RequestContext, RpcModule,
+		ServerMetrics, Spawner,
+	},
+};
+
+use futures::prelude::*;
+use std::{
+	error,
+	path::{Path, PathBuf},
+	sync::Arc,
+	time::Duration,
+};
+
+/// Server that can be cloned.
+///
+/// > **Note**: This struct is designed to be easy to use, but it works by maintaining a background
+/// >           task running in parallel. If this is not desirable, you are encouraged to use the
+/// >           [`RawServer`](crate::IpcRawServer) struct instead.
+#[derive(Clone)]
+pub struct Server {
+	/// Path of the socket file of the transport server, or `None` if it serves a single stream.
+	local_path: Option<PathBuf>,
+	/// Handle to the background task.
+	frontend: Frontend,
+	/// Metrics of the server.
+	metrics: ServerMetrics,
+	/// Resolves once the transport server has been closed.
+	closed: future::Shared<future::BoxFuture<'static, ()>>,
+}
+
+/// Builder for a [`Server`].
+pub struct Builder {
+	/// Path of the socket file to create.
+	path: PathBuf,
+	/// Configuration of the server.
+	config: IpcConfig,
+	/// Permissions to set on the socket file, if any.
+	permissions: Option<u32>,
+}
+
+impl Server {
+	/// Initializes a new server listening on the given socket file.
+	pub async fn new(path: impl AsRef<Path>, config: IpcConfig) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+		Server::builder(path).config(config).build().await
+	}
+
+	/// Creates a new [`Builder`] that listens on the given socket file.
+	pub fn builder(path: impl AsRef<Path>) -> Builder {
+		Builder { path: path.as_ref().to_owned(), config: IpcConfig::default(), permissions: None }
+	}
+
+	/// Initializes a new server that processes the requests received on the standard input of the
+	/// process and writes the responses to its standard output.
+	///
+	/// This is meant for programs spawned by another process, which usually uses
+	/// [`Framing::ContentLength`](jsonrpsee_types::ipc::Framing::ContentLength) or
+	/// [`Framing::Newline`](jsonrpsee_types::ipc::Framing::Newline). Nothing else must be written
+	/// to the standard output while the server is running. See also [`closed`](Server::closed).
+	pub fn stdio(config: IpcConfig) -> Self {
+		Server::start(IpcTransportServer::stdio(config))
+	}
+
+	/// Initializes a new server that processes the requests received on `reader` and writes the
+	/// responses to `writer`, as a single connection.
+	pub fn from_stream<R, W>(reader: R, writer: W, config: IpcConfig) -> Self
+	where
+		R: AsyncRead + Unpin + Send + 'static,
+		W: AsyncWrite + Unpin + Send + 'static,
+	{
+		Server::start(IpcTransportServer::from_stream(reader, writer, config))
+	}
+
+	/// Spawns the background task processing the requests received by `transport_server`.
+	fn start(transport_server: IpcTransportServer) -> Self {
+		let local_path = transport_server.local_path().map(Path::to_owned);
+		let metrics = transport_server.metrics().clone();
+		let closed = transport_server.closed().boxed().shared();
+		let spawner: Spawner = Arc::new(|future| {
+			async_std::task::spawn(future);
+		});
+
+		Server { local_path, frontend: Frontend::start(transport_server, spawner), metrics, closed }
+	}
+
+	/// Path of the socket file of the transport server, or `None` if the server processes a
+	/// single stream.
+	pub fn local_path(&self) -> Option<&Path> {
+		self.local_path.as_deref()
+	}
+
+	/// Returns a future that resolves once the server has been stopped or, for a server that
+	/// processes a single stream such as [`stdio`](Server::stdio), once the end of the stream has
+	/// been reached and the requests received before have been answered.
+	pub fn closed(&self) -> impl Future<Output = ()> {
+		self.closed.clone()
+	}
+
+	/// Returns the metrics of the server. See [`ServerMetrics::encode`] to expose them.
+	pub fn metrics(&self) -> &ServerMetrics {
+		&self.metrics
+	}
+
+	/// Stops the server gracefully.
+	///
+	/// No new requests are processed, and the server waits up to `deadline` for the requests
+	/// that are being handled to be answered. Afterwards, the responses are sent out, every
+	/// connection is closed and the socket file is removed.
+	///
+	/// Returns an error if the server was already stopped.
+	pub async fn stop(&self, deadline: Duration) -> Result<(), Error> {
+		self.frontend.stop(async_std::task::sleep(deadline)).await
+	}
+
+	/// Adds a middleware, called around every method call made to the server.
+	///
+	/// See [`Frontend::add_middleware`] for more information.
+	pub fn add_middleware(&self, middleware: impl Middleware) -> Result<(), Error> {
+		self.frontend.add_middleware(middleware)
+	}
+
+	/// Registers a notification method name towards the server.
+	///
+	/// See [`Frontend::register_notification`] for more information.
+	pub fn register_notification(
+		&self,
+		method_name: String,
+		allow_losses: bool,
+	) -> Result<RegisteredNotification, Error> {
+		self.frontend.register_notification(method_name, allow_losses)
+	}
+
+	/// Registers a method towards the server.
+	///
+	/// See [`Frontend::register_method`] for more information.
+	pub fn register_method(&self, method_name: String) -> Result<RegisteredMethod, Error> {
+		self.frontend.register_method(method_name)
+	}
+
+	/// Registers a method towards the server, with a custom configuration for its queue of
+	/// requests.
+	///
+	/// See [`Frontend::register_method_with_config`] for more information.
+	pub fn register_method_with_config(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+	) -> Result<RegisteredMethod, Error> {
+		self.frontend.register_method_with_config(method_name, config)
+	}
+
+	/// Registers a method towards the server and lets the server drive its handler.
+	///
+	/// See [`Frontend::register_async_method`] for more information.
+	pub fn register_async_method<F, Fut, P, T, E>(&self, method_name: String, callback: F) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.frontend.register_async_method(method_name, callback)
+	}
+
+	/// Registers a method towards the server and lets the server drive its handler, with a custom
+	/// configuration for its queue of requests.
+	///
+	/// See [`Frontend::register_async_method_with_config`] for more information.
+	pub fn register_async_method_with_config<F, Fut, P, T, E>(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.frontend.register_async_method_with_config(method_name, config, callback)
+	}
+
+	/// Same as [`register_async_method`](Server::register_async_method), except that `callback`
+	/// is also passed the [`RequestContext`] of each request.
+	pub fn register_async_method_with_context<F, Fut, P, T, E>(
+		&self,
+		method_name: String,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P, RequestContext) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.frontend.register_async_method_with_context(method_name, callback)
+	}
+
+	/// Registers all the methods of `module` towards the server.
+	///
+	/// The subscriptions of the module are ignored, as the IPC server doesn't support them. See
+	/// [`Frontend::register_module`] for more information.
+	pub fn register_module(&self, module: &RpcModule) -> Result<(), Error> {
+		self.frontend.register_module(module)
+	}
+}
+
+impl Builder {
+	/// Sets the configuration of the server.
+	pub fn config(mut self, config: IpcConfig) -> Self {
+		self.config = config;
+		self
+	}
+
+	/// Sets the permissions of the socket file, for example `0o600` so that only the user running
+	/// the server can connect.
+	///
+	/// By default, the permissions depend on the umask of the process.
+	pub fn permissions(mut self, mode: u32) -> Self {
+		self.permissions = Some(mode);
+		self
+	}
+
+	/// Starts the server.
+	pub async fn build(self) -> Result<Server, Box<dyn error::Error + Send + Sync>> {
+		let mut transport_server = IpcTransportServer::builder(&self.path).config(self.config);
+		if let Some(mode) = self.permissions {
+			transport_server = transport_server.permissions(mode);
+		}
+		Ok(Server::start(transport_server.build().await?))
+	}
+}
@@ -0,0 +1,211 @@
+#![cfg(test)]
+
+use crate::{Framing, IpcConfig, IpcServer};
+use futures::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use jsonrpsee_ipc_client::IpcClient;
+use jsonrpsee_test_utils::helpers::*;
+use jsonrpsee_test_utils::ipc::{ipc_request, temp_socket_path};
+use jsonrpsee_test_utils::types::Id;
+use jsonrpsee_types::{
+	error::Error,
+	jsonrpc::{self, JsonValue, Params},
+	server::{MethodConfig, OverflowPolicy, RequestContext, RpcModule},
+};
+use std::os::unix::fs::PermissionsExt as _;
+use std::time::Duration;
+
+async fn server(name: &str, config: IpcConfig) -> IpcServer {
+	let server = IpcServer::new(temp_socket_path(name), config).await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	server
+		.register_async_method("add".to_owned(), |(a, b): (u64, u64)| async move { Ok::<_, jsonrpc::Error>(a + b) })
+		.unwrap();
+	server
+}
+
+#[tokio::test]
+async fn single_method_call_works() {
+	let server = server("single_method_call_works", IpcConfig::default()).await;
+	let client = IpcClient::new(server.local_path().unwrap(), IpcConfig::default()).await.unwrap();
+
+	for _ in 0..10 {
+		let response: JsonValue = client.request("say_hello", Params::None).await.unwrap();
+		assert_eq!(response, JsonValue::String("hello".to_owned()));
+		let response: JsonValue = client.request("add", Params::Array(vec![1.into(), 2.into()])).await.unwrap();
+		assert_eq!(response, JsonValue::Number(3.into()));
+	}
+}
+
+#[tokio::test]
+async fn length_delimited_framing_works() {
+	let config = IpcConfig { framing: Framing::LengthDelimited, ..Default::default() };
+	let server = server("length_delimited_framing_works", config).await;
+	let client = IpcClient::new(server.local_path().unwrap(), config).await.unwrap();
+
+	let response: JsonValue = client.request("say_hello", Params::None).await.unwrap();
+	assert_eq!(response, JsonValue::String("hello".to_owned()));
+}
+
+#[tokio::test]
+async fn batch_and_invalid_requests() {
+	let server = server("batch_and_invalid_requests", IpcConfig::default()).await;
+	let path = server.local_path().unwrap();
+
+	let req =
+		r#"[{"jsonrpc":"2.0","method":"add","params":[1, 2],"id":1},{"jsonrpc":"2.0","method":"say_hello","id":2}]"#;
+	let response = ipc_request(path, req).await.unwrap();
+	assert_eq!(response, r#"[{"jsonrpc":"2.0","result":3,"id":1},{"jsonrpc":"2.0","result":"hello","id":2}]"#);
+
+	let req = r#"{"jsonrpc":"2.0","method":"bar","id":1}"#;
+	assert_eq!(ipc_request(path, req).await.unwrap(), method_not_found(Id::Num(1)));
+
+	let req = r#"{"jsonrpc":"2.0","method":"add""#;
+	assert_eq!(ipc_request(path, req).await.unwrap(), parse_error(Id::Null));
+}
+
+#[tokio::test]
+async fn request_context_and_module_work() {
+	let server =
+		IpcServer::new(temp_socket_path("request_context_and_module_work"), IpcConfig::default()).await.unwrap();
+	server
+		.register_async_method_with_context("context".to_owned(), |_: (), context: RequestContext| async move {
+			Ok::<_, jsonrpc::Error>((context.id().clone(), context.connection_id().is_some()))
+		})
+		.unwrap();
+	let mut module = RpcModule::new();
+	module
+		.register_method("module_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello from module") })
+		.unwrap();
+	server.register_module(&module).unwrap();
+	assert!(matches!(server.register_module(&module), Err(Error::MethodAlreadyRegistered(_))));
+
+	let req = r#"{"jsonrpc":"2.0","method":"context","id":"abc"}"#;
+	let response = ipc_request(server.local_path().unwrap(), req).await.unwrap();
+	assert_eq!(response, ok_response(serde_json::json!(["abc", true]), Id::Str("abc".to_owned())));
+
+	let req = r#"{"jsonrpc":"2.0","method":"module_hello","id":1}"#;
+	let response = ipc_request(server.local_path().unwrap(), req).await.unwrap();
+	assert_eq!(response, ok_response(JsonValue::String("hello from module".to_owned()), Id::Num(1)));
+}
+
+#[tokio::test]
+async fn permissions_are_applied() {
+	let path = temp_socket_path("permissions_are_applied");
+	let _server = IpcServer::builder(&path).permissions(0o600).build().await.unwrap();
+	assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+}
+
+#[tokio::test]
+async fn stop_works() {
+	let server = server("stop_works", IpcConfig::default()).await;
+	let path = server.local_path().unwrap().to_owned();
+
+	let req = r#"{"jsonrpc":"2.0","method":"say_hello","id":1}"#;
+	let response = ipc_request(&path, req).await.unwrap();
+	assert_eq!(response, ok_response(JsonValue::String("hello".to_owned()), Id::Num(1)));
+
+	server.stop(Duration::from_secs(5)).await.unwrap();
+	assert!(matches!(server.stop(Duration::from_secs(5)).await, Err(Error::AlreadyStopped)));
+	// The socket file must have been removed.
+	assert!(!path.exists());
+	assert!(ipc_request(&path, req).await.is_err());
+}
+
+#[tokio::test]
+async fn method_queue_wait_policy() {
+	let server = server("method_queue_wait_policy", IpcConfig::default()).await;
+	let config = MethodConfig { queue_capacity: 1, overflow_policy: OverflowPolicy::Wait };
+	let mut wait = server.register_method_with_config("wait".to_owned(), config).unwrap();
+	let path = server.local_path().unwrap().to_owned();
+	let call = |id: u64| {
+		let req = format!(r#"{{"jsonrpc":"2.0","method":"wait","id":{}}}"#, id);
+		let path = path.clone();
+		tokio::spawn(async move { ipc_request(path, &req).await.unwrap() })
+	};
+
+	let first = call(1);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	let second = call(2);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+
+	// The other methods are served while the second request waits for room in the queue.
+	let req = r#"{"jsonrpc":"2.0","method":"say_hello","id":3}"#;
+	let response = tokio::time::timeout(Duration::from_secs(5), ipc_request(&path, req)).await.unwrap();
+	assert_eq!(response.unwrap(), ok_response(JsonValue::String("hello".to_owned()), Id::Num(3)));
+
+	// Both requests are processed.
+	wait.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	wait.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(first.await.unwrap(), ok_response(JsonValue::Null, Id::Num(1)));
+	assert_eq!(second.await.unwrap(), ok_response(JsonValue::Null, Id::Num(2)));
+}
+
+#[tokio::test]
+async fn stale_socket_file_is_replaced() {
+	let path = temp_socket_path("stale_socket_file_is_replaced");
+	drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+	assert!(path.exists());
+
+	let server = IpcServer::new(&path, IpcConfig::default()).await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let req = r#"{"jsonrpc":"2.0","method":"say_hello","id":1}"#;
+	let response = ipc_request(&path, req).await.unwrap();
+	assert_eq!(response, ok_response(JsonValue::String("hello".to_owned()), Id::Num(1)));
+}
+
+#[tokio::test]
+async fn requests_are_answered_after_end_of_input() {
+	let (ours, theirs) = async_std::os::unix::net::UnixStream::pair().unwrap();
+	let server = IpcServer::from_stream(theirs.clone(), theirs, IpcConfig::default());
+	server
+		.register_async_method("slow".to_owned(), |_: ()| async {
+			async_std::task::sleep(Duration::from_millis(100)).await;
+			Ok::<_, jsonrpc::Error>("done")
+		})
+		.unwrap();
+
+	let mut socket = ours.clone();
+	socket.write_all(b"{\"jsonrpc\":\"2.0\",\"method\":\"slow\",\"id\":1}\n").await.unwrap();
+	ours.shutdown(std::net::Shutdown::Write).unwrap();
+
+	let mut response = String::new();
+	let read = tokio::time::timeout(Duration::from_secs(5), socket.read_to_string(&mut response));
+	read.await.unwrap().unwrap();
+	assert_eq!(response, format!("{}\n", ok_response(JsonValue::String("done".to_owned()), Id::Num(1))));
+	tokio::time::timeout(Duration::from_secs(5), server.closed()).await.unwrap();
+}
+
+#[tokio::test]
+async fn content_length_stream_works() {
+	let (ours, theirs) = async_std::os::unix::net::UnixStream::pair().unwrap();
+	let config = IpcConfig { framing: Framing::ContentLength, ..Default::default() };
+	let server = IpcServer::from_stream(theirs.clone(), theirs, config);
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	assert!(server.local_path().is_none());
+
+	let mut reader = futures::io::BufReader::new(ours.clone());
+	let mut writer = ours.clone();
+	let req = r#"{"jsonrpc":"2.0","method":"say_hello","id":1}"#;
+	let message = format!("Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc\r\n\r\n{}", req.len(), req);
+	writer.write_all(message.as_bytes()).await.unwrap();
+
+	let response = ok_response(JsonValue::String("hello".to_owned()), Id::Num(1));
+	let mut expected = format!("Content-Length: {}\r\n\r\n{}", response.len(), response).into_bytes();
+	let mut received = vec![0; expected.len()];
+	reader.read_exact(&mut received).await.unwrap();
+	assert_eq!(received, expected);
+
+	// A notification isn't answered, so the next response is the one of the following request.
+	let notif = r#"{"jsonrpc":"2.0","method":"say_hello"}"#;
+	let message =
+		format!("Content-Length: {}\r\n\r\n{}Content-Length: {}\r\n\r\n{}", notif.len(), notif, req.len(), req);
+	writer.write_all(message.as_bytes()).await.unwrap();
+	reader.read_exact(&mut received).await.unwrap();
+	assert_eq!(received, expected);
+
+	// The server is closed once the end of the stream is reached.
+	ours.shutdown(std::net::Shutdown::Write).unwrap();
+	tokio::time::timeout(Duration::from_secs(5), server.closed()).await.unwrap();
+	expected.clear();
+	assert_eq!(reader.read_to_end(&mut expected).await.unwrap(), 0);
+}
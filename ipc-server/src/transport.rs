@@ -0,0 +1,567 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+use jsonrpsee_types::{
+	error::GenericTransportError,
+	ipc::IpcConfig,
+	jsonrpc,
+	server::{ConnectionExtensions, RequestContext, ServerMetrics, TransportServer, TransportServerEvent},
+};
+use jsonrpsee_utils::ipc::{read_message, write_message};
+
+use async_std::os::unix::net::{UnixListener, UnixStream};
+use futures::{
+	channel::{mpsc, oneshot},
+	io::BufReader,
+	prelude::*,
+};
+use std::{
+	collections::HashMap,
+	fmt, fs, io,
+	os::unix::fs::{FileTypeExt as _, PermissionsExt as _},
+	path::{Path, PathBuf},
+	pin::Pin,
+	sync::{atomic, Arc},
+};
+
+/// Implementation of a raw server for requests received over a Unix domain socket, or over a
+/// single byte stream such as the standard input and output of the process.
+//
+// # Implementation notes
+//
+// This is the same design as the WebSocket transport server: every connection accepted on the
+// listener is processed by a dedicated task, which reports the requests it receives through
+// [`IpcTransportServer::to_front`] and returns the list of its unanswered requests when it
+// finishes.
+pub struct IpcTransportServer {
+	/// Path of the socket file, or `None` if the server serves a single stream.
+	path: Option<PathBuf>,
+	/// Configuration of the server.
+	config: IpcConfig,
+	/// List of events to for `next_request` to immediately produce.
+	pending_events: Vec<TransportServerEvent<IpcRequestId>>,
+	/// Endpoint for incoming connections. `None` if the server has been closed.
+	listener: Option<UnixListener>,
+	/// Next identifier to assign to a request. Shared amongst all the tasks in the server so that
+	/// they all assign from the same pool.
+	next_request_id: Arc<atomic::AtomicU64>,
+	/// Next identifier to assign to a connection.
+	next_connection_id: u64,
+	/// Events received from connections.
+	from_connections: mpsc::Receiver<BackToFront>,
+	/// Sending side of [`IpcTransportServer::from_connections`]. Cloned in each member of
+	/// [`IpcTransportServer::connections_tasks`].
+	to_front: mpsc::Sender<BackToFront>,
+	/// For each request, where to send its response and its context.
+	to_connections: HashMap<IpcRequestId, (mpsc::Sender<FrontToBack>, RequestContext)>,
+	/// List of connections. Must be processed for the system to work. When a task finishes, it
+	/// returns the list of pending requests that should now be closed.
+	connections_tasks: stream::FuturesUnordered<Pin<Box<dyn Future<Output = Vec<IpcRequestId>> + Send>>>,
+	/// Sending side of [`IpcTransportServer::stop_rx`]. Taken when the server is closed.
+	stop_tx: Option<oneshot::Sender<()>>,
+	/// Resolves when the server is closed. Cloned in each member of
+	/// [`IpcTransportServer::connections_tasks`].
+	stop_rx: future::Shared<oneshot::Receiver<()>>,
+	/// Metrics of the server.
+	metrics: ServerMetrics,
+}
+
+/// Message sent from a per-connection task to the main frontend.
+enum BackToFront {
+	NewRequest {
+		id: IpcRequestId,
+		body: jsonrpc::Request,
+		sender: mpsc::Sender<FrontToBack>,
+		/// Information about the connection the request was received on.
+		context: RequestContext,
+	},
+}
+
+/// Message sent from the main frontend to a per-connection task.
+enum FrontToBack {
+	/// Send a payload to the client.
+	Send(Vec<u8>),
+	/// No more data concerning that request will be sent.
+	Finished(IpcRequestId),
+}
+
+/// Identifier for a request made to an IPC server.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct IpcRequestId(u64);
+
+/// Builder for an [`IpcTransportServer`].
+pub struct IpcTransportServerBuilder {
+	/// Path of the socket file to create.
+	path: PathBuf,
+	/// Configuration of the server.
+	config: IpcConfig,
+	/// Permissions to set on the socket file, if any.
+	permissions: Option<u32>,
+}
+
+impl IpcTransportServer {
+	/// Creates a new [`IpcTransportServerBuilder`] that listens on the given socket file.
+	pub fn builder(path: impl AsRef<Path>) -> IpcTransportServerBuilder {
+		IpcTransportServerBuilder { path: path.as_ref().to_owned(), config: IpcConfig::default(), permissions: None }
+	}
+
+	/// Creates a server that processes the requests received on `reader` and writes the responses
+	/// to `writer`, as a single connection.
+	///
+	/// The server is considered closed once `reader` reaches its end; see
+	/// [`closed`](IpcTransportServer::closed).
+	pub fn from_stream<R, W>(reader: R, writer: W, config: IpcConfig) -> IpcTransportServer
+	where
+		R: AsyncRead + Unpin + Send + 'static,
+		W: AsyncWrite + Unpin + Send + 'static,
+	{
+		let mut server = IpcTransportServer::new(None, None, config);
+		let context = RequestContext::new(ConnectionExtensions::default()).with_connection_id(0);
+		server.next_connection_id = 1;
+		server.metrics.connection_opened();
+		server.connections_tasks.push(
+			per_connection_task(
+				reader,
+				writer,
+				context,
+				config,
+				server.next_request_id.clone(),
+				server.to_front.clone(),
+				server.stop_rx.clone(),
+			)
+			.boxed(),
+		);
+		server
+	}
+
+	/// Creates a server that processes the requests received on the standard input of the process
+	/// and writes the responses to its standard output.
+	///
+	/// Nothing else must be written to the standard output while the server is running.
+	pub fn stdio(config: IpcConfig) -> IpcTransportServer {
+		IpcTransportServer::from_stream(async_std::io::stdin(), async_std::io::stdout(), config)
+	}
+
+	fn new(path: Option<PathBuf>, listener: Option<UnixListener>, config: IpcConfig) -> IpcTransportServer {
+		let connections_tasks = {
+			let futures = stream::FuturesUnordered::new();
+			// We push a dummy future in order for the `FuturesUnordered` to never produce `None`.
+			futures.push(
+				async move {
+					loop {
+						futures::pending!()
+					}
+				}
+				.boxed(),
+			);
+			futures
+		};
+
+		let (to_front, from_connections) = mpsc::channel(256);
+		let (stop_tx, stop_rx) = oneshot::channel();
+
+		IpcTransportServer {
+			path,
+			config,
+			pending_events: Vec::new(),
+			listener,
+			next_request_id: Arc::new(atomic::AtomicU64::new(1)),
+			next_connection_id: 0,
+			connections_tasks,
+			to_front,
+			from_connections,
+			to_connections: HashMap::new(),
+			stop_tx: Some(stop_tx),
+			stop_rx: stop_rx.shared(),
+			metrics: ServerMetrics::with_connections("jsonrpsee_ipc"),
+		}
+	}
+
+	/// Path of the socket file, or `None` if the server serves a single stream.
+	pub fn local_path(&self) -> Option<&Path> {
+		self.path.as_deref()
+	}
+
+	/// Returns a future that resolves once the server has been closed, either by calling
+	/// [`close`](IpcTransportServer::close) or, for a server built with
+	/// [`from_stream`](IpcTransportServer::from_stream), once the end of the stream is reached and
+	/// the requests received before have been answered.
+	pub fn closed(&self) -> impl Future<Output = ()> + Send + 'static {
+		self.stop_rx.clone().map(|_| ())
+	}
+
+	/// Returns the metrics of the server.
+	pub fn metrics(&self) -> &ServerMetrics {
+		&self.metrics
+	}
+}
+
+impl TransportServer for IpcTransportServer {
+	type RequestId = IpcRequestId;
+
+	/// Returns the next event that the raw server wants to notify us.
+	fn next_request<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = TransportServerEvent<IpcRequestId>> + Send + 'a>> {
+		Box::pin(async move {
+			loop {
+				if !self.pending_events.is_empty() {
+					return self.pending_events.remove(0);
+				} else {
+					self.pending_events.shrink_to_fit();
+				}
+
+				enum Event {
+					TaskFinished(Vec<IpcRequestId>),
+					NewConnection(UnixStream),
+					FromConnection(BackToFront),
+				}
+
+				let next = {
+					let next_connection = {
+						let listener = &self.listener;
+						async move {
+							let listener = match listener {
+								Some(listener) => listener,
+								// The server has been closed; no more connections are accepted.
+								None => future::pending().await,
+							};
+							loop {
+								if let Ok((connec, _)) = listener.accept().await {
+									break Event::NewConnection(connec);
+								}
+							}
+						}
+					};
+
+					let next_event = {
+						let from_connections = &mut self.from_connections;
+						async move { Event::FromConnection(from_connections.next().await.unwrap()) }
+					};
+
+					let next_finished_task = {
+						let connections_tasks = &mut self.connections_tasks;
+						async move { Event::TaskFinished(connections_tasks.next().await.unwrap()) }
+					};
+
+					futures::pin_mut!(next_connection, next_event, next_finished_task);
+					match future::select(future::select(next_connection, next_event), next_finished_task).await {
+						future::Either::Left((future::Either::Left((ev, _)), _)) => ev,
+						future::Either::Left((future::Either::Right((ev, _)), _)) => ev,
+						future::Either::Right((ev, _)) => ev,
+					}
+				};
+
+				match next {
+					Event::NewConnection(connec) => {
+						log::trace!("{:?}: new connection", self.next_request_id);
+						let context = RequestContext::new(ConnectionExtensions::default())
+							.with_connection_id(self.next_connection_id);
+						self.next_connection_id = self.next_connection_id.wrapping_add(1);
+						self.metrics.connection_opened();
+						self.connections_tasks.push(
+							per_connection_task(
+								connec.clone(),
+								connec,
+								context,
+								self.config,
+								self.next_request_id.clone(),
+								self.to_front.clone(),
+								self.stop_rx.clone(),
+							)
+							.boxed(),
+						);
+					}
+					Event::FromConnection(BackToFront::NewRequest { id, body, sender, context }) => {
+						log::trace!("{:?}: new request", self.next_request_id);
+						let _was_in = self.to_connections.insert(id, (sender, context));
+						debug_assert!(_was_in.is_none());
+						return TransportServerEvent::Request { id, request: body };
+					}
+					Event::TaskFinished(list) => {
+						self.metrics.connection_closed();
+						// A server serving a single stream has nothing left to do.
+						if self.path.is_none() {
+							if let Some(stop_tx) = self.stop_tx.take() {
+								let _ = stop_tx.send(());
+							}
+						}
+						for rq_id in list {
+							// The request might have been finished in the meantime.
+							if self.to_connections.remove(&rq_id).is_some() {
+								log::trace!("{:?}: closed connection", self.next_request_id);
+								self.pending_events.push(TransportServerEvent::Closed(rq_id));
+							}
+						}
+					}
+				}
+			}
+		})
+	}
+
+	/// Sends back a response and destroys the request.
+	///
+	/// You can pass `None` in order to destroy the request object without sending back anything.
+	fn finish<'a>(
+		&'a mut self,
+		request_id: &'a IpcRequestId,
+		response: Option<&'a jsonrpc::Response>,
+	) -> Pin<Box<dyn Future<Output = Result<(), ()>> + Send + 'a>> {
+		Box::pin(async move {
+			if let Some((mut sender, _)) = self.to_connections.remove(request_id) {
+				if let Some(response) = response {
+					let serialized = serde_json::to_vec(response).map_err(|_| ())?;
+					sender.send(FrontToBack::Send(serialized)).await.map_err(|_| ())?;
+				}
+				sender.send(FrontToBack::Finished(*request_id)).await.map_err(|_| ())?;
+				Ok(())
+			} else {
+				Err(())
+			}
+		})
+	}
+
+	/// Closes the server.
+	///
+	/// Stops accepting new connections and removes the socket file. Every open connection sends
+	/// out the messages that are already queued, then gets closed. The returned `Future` resolves
+	/// once all the connections have been closed.
+	///
+	/// All the pending requests are destroyed. Calling this method multiple times is harmless.
+	fn close<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+		Box::pin(async move {
+			if let (Some(_), Some(path)) = (self.listener.take(), &self.path) {
+				if let Err(err) = fs::remove_file(path) {
+					log::warn!("Failed to remove IPC socket file {}: {}", path.display(), err);
+				}
+			}
+			if let Some(stop_tx) = self.stop_tx.take() {
+				let _ = stop_tx.send(());
+			}
+
+			// The dummy future pushed in `connections_tasks` never finishes.
+			while self.connections_tasks.len() > 1 {
+				let _ = self.connections_tasks.next().await;
+				self.metrics.connection_closed();
+			}
+
+			self.to_connections.clear();
+			self.pending_events.clear();
+		})
+	}
+
+	/// Returns the context of a request that hasn't been finished yet.
+	///
+	/// The id of the returned context is always `Null`, as a single message might contain a batch
+	/// of JSON-RPC requests.
+	fn request_context(&self, request_id: &IpcRequestId) -> Option<&RequestContext> {
+		self.to_connections.get(request_id).map(|(_, context)| context)
+	}
+
+	fn metrics(&self) -> &ServerMetrics {
+		&self.metrics
+	}
+}
+
+impl fmt::Debug for IpcTransportServer {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_tuple("IpcTransportServer").field(&self.path).finish()
+	}
+}
+
+impl IpcTransportServerBuilder {
+	/// Sets the configuration of the server.
+	pub fn config(mut self, config: IpcConfig) -> Self {
+		self.config = config;
+		self
+	}
+
+	/// Sets the permissions of the socket file, for example `0o600` so that only the user running
+	/// the server can connect.
+	///
+	/// By default, the permissions depend on the umask of the process.
+	pub fn permissions(mut self, mode: u32) -> Self {
+		self.permissions = Some(mode);
+		self
+	}
+
+	/// Creates the socket file and starts listening on it.
+	///
+	/// A socket file left over at the same path, for example by a server that has crashed, is
+	/// removed first. Returns an error if the path is occupied by any other kind of file.
+	pub async fn build(self) -> Result<IpcTransportServer, io::Error> {
+		if let Ok(metadata) = fs::symlink_metadata(&self.path) {
+			if metadata.file_type().is_socket() {
+				fs::remove_file(&self.path)?;
+			}
+		}
+		let listener = UnixListener::bind(&self.path).await?;
+		if let Some(mode) = self.permissions {
+			fs::set_permissions(&self.path, fs::Permissions::from_mode(mode))?;
+		}
+
+		Ok(IpcTransportServer::new(Some(self.path), Some(listener), self.config))
+	}
+}
+
+/// Processes a single connection, made of a reading and a writing side.
+///
+/// Returns the list of requests of the connection that haven't been finished.
+async fn per_connection_task(
+	reader: impl AsyncRead + Unpin,
+	mut writer: impl AsyncWrite + Unpin,
+	context: RequestContext,
+	config: IpcConfig,
+	next_request_id: Arc<atomic::AtomicU64>,
+	mut to_front: mpsc::Sender<BackToFront>,
+	mut stop: future::Shared<oneshot::Receiver<()>>,
+) -> Vec<IpcRequestId> {
+	let mut pending_requests = Vec::new();
+	// True once the client has closed its side of the connection. The requests it sent before
+	// are still answered.
+	let mut input_closed = false;
+	let (to_connec, mut from_front) = mpsc::channel(16);
+
+	let socket_packets = stream::unfold(BufReader::new(reader), move |mut reader| async move {
+		let message = read_message(&mut reader, config.framing, config.max_message_size).await;
+		Some((message, reader))
+	});
+	futures::pin_mut!(socket_packets);
+
+	loop {
+		let next_from_front = from_front.next();
+		let next_socket_packet = async {
+			if input_closed {
+				future::pending().await
+			} else {
+				socket_packets.next().await
+			}
+		};
+		futures::pin_mut!(next_socket_packet, next_from_front);
+		let next = match future::select(future::select(next_socket_packet, next_from_front), &mut stop).await {
+			future::Either::Left((next, _)) => next,
+			// The server is being closed.
+			future::Either::Right(_) => {
+				// Send out what the server has already queued for this connection.
+				while let Ok(message) = from_front.try_recv() {
+					if let FrontToBack::Send(to_send) = message {
+						if write_message(&mut writer, config.framing, &to_send).await.is_err() {
+							break;
+						}
+					}
+				}
+				return pending_requests;
+			}
+		};
+
+		match next {
+			future::Either::Left((socket_packet, _)) => {
+				let socket_packet = match socket_packet {
+					Some(Ok(Some(packet))) => packet,
+					Some(Ok(None)) | None => {
+						log::trace!("{:?}: IPC connection closed by the client", next_request_id);
+						if pending_requests.is_empty() {
+							return pending_requests;
+						}
+						input_closed = true;
+						continue;
+					}
+					Some(Err(GenericTransportError::TooLarge)) => {
+						log::warn!("{:?}: IPC message too large; closing the connection", next_request_id);
+						return pending_requests;
+					}
+					Some(Err(GenericTransportError::Inner(err))) => {
+						log::error!("{:?}: failed to receive data from IPC connection: {:?}", next_request_id, err);
+						return pending_requests;
+					}
+				};
+
+				let request = match serde_json::from_slice(&socket_packet) {
+					Ok(b) => b,
+					Err(err) => {
+						log::warn!("Deserialization of incoming request failed: {:?}", err);
+						let response = serde_json::to_vec(&jsonrpc::Response::from(
+							jsonrpc::Error::parse_error(),
+							jsonrpc::Version::V2,
+						))
+						.expect("valid JSON; qed");
+						match write_message(&mut writer, config.framing, &response).await {
+							Ok(()) => continue,
+							Err(err) => {
+								log::warn!("{:?}: failed to send parse error: {:?}", next_request_id, err);
+								return pending_requests;
+							}
+						}
+					}
+				};
+
+				let request_id = IpcRequestId(next_request_id.fetch_add(1, atomic::Ordering::Relaxed));
+				log::debug!("recv: {}", request);
+
+				// As in the WebSocket server, sending to the frontend never blocks in order to
+				// avoid a deadlock between the two bounded channels. If the frontend is too slow,
+				// the connection is closed.
+				let result = to_front
+					.send(BackToFront::NewRequest {
+						id: request_id,
+						body: request,
+						sender: to_connec.clone(),
+						context: context.clone(),
+					})
+					.now_or_never();
+
+				match result {
+					Some(Ok(_)) => pending_requests.push(request_id),
+					Some(Err(_)) | None => {
+						log::error!("{:?}: send request to frontend failed, terminating the connection", request_id);
+						return pending_requests;
+					}
+				}
+			}
+
+			// Received data to send on the connection.
+			future::Either::Right((Some(FrontToBack::Send(to_send)), _)) => {
+				log::debug!("send: {}", String::from_utf8_lossy(&to_send));
+				if let Err(err) = write_message(&mut writer, config.framing, &to_send).await {
+					log::warn!("failed to send response over IPC transport: {:?}", err);
+					return pending_requests;
+				}
+			}
+
+			// Request finished.
+			future::Either::Right((Some(FrontToBack::Finished(rq_id)), _)) => {
+				log::trace!("finished request_id={:?}", rq_id);
+				if let Some(pos) = pending_requests.iter().position(|r| *r == rq_id) {
+					pending_requests.remove(pos);
+				}
+				if input_closed && pending_requests.is_empty() {
+					return pending_requests;
+				}
+			}
+
+			// Channel to main IPC server struct has closed. Let's close the task.
+			future::Either::Right((None, _)) => return pending_requests,
+		}
+	}
+}
@@ -1,4 +1,4 @@
-use crate::types::{Body, HttpResponse, Id, Uri};
+use crate::types::{Body, HttpResponse, Id, Method, Uri};
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Request, Response, Server};
 use serde_json::Value;
@@ -83,11 +83,22 @@ pub fn server_subscription_response(result: Value) -> String {
 }
 
 pub async fn http_request(body: Body, uri: Uri) -> Result<HttpResponse, String> {
+	http_request_with_headers(Method::POST, &[("content-type", "application/json")], body, uri).await
+}
+
+/// Sends an HTTP request with the given method and headers.
+pub async fn http_request_with_headers(
+	method: Method,
+	headers: &[(&str, &str)],
+	body: Body,
+	uri: Uri,
+) -> Result<HttpResponse, String> {
 	let client = hyper::Client::new();
-	let r = hyper::Request::post(uri)
-		.header(hyper::header::CONTENT_TYPE, hyper::header::HeaderValue::from_static("application/json"))
-		.body(body)
-		.expect("uri and request headers are valid; qed");
+	let mut r = hyper::Request::builder().method(method).uri(uri);
+	for (name, value) in headers {
+		r = r.header(*name, *value);
+	}
+	let r = r.body(body).expect("uri and request headers are valid; qed");
 	let res = client.request(r).await.map_err(|e| format!("{:?}", e))?;
 
 	let (parts, body) = res.into_parts();
@@ -0,0 +1,50 @@
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+
+/// Returns a path for a socket file in the temporary directory that is unique to the test
+/// `name` within this process. Any file already present at this path is removed.
+pub fn temp_socket_path(name: &str) -> PathBuf {
+	let path = std::env::temp_dir().join(format!("jsonrpsee-{}-{}.sock", name, std::process::id()));
+	let _ = std::fs::remove_file(&path);
+	path
+}
+
+/// Sends a message to the IPC server listening on `path`, with one JSON message per line, and
+/// returns the line it answers with.
+pub async fn ipc_request(path: impl AsRef<Path>, request: &str) -> Result<String, String> {
+	let socket = UnixStream::connect(path).await.map_err(|e| format!("{:?}", e))?;
+	let (reader, mut writer) = socket.into_split();
+	writer.write_all(format!("{}\n", request).as_bytes()).await.map_err(|e| format!("{:?}", e))?;
+	match BufReader::new(reader).lines().next_line().await {
+		Ok(Some(response)) => Ok(response),
+		Ok(None) => Err("connection closed".to_owned()),
+		Err(e) => Err(format!("{:?}", e)),
+	}
+}
+
+/// Spawn an IPC server that answers every request with a hardcoded response, with one JSON
+/// message per line. Notifications aren't answered.
+//
+// NOTE: This must be spawned on tokio.
+pub async fn ipc_server_with_hardcoded_response(path: impl AsRef<Path>, response: String) {
+	let listener = UnixListener::bind(path).unwrap();
+
+	tokio::spawn(async move {
+		while let Ok((socket, _)) = listener.accept().await {
+			let response = response.clone();
+			tokio::spawn(async move {
+				let (reader, mut writer) = socket.into_split();
+				let mut lines = BufReader::new(reader).lines();
+				while let Ok(Some(line)) = lines.next_line().await {
+					let is_request = serde_json::from_str::<serde_json::Value>(&line)
+						.map(|request| request.get("id").is_some())
+						.unwrap_or(true);
+					if is_request && writer.write_all(format!("{}\n", response).as_bytes()).await.is_err() {
+						break;
+					}
+				}
+			});
+		}
+	});
+}
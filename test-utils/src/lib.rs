@@ -3,4 +3,7 @@
 #![recursion_limit = "256"]
 
 pub mod helpers;
+#[cfg(unix)]
+pub mod ipc;
+pub mod tls;
 pub mod types;
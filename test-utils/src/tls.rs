@@ -0,0 +1,71 @@
+//! Certificates issued by a test certificate authority, and TLS clients that trust it.
+
+use crate::types::{Body, HttpResponse};
+use std::{net::SocketAddr, sync::Arc};
+use tokio_util::compat::{FuturesAsyncReadCompatExt as _, TokioAsyncReadCompatExt as _};
+
+/// Self-signed certificate of the test certificate authority, in the PEM format.
+pub const CA_CERT: &[u8] = include_bytes!("../certs/ca.crt");
+/// Server certificate for `localhost` issued by [`CA_CERT`], in the PEM format.
+pub const LOCALHOST_CERT: &[u8] = include_bytes!("../certs/localhost.crt");
+/// Private key of [`LOCALHOST_CERT`], in the PEM format.
+pub const LOCALHOST_KEY: &[u8] = include_bytes!("../certs/localhost.key");
+/// Client certificate issued by [`CA_CERT`], in the PEM format.
+pub const CLIENT_CERT: &[u8] = include_bytes!("../certs/client.crt");
+/// Private key of [`CLIENT_CERT`], in the PEM format.
+pub const CLIENT_KEY: &[u8] = include_bytes!("../certs/client.key");
+
+/// Returns a client configuration that only trusts [`CA_CERT`] and offers the given ALPN
+/// protocols.
+pub fn client_config(alpn_protocols: &[&[u8]]) -> rustls::ClientConfig {
+	let mut config = rustls::ClientConfig::new();
+	config.root_store.add_pem_file(&mut std::io::Cursor::new(CA_CERT)).unwrap();
+	config.set_protocols(&alpn_protocols.iter().map(|protocol| protocol.to_vec()).collect::<Vec<_>>());
+	config
+}
+
+/// Performs a TLS handshake with the server listening on `addr`, then returns the ALPN protocol
+/// that the server has picked amongst `alpn_protocols`.
+///
+/// This function is blocking.
+pub fn negotiated_alpn(addr: SocketAddr, alpn_protocols: &[&[u8]]) -> Result<Option<Vec<u8>>, String> {
+	use rustls::Session as _;
+
+	let config = Arc::new(client_config(alpn_protocols));
+	let dns_name = webpki::DNSNameRef::try_from_ascii_str("localhost").unwrap();
+	let mut session = rustls::ClientSession::new(&config, dns_name);
+	let mut socket = std::net::TcpStream::connect(addr).map_err(|e| format!("{:?}", e))?;
+	while session.is_handshaking() {
+		session.complete_io(&mut socket).map_err(|e| format!("{:?}", e))?;
+	}
+	Ok(session.get_alpn_protocol().map(|protocol| protocol.to_vec()))
+}
+
+/// Sends a JSON-RPC request over HTTPS to the server listening on `addr`, using HTTP/2 if
+/// `http2` is true and HTTP/1.1 otherwise.
+pub async fn https_request(body: Body, addr: SocketAddr, http2: bool) -> Result<HttpResponse, String> {
+	let alpn: &[u8] = if http2 { b"h2" } else { b"http/1.1" };
+	let connector = async_tls::TlsConnector::from(Arc::new(client_config(&[alpn])));
+	let socket = tokio::net::TcpStream::connect(addr).await.map_err(|e| format!("{:?}", e))?;
+	let stream = connector.connect("localhost", socket.compat()).await.map_err(|e| format!("{:?}", e))?;
+
+	let (mut sender, connection) = hyper::client::conn::Builder::new()
+		.http2_only(http2)
+		.handshake(stream.compat())
+		.await
+		.map_err(|e| format!("{:?}", e))?;
+	tokio::spawn(connection);
+
+	let mut request = hyper::Request::post(format!("https://localhost:{}/", addr.port()))
+		.header(hyper::header::CONTENT_TYPE, hyper::header::HeaderValue::from_static("application/json"));
+	// HTTP/2 derives the `:authority` pseudo-header from the URI.
+	if !http2 {
+		request = request.header(hyper::header::HOST, format!("localhost:{}", addr.port()));
+	}
+	let request = request.body(body).expect("uri and request headers are valid; qed");
+	let response = sender.send_request(request).await.map_err(|e| format!("{:?}", e))?;
+
+	let (parts, body) = response.into_parts();
+	let bytes = hyper::body::to_bytes(body).await.map_err(|e| format!("{:?}", e))?;
+	Ok(HttpResponse { status: parts.status, header: parts.headers, body: String::from_utf8(bytes.to_vec()).unwrap() })
+}
@@ -11,7 +11,7 @@ use std::time::Duration;
 use tokio::net::TcpStream;
 use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
 
-pub use hyper::{Body, HeaderMap, StatusCode, Uri};
+pub use hyper::{Body, HeaderMap, Method, StatusCode, Uri};
 
 type Error = Box<dyn std::error::Error>;
 
@@ -43,8 +43,15 @@ pub struct WebSocketTestClient {
 
 impl WebSocketTestClient {
 	pub async fn new(url: SocketAddr) -> Result<Self, Error> {
+		Self::with_host_and_origin(url, "test-client", None).await
+	}
+
+	pub async fn with_host_and_origin(url: SocketAddr, host: &str, origin: Option<&str>) -> Result<Self, Error> {
 		let socket = TcpStream::connect(url).await?;
-		let mut client = handshake::Client::new(BufReader::new(BufWriter::new(socket.compat())), "test-client", "/");
+		let mut client = handshake::Client::new(BufReader::new(BufWriter::new(socket.compat())), host, "/");
+		if let Some(origin) = origin {
+			client.set_origin(origin);
+		}
 		match client.handshake().await {
 			Ok(handshake::ServerResponse::Accepted { .. }) => {
 				let (tx, rx) = client.into_builder().finish();
@@ -62,6 +69,11 @@ impl WebSocketTestClient {
 		String::from_utf8(data).map_err(Into::into)
 	}
 
+	pub async fn send(&mut self, msg: impl AsRef<str>) -> Result<(), Error> {
+		self.tx.send_text(msg).await?;
+		self.tx.flush().await.map_err(Into::into)
+	}
+
 	pub async fn send_request_binary(&mut self, msg: &[u8]) -> Result<String, Error> {
 		self.tx.send_binary(msg).await?;
 		self.tx.flush().await?;
@@ -70,6 +82,12 @@ impl WebSocketTestClient {
 		String::from_utf8(data).map_err(Into::into)
 	}
 
+	pub async fn receive(&mut self) -> Result<String, Error> {
+		let mut data = Vec::new();
+		self.rx.receive_data(&mut data).await?;
+		String::from_utf8(data).map_err(Into::into)
+	}
+
 	pub async fn close(&mut self) -> Result<(), Error> {
 		self.tx.close().await.map_err(Into::into)
 	}
@@ -26,7 +26,7 @@
 
 use jsonrpsee_http_server::{HttpConfig, HttpServer};
 use jsonrpsee_types::jsonrpc::JsonValue;
-use jsonrpsee_ws_server::WsServer;
+use jsonrpsee_ws_server::{WsServer, WsServerConfig};
 
 use std::net::SocketAddr;
 use std::time::Duration;
@@ -38,7 +38,7 @@ pub fn websocket_server(server_started: Sender<SocketAddr>) {
 	std::thread::spawn(move || {
 		let rt = tokio::runtime::Runtime::new().unwrap();
 
-		let server = rt.block_on(WsServer::new("127.0.0.1:0")).unwrap();
+		let server = rt.block_on(WsServer::new("127.0.0.1:0", WsServerConfig::default())).unwrap();
 		let mut sub_hello =
 			server.register_subscription("subscribe_hello".to_owned(), "unsubscribe_hello".to_owned()).unwrap();
 		let mut sub_foo =
@@ -72,7 +72,7 @@ pub fn websocket_server_with_wait_period(server_started: Sender<SocketAddr>, wai
 	std::thread::spawn(move || {
 		let rt = tokio::runtime::Runtime::new().unwrap();
 
-		let server = rt.block_on(WsServer::new("127.0.0.1:0")).unwrap();
+		let server = rt.block_on(WsServer::new("127.0.0.1:0", WsServerConfig::default())).unwrap();
 		let mut respond = server.register_method("say_hello".to_owned()).unwrap();
 		server_started.send(*server.local_addr()).unwrap();
 
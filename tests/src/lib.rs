@@ -31,11 +31,25 @@ mod helpers;
 use std::net::SocketAddr;
 use std::time::Duration;
 
-use futures::channel::oneshot;
+use futures::{channel::oneshot, FutureExt as _, StreamExt as _};
 use helpers::{http_server, websocket_server, websocket_server_with_wait_period};
-use jsonrpsee_http_client::{HttpClient, HttpConfig};
-use jsonrpsee_types::jsonrpc::{JsonValue, Params};
-use jsonrpsee_ws_client::{WsClient, WsConfig, WsSubscription};
+use jsonrpsee_http_client::{HttpClient, HttpConfig, TlsClientConfig};
+use jsonrpsee_http_server::{HttpServer, TlsServerConfig};
+use jsonrpsee_ipc_client::{Framing, IpcClient, IpcConfig};
+use jsonrpsee_ipc_server::IpcServer;
+use jsonrpsee_test_utils::ipc::temp_socket_path;
+use jsonrpsee_test_utils::tls::{CA_CERT, CLIENT_CERT, CLIENT_KEY, LOCALHOST_CERT, LOCALHOST_KEY};
+use jsonrpsee_types::{
+	client::BatchEntry,
+	error::Error,
+	jsonrpc::{self, JsonValue, Params},
+	server::RpcModule,
+};
+use jsonrpsee_ws_client::{
+	Client, KeepAliveConfig, ReconnectConfig, SubscriptionClient, SubscriptionEnd, SubscriptionEvent, WsClient,
+	WsConfig, WsSubscription,
+};
+use jsonrpsee_ws_server::{WsServer, WsServerConfig};
 
 #[tokio::test]
 async fn ws_subscription_works() {
@@ -79,6 +93,115 @@ async fn http_method_call_works() {
 	assert_eq!(response, JsonValue::String("hello".into()));
 }
 
+#[tokio::test]
+async fn http_batch_request_works() {
+	let server = HttpServer::new("127.0.0.1:0", HttpConfig::default()).await.unwrap();
+	server
+		.register_async_method("add".to_owned(), |[a, b]: [u64; 2]| async move { Ok::<_, jsonrpc::Error>(a + b) })
+		.unwrap();
+	let mut notif = server.register_notification("notif".to_owned(), false).unwrap();
+	let uri = format!("http://{}", server.local_addr());
+	let client = HttpClient::new(&uri, HttpConfig::default()).unwrap();
+
+	let batch = vec![
+		BatchEntry::call("add", Params::Array(vec![1.into(), 2.into()])),
+		BatchEntry::notification("notif", Params::Array(vec![42.into()])),
+		BatchEntry::call("unknown", Params::None),
+		("add", Params::Array(vec![3.into(), 4.into()])).into(),
+	];
+	let results = client.batch_request(batch).await.unwrap();
+	assert_eq!(results.len(), 3);
+	assert_eq!(results[0].as_ref().unwrap(), &JsonValue::from(3));
+	assert!(matches!(&results[1], Err(Error::Request(err)) if err.code == jsonrpc::ErrorCode::MethodNotFound));
+	assert_eq!(results[2].as_ref().unwrap(), &JsonValue::from(7));
+	assert_eq!(notif.next().await, Params::Array(vec![42.into()]));
+
+	// A batch made only of notifications isn't answered.
+	let results = client.batch_request(vec![BatchEntry::notification("notif", Params::None)]).await.unwrap();
+	assert!(results.is_empty());
+	assert_eq!(notif.next().await, Params::None);
+}
+
+#[tokio::test]
+async fn ws_batch_request_works() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	server
+		.register_async_method("add".to_owned(), |[a, b]: [u64; 2]| async move { Ok::<_, jsonrpc::Error>(a + b) })
+		.unwrap();
+	let mut notif = server.register_notification("notif".to_owned(), false).unwrap();
+	let uri = format!("ws://{}", server.local_addr());
+	let client = WsClient::new(&uri, WsConfig::default()).await.unwrap();
+
+	let batch = vec![
+		BatchEntry::call("add", Params::Array(vec![1.into(), 2.into()])),
+		BatchEntry::notification("notif", Params::Array(vec![42.into()])),
+		BatchEntry::call("add", Params::Array(vec!["oops".into()])),
+		("add", Params::Array(vec![3.into(), 4.into()])).into(),
+	];
+	let results = client.batch_request(batch).await.unwrap();
+	assert_eq!(results.len(), 3);
+	assert_eq!(results[0].as_ref().unwrap(), &JsonValue::from(3));
+	assert!(matches!(&results[1], Err(Error::Request(err)) if err.code == jsonrpc::ErrorCode::InvalidParams));
+	assert_eq!(results[2].as_ref().unwrap(), &JsonValue::from(7));
+	assert_eq!(notif.next().await, Params::Array(vec![42.into()]));
+
+	// A batch made only of notifications isn't answered.
+	let results = client.batch_request(vec![BatchEntry::notification("notif", Params::None)]).await.unwrap();
+	assert!(results.is_empty());
+	assert_eq!(notif.next().await, Params::None);
+
+	// The client is still usable.
+	let sum: u64 = client.request("add", Params::Array(vec![5.into(), 6.into()])).await.unwrap();
+	assert_eq!(sum, 11);
+}
+
+/// Calls the methods of the servers started by the helpers, whatever the transport of `client`.
+async fn call_hello_with<C: Client>(client: &C) {
+	let response: String = client.request("say_hello", Params::None).await.unwrap();
+	assert_eq!(response, "hello");
+
+	let batch = vec![BatchEntry::call("say_hello", Params::None), BatchEntry::call("unknown", Params::None)];
+	let results: Vec<Result<String, Error>> = client.batch_request(batch).await.unwrap();
+	assert_eq!(results.len(), 2);
+	assert_eq!(results[0].as_ref().unwrap(), "hello");
+	assert!(matches!(&results[1], Err(Error::Request(err)) if err.code == jsonrpc::ErrorCode::MethodNotFound));
+
+	// A result that doesn't match the expected type is reported for the call alone.
+	let batch = vec![BatchEntry::call("say_hello", Params::None)];
+	let results: Vec<Result<u64, Error>> = client.batch_request(batch).await.unwrap();
+	assert!(matches!(&results[..], [Err(Error::ParseError(_))]));
+}
+
+async fn subscribe_hello_with<C: SubscriptionClient>(client: &C) {
+	call_hello_with(client).await;
+	let mut sub: WsSubscription<String> =
+		client.subscribe("subscribe_hello", Params::None, "unsubscribe_hello").await.unwrap();
+	assert_eq!(sub.next().await, Ok("hello from subscription".to_owned()));
+}
+
+#[tokio::test]
+async fn client_trait_works_on_http_and_ws() {
+	let (server_started_tx, server_started_rx) = oneshot::channel::<SocketAddr>();
+	http_server(server_started_tx);
+	let server_addr = server_started_rx.await.unwrap();
+	let http_client = HttpClient::new(format!("http://{}", server_addr), HttpConfig::default()).unwrap();
+	call_hello_with(&http_client).await;
+
+	let (server_started_tx, server_started_rx) = oneshot::channel::<SocketAddr>();
+	websocket_server(server_started_tx);
+	let server_addr = server_started_rx.await.unwrap();
+	let ws_client = WsClient::new(format!("ws://{}", server_addr), WsConfig::default()).await.unwrap();
+	subscribe_hello_with(&ws_client).await;
+}
+
+#[tokio::test]
+async fn client_trait_works_on_ipc() {
+	let server = IpcServer::builder(temp_socket_path("client_trait_works_on_ipc")).build().await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let client = IpcClient::new(server.local_path().unwrap(), IpcConfig::default()).await.unwrap();
+	call_hello_with(&client).await;
+}
+
 #[tokio::test]
 async fn ws_subscription_several_clients() {
 	let (server_started_tx, server_started_rx) = oneshot::channel::<SocketAddr>();
@@ -161,11 +284,11 @@ async fn ws_subscription_without_polling_doesnt_make_client_unuseable() {
 
 	// Capacity is `num_sender` + `capacity`
 	for _ in 0..5 {
-		assert!(hello_sub.next().await.is_some());
+		assert!(hello_sub.next().await.is_ok());
 	}
 
 	// NOTE: this is now unuseable and unregistered.
-	assert!(hello_sub.next().await.is_none());
+	assert_eq!(hello_sub.next().await, Err(SubscriptionEnd::Lagged));
 
 	// The client should still be useable => make sure it still works.
 	let _hello_req: JsonValue = client.request("say_hello", Params::None).await.unwrap();
@@ -201,3 +324,334 @@ async fn ws_more_request_than_buffer_should_not_deadlock() {
 		req.await.unwrap();
 	}
 }
+
+#[tokio::test]
+async fn ws_server_stop_ends_subscriptions() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	let _sub = server.register_subscription("subscribe_hello".to_owned(), "unsubscribe_hello".to_owned()).unwrap();
+	let uri = format!("ws://{}", server.local_addr());
+	let client = WsClient::new(&uri, WsConfig::default()).await.unwrap();
+	let mut hello_sub: WsSubscription<JsonValue> =
+		client.subscribe("subscribe_hello", Params::None, "unsubscribe_hello").await.unwrap();
+
+	server.stop(Duration::from_secs(1)).await.unwrap();
+
+	assert_eq!(hello_sub.next().await, Err(SubscriptionEnd::Error(jsonrpc::Error::server_shutting_down())));
+	assert!(client.request::<JsonValue>("say_hello", Params::None).await.is_err());
+}
+
+#[tokio::test]
+async fn ws_subscription_closed_by_server_ends_with_reason() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	server
+		.register_subscription_handler(
+			"subscribe_replay".to_owned(),
+			"unsubscribe_replay".to_owned(),
+			|params, mut sink| async move {
+				let [count]: [u64; 1] = params.parse().unwrap();
+				if count == 0 {
+					let _ = sink
+						.close(jsonrpc::SubscriptionClosedReason::Error(jsonrpc::Error::invalid_params("empty replay")))
+						.await;
+					return;
+				}
+				for n in 0..count {
+					sink.send(n.into()).await.unwrap();
+				}
+				sink.close(jsonrpc::SubscriptionClosedReason::Completed).await.unwrap();
+			},
+		)
+		.unwrap();
+	let uri = format!("ws://{}", server.local_addr());
+	let client = WsClient::new(&uri, WsConfig::default()).await.unwrap();
+
+	let mut replay: WsSubscription<u64> =
+		client.subscribe("subscribe_replay", Params::Array(vec![3.into()]), "unsubscribe_replay").await.unwrap();
+	for n in 0..3 {
+		assert_eq!(replay.next().await, Ok(n));
+	}
+	assert_eq!(replay.next().await, Err(SubscriptionEnd::Completed));
+	// The end of the stream is sticky.
+	assert_eq!(replay.next().await, Err(SubscriptionEnd::Completed));
+
+	let mut empty: WsSubscription<u64> =
+		client.subscribe("subscribe_replay", Params::Array(vec![0.into()]), "unsubscribe_replay").await.unwrap();
+	assert_eq!(empty.next().await, Err(SubscriptionEnd::Error(jsonrpc::Error::invalid_params("empty replay"))));
+
+	// The client is still usable.
+	let mut replay: WsSubscription<u64> =
+		client.subscribe("subscribe_replay", Params::Array(vec![1.into()]), "unsubscribe_replay").await.unwrap();
+	assert_eq!(replay.next().await, Ok(0));
+	assert_eq!(replay.next().await, Err(SubscriptionEnd::Completed));
+}
+
+/// Starts a server whose subscriptions send their first parameter, then wait for the subscriber
+/// to leave. Its `never_answered` method is never answered.
+async fn restartable_server(addr: &str) -> (WsServer, jsonrpsee_ws_server::RegisteredMethod) {
+	let server = WsServer::new(addr, WsServerConfig::default()).await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	server
+		.register_subscription_handler(
+			"subscribe_echo".to_owned(),
+			"unsubscribe_echo".to_owned(),
+			|params, mut sink| async move {
+				let [value]: [u64; 1] = params.parse().unwrap();
+				if sink.send(value.into()).await.is_ok() {
+					sink.closed().await;
+				}
+			},
+		)
+		.unwrap();
+	let never_answered = server.register_method("never_answered".to_owned()).unwrap();
+	(server, never_answered)
+}
+
+#[tokio::test]
+async fn ws_client_reconnects_and_resubscribes() {
+	let (server, _never_answered) = restartable_server("127.0.0.1:0").await;
+	let addr = server.local_addr().to_string();
+	let reconnect = ReconnectConfig {
+		initial_delay: Duration::from_millis(50),
+		max_delay: Duration::from_millis(200),
+		..Default::default()
+	};
+	let client = WsClient::new(format!("ws://{}", addr), WsConfig { reconnect: Some(reconnect), ..Default::default() })
+		.await
+		.unwrap();
+	let mut sub: WsSubscription<u64> =
+		client.subscribe("subscribe_echo", Params::Array(vec![7.into()]), "unsubscribe_echo").await.unwrap();
+	assert_eq!(sub.next_event().await, Ok(SubscriptionEvent::Notification(7)));
+
+	// The request pending when the connection is lost fails.
+	let pending = {
+		let client = client.clone();
+		tokio::spawn(async move { client.request::<JsonValue>("never_answered", Params::None).await })
+	};
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	server.stop(Duration::from_millis(50)).await.unwrap();
+	assert!(matches!(pending.await.unwrap(), Err(Error::ConnectionLost)));
+
+	// The subscription is established again with its original parameters once the server is back.
+	let (_server, _never_answered) = restartable_server(&addr).await;
+	assert_eq!(sub.next_event().await, Ok(SubscriptionEvent::Resubscribed));
+	assert_eq!(sub.next_event().await, Ok(SubscriptionEvent::Notification(7)));
+	let response: JsonValue = client.request("say_hello", Params::None).await.unwrap();
+	assert_eq!(response, JsonValue::String("hello".into()));
+}
+
+#[tokio::test]
+async fn ws_client_retries_pending_requests_after_reconnecting() {
+	let (server, mut never_answered) = restartable_server("127.0.0.1:0").await;
+	let addr = server.local_addr().to_string();
+	let reconnect = ReconnectConfig {
+		initial_delay: Duration::from_millis(50),
+		max_delay: Duration::from_millis(200),
+		retry_pending_requests: true,
+		..Default::default()
+	};
+	let client = WsClient::new(format!("ws://{}", addr), WsConfig { reconnect: Some(reconnect), ..Default::default() })
+		.await
+		.unwrap();
+
+	let pending = {
+		let client = client.clone();
+		tokio::spawn(async move { client.request::<JsonValue>("never_answered", Params::None).await })
+	};
+	// The first server receives the request but never answers it.
+	let _unanswered = never_answered.next().await;
+	server.stop(Duration::from_millis(50)).await.unwrap();
+
+	// The request is sent again to the restarted server.
+	let (_server, mut answered) = restartable_server(&addr).await;
+	answered.next().await.respond(Ok(JsonValue::from(42))).await.unwrap();
+	assert_eq!(pending.await.unwrap().unwrap(), JsonValue::from(42));
+}
+
+#[tokio::test]
+async fn ws_client_gives_up_reconnecting() {
+	let (server, _never_answered) = restartable_server("127.0.0.1:0").await;
+	let reconnect = ReconnectConfig {
+		initial_delay: Duration::from_millis(10),
+		max_delay: Duration::from_millis(20),
+		max_attempts: Some(3),
+		..Default::default()
+	};
+	let client = WsClient::new(
+		format!("ws://{}", server.local_addr()),
+		WsConfig { reconnect: Some(reconnect), ..Default::default() },
+	)
+	.await
+	.unwrap();
+	let mut sub: WsSubscription<u64> =
+		client.subscribe("subscribe_echo", Params::Array(vec![1.into()]), "unsubscribe_echo").await.unwrap();
+	assert_eq!(sub.next().await, Ok(1));
+
+	server.stop(Duration::from_secs(1)).await.unwrap();
+	assert_eq!(sub.next_event().await, Err(SubscriptionEnd::Disconnected));
+	assert!(client.request::<JsonValue>("say_hello", Params::None).await.is_err());
+}
+
+#[tokio::test]
+async fn ws_timed_out_request_is_forgotten() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	let mut slow = server.register_method("slow".to_owned()).unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let uri = format!("ws://{}", server.local_addr());
+	let client =
+		WsClient::new(&uri, WsConfig { request_timeout: Some(Duration::from_millis(100)), ..Default::default() })
+			.await
+			.unwrap();
+
+	assert!(matches!(client.request::<JsonValue>("slow", Params::None).await, Err(Error::WsRequestTimeout)));
+	// A dropped request is given up as well.
+	assert!(client.request::<JsonValue>("slow", Params::None).now_or_never().is_none());
+
+	// The late responses are ignored.
+	slow.next().await.respond(Ok(JsonValue::from(1))).await.unwrap();
+	slow.next().await.respond(Ok(JsonValue::from(2))).await.unwrap();
+	let response: JsonValue = client.request("say_hello", Params::None).await.unwrap();
+	assert_eq!(response, JsonValue::String("hello".into()));
+}
+
+#[tokio::test]
+async fn ws_dropped_pending_subscription_unsubscribes() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	let (closed_tx, mut closed_rx) = futures::channel::mpsc::unbounded();
+	server
+		.register_subscription_handler(
+			"subscribe_hello".to_owned(),
+			"unsubscribe_hello".to_owned(),
+			move |_, mut sink| {
+				let closed_tx = closed_tx.clone();
+				async move {
+					sink.closed().await;
+					closed_tx.unbounded_send(()).unwrap();
+				}
+			},
+		)
+		.unwrap();
+	let uri = format!("ws://{}", server.local_addr());
+	let client = WsClient::new(&uri, WsConfig::default()).await.unwrap();
+
+	// The subscription request is sent, but the subscription is given up before the server answers.
+	let subscribe = client.subscribe::<JsonValue>("subscribe_hello", Params::None, "unsubscribe_hello");
+	assert!(subscribe.now_or_never().is_none());
+
+	// The client unsubscribes once the server has answered.
+	closed_rx.next().await.unwrap();
+	let sub: WsSubscription<JsonValue> =
+		client.subscribe("subscribe_hello", Params::None, "unsubscribe_hello").await.unwrap();
+	drop(sub);
+	closed_rx.next().await.unwrap();
+}
+
+#[tokio::test]
+async fn ws_client_keep_alive_works() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let uri = format!("ws://{}", server.local_addr());
+
+	// The server answers the pings.
+	let keep_alive = KeepAliveConfig {
+		ping_interval: Some(Duration::from_millis(50)),
+		pong_timeout: Duration::from_millis(100),
+		idle_timeout: None,
+	};
+	let client = WsClient::new(&uri, WsConfig { keep_alive, ..Default::default() }).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	let response: JsonValue = client.request("say_hello", Params::None).await.unwrap();
+	assert_eq!(response, JsonValue::String("hello".into()));
+
+	// Pings don't prevent the connection from becoming idle.
+	let keep_alive = KeepAliveConfig { idle_timeout: Some(Duration::from_millis(100)), ..keep_alive };
+	let client = WsClient::new(&uri, WsConfig { keep_alive, ..Default::default() }).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+	assert!(client.request::<JsonValue>("say_hello", Params::None).await.is_err());
+}
+
+#[tokio::test]
+async fn rpc_module_on_http_and_ws_works() {
+	let mut module = RpcModule::new();
+	module.register_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let mut other = RpcModule::new();
+	let hello_sub = other.register_subscription("subscribe_hello".to_owned(), "unsubscribe_hello".to_owned()).unwrap();
+	module.merge(other).unwrap();
+
+	let http_server = HttpServer::new("127.0.0.1:0", HttpConfig::default()).await.unwrap();
+	http_server.register_module(&module).unwrap();
+	assert!(http_server.register_module(&module).is_err());
+	let ws_server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	ws_server.register_module(&module).unwrap();
+	assert!(ws_server.register_module(&module).is_err());
+
+	let http_client = HttpClient::new(format!("http://{}", http_server.local_addr()), HttpConfig::default()).unwrap();
+	let response: JsonValue = http_client.request("say_hello", Params::None).await.unwrap();
+	assert_eq!(response, JsonValue::String("hello".into()));
+
+	let ws_client = WsClient::new(format!("ws://{}", ws_server.local_addr()), WsConfig::default()).await.unwrap();
+	let response: JsonValue = ws_client.request("say_hello", Params::None).await.unwrap();
+	assert_eq!(response, JsonValue::String("hello".into()));
+	let mut sub: WsSubscription<JsonValue> =
+		ws_client.subscribe("subscribe_hello", Params::None, "unsubscribe_hello").await.unwrap();
+	hello_sub.send(JsonValue::String("hello from subscription".into()));
+	assert_eq!(sub.next().await, Ok(JsonValue::String("hello from subscription".into())));
+}
+
+#[tokio::test]
+async fn https_method_call_works() {
+	let tls = TlsServerConfig::from_pem(LOCALHOST_CERT, LOCALHOST_KEY).unwrap();
+	let server = HttpServer::builder("127.0.0.1:0").tls(tls).build().await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let uri = format!("https://localhost:{}", server.local_addr().port());
+
+	let tls = TlsClientConfig::new().without_webpki_roots().add_root_certs_pem(CA_CERT).unwrap();
+	let client = HttpClient::with_tls_config(&uri, HttpConfig::default(), &tls).unwrap();
+	let response: JsonValue = client.request("say_hello", Params::None).await.unwrap();
+	assert_eq!(response, JsonValue::String("hello".into()));
+
+	// The test certificate authority isn't trusted by default.
+	let client = HttpClient::new(&uri, HttpConfig::default()).unwrap();
+	assert!(client.request::<JsonValue>("say_hello", Params::None).await.is_err());
+}
+
+#[tokio::test]
+async fn https_client_auth_works() {
+	let tls = TlsServerConfig::from_pem(LOCALHOST_CERT, LOCALHOST_KEY).unwrap().client_auth_pem(CA_CERT).unwrap();
+	let server = HttpServer::builder("127.0.0.1:0").tls(tls).build().await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let uri = format!("https://localhost:{}", server.local_addr().port());
+	let tls = TlsClientConfig::new().add_root_certs_pem(CA_CERT).unwrap();
+
+	// Clients without a certificate are rejected.
+	let client = HttpClient::with_tls_config(&uri, HttpConfig::default(), &tls).unwrap();
+	assert!(client.request::<JsonValue>("say_hello", Params::None).await.is_err());
+
+	let tls = tls.client_auth_pem(CLIENT_CERT, CLIENT_KEY).unwrap();
+	let client = HttpClient::with_tls_config(&uri, HttpConfig::default(), &tls).unwrap();
+	let response: JsonValue = client.request("say_hello", Params::None).await.unwrap();
+	assert_eq!(response, JsonValue::String("hello".into()));
+}
+
+#[tokio::test]
+async fn ipc_method_call_works() {
+	let mut module = RpcModule::new();
+	module.register_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+
+	for framing in &[Framing::Newline, Framing::LengthDelimited] {
+		let config = IpcConfig { framing: *framing, ..Default::default() };
+		let server = IpcServer::builder(temp_socket_path(&format!("ipc_method_call_works_{:?}", framing)))
+			.config(config)
+			.permissions(0o600)
+			.build()
+			.await
+			.unwrap();
+		server.register_module(&module).unwrap();
+
+		let client = IpcClient::new(server.local_path().unwrap(), config).await.unwrap();
+		let response: JsonValue = client.request("say_hello", Params::None).await.unwrap();
+		assert_eq!(response, JsonValue::String("hello".into()));
+
+		server.stop(Duration::from_secs(5)).await.unwrap();
+		assert!(client.request::<JsonValue>("say_hello", Params::None).await.is_err());
+	}
+}
@@ -0,0 +1,186 @@
+//! Shared client types
+
+use crate::{error::Error, jsonrpc};
+use core::convert::TryInto;
+use futures::future::BoxFuture;
+use std::collections::HashMap;
+
+/// JSON-RPC client, independent of the transport it uses.
+///
+/// Lets code that performs calls be generic over the transport.
+pub trait Client {
+	/// Send a notification to the server.
+	fn notification<'a>(
+		&'a self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> BoxFuture<'a, Result<(), Error>>;
+
+	/// Perform a request towards the server and deserialize its result into `T`.
+	fn request<'a, T>(
+		&'a self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> BoxFuture<'a, Result<T, Error>>
+	where
+		T: jsonrpc::DeserializeOwned + Send + 'a;
+
+	/// Send a batch of method calls and notifications to the server.
+	///
+	/// Returns one result per method call, deserialized into `T`, in the order in which the calls
+	/// appear in `batch`. Notifications don't produce any result. Each call succeeds or fails
+	/// independently from the others.
+	///
+	/// Returns an error if the whole batch failed, for example if the server couldn't be reached
+	/// or didn't answer with a batch.
+	fn batch_request<'a, T>(&'a self, batch: Vec<BatchEntry>) -> BoxFuture<'a, Result<Vec<Result<T, Error>>, Error>>
+	where
+		T: jsonrpc::DeserializeOwned + Send + 'a;
+}
+
+/// Entry of a batch of requests sent by a client.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BatchEntry {
+	/// Method call, whose result is returned to the caller.
+	Call {
+		/// Name of the method.
+		method: String,
+		/// Parameters of the call.
+		params: jsonrpc::Params,
+	},
+	/// Notification, which the server doesn't answer.
+	Notification {
+		/// Name of the method.
+		method: String,
+		/// Parameters of the notification.
+		params: jsonrpc::Params,
+	},
+}
+
+impl BatchEntry {
+	/// Creates a method call.
+	pub fn call(method: impl Into<String>, params: impl Into<jsonrpc::Params>) -> Self {
+		BatchEntry::Call { method: method.into(), params: params.into() }
+	}
+
+	/// Creates a notification.
+	pub fn notification(method: impl Into<String>, params: impl Into<jsonrpc::Params>) -> Self {
+		BatchEntry::Notification { method: method.into(), params: params.into() }
+	}
+
+	/// Converts the entry into a [`jsonrpc::Call`]. Method calls are given the identifier `id`,
+	/// which is ignored for notifications.
+	pub fn into_call(self, id: u64) -> jsonrpc::Call {
+		match self {
+			BatchEntry::Call { method, params } => jsonrpc::Call::MethodCall(jsonrpc::MethodCall {
+				jsonrpc: jsonrpc::Version::V2,
+				method,
+				params,
+				id: jsonrpc::Id::Num(id),
+			}),
+			BatchEntry::Notification { method, params } => {
+				jsonrpc::Call::Notification(jsonrpc::Notification { jsonrpc: jsonrpc::Version::V2, method, params })
+			}
+		}
+	}
+}
+
+/// A `(method, params)` pair is a method call.
+impl<M: Into<String>, P: Into<jsonrpc::Params>> From<(M, P)> for BatchEntry {
+	fn from((method, params): (M, P)) -> Self {
+		BatchEntry::call(method, params)
+	}
+}
+
+/// Matches the response of the server to a batch with its method calls, whose request IDs are
+/// `ids` in the order of the batch.
+///
+/// Returns one result per method call, see [`Client::batch_request`].
+pub fn batch_results(
+	ids: Vec<u64>,
+	response: jsonrpc::Response,
+) -> Result<Vec<Result<jsonrpc::JsonValue, Error>>, Error> {
+	let responses = match response {
+		jsonrpc::Response::Batch(rps) => rps,
+		// The server couldn't process the batch as a whole.
+		jsonrpc::Response::Single(jsonrpc::Output::Failure(failure)) => return Err(Error::Request(failure.error)),
+		jsonrpc::Response::Single(_) => {
+			return Err(Error::Custom("Server replied with single response to a batch request".to_string()))
+		}
+		jsonrpc::Response::Notif(_) | jsonrpc::Response::SubscriptionClosed(_) => {
+			return Err(Error::Custom("Server replied with notification response to batch request".to_string()))
+		}
+	};
+
+	let mut responses_by_id = HashMap::with_capacity(responses.len());
+	for rp in responses {
+		let id = *rp.id().as_number().ok_or(Error::InvalidRequestId)?;
+		if responses_by_id.insert(id, rp).is_some() {
+			return Err(Error::DuplicateRequestId);
+		}
+	}
+
+	let results = ids
+		.into_iter()
+		.map(|id| match responses_by_id.remove(&id) {
+			Some(rp) => rp.try_into().map_err(Error::Request),
+			None => Err(Error::Custom(format!("Server didn't answer request ID: {}", id))),
+		})
+		.collect();
+	// Responses that don't correspond to any call of the batch.
+	if !responses_by_id.is_empty() {
+		return Err(Error::InvalidRequestId);
+	}
+	Ok(results)
+}
+
+/// Deserializes the results of a batch returned by a client.
+pub fn parse_batch_results<T: jsonrpc::DeserializeOwned>(
+	results: Vec<Result<jsonrpc::JsonValue, Error>>,
+) -> Vec<Result<T, Error>> {
+	results
+		.into_iter()
+		.map(|result| result.and_then(|value| jsonrpc::from_value(value).map_err(Error::ParseError)))
+		.collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{parse_batch_results, BatchEntry};
+	use crate::error::Error;
+	use crate::jsonrpc::{self, JsonValue, Params};
+
+	#[test]
+	fn batch_entry_into_call() {
+		let call = BatchEntry::from(("foo", Params::None)).into_call(3);
+		assert_eq!(
+			call,
+			jsonrpc::Call::MethodCall(jsonrpc::MethodCall {
+				jsonrpc: jsonrpc::Version::V2,
+				method: "foo".into(),
+				params: Params::None,
+				id: jsonrpc::Id::Num(3),
+			})
+		);
+
+		let notif = BatchEntry::notification("bar", Params::Array(vec![1.into()])).into_call(4);
+		assert_eq!(
+			notif,
+			jsonrpc::Call::Notification(jsonrpc::Notification {
+				jsonrpc: jsonrpc::Version::V2,
+				method: "bar".into(),
+				params: Params::Array(vec![1.into()]),
+			})
+		);
+	}
+
+	#[test]
+	fn parse_batch_results_works() {
+		let results = vec![Ok(JsonValue::from(1)), Ok(JsonValue::from("oops")), Err(Error::Custom("failed".into()))];
+		let parsed: Vec<Result<u64, Error>> = parse_batch_results(results);
+		assert_eq!(parsed.len(), 3);
+		assert_eq!(*parsed[0].as_ref().unwrap(), 1);
+		assert!(matches!(parsed[1], Err(Error::ParseError(_))));
+		assert!(matches!(&parsed[2], Err(Error::Custom(msg)) if msg == "failed"));
+	}
+}
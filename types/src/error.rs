@@ -48,6 +48,15 @@ pub enum Error {
 	/// Websocket request timeout
 	#[error("Websocket request timeout")]
 	WsRequestTimeout,
+	/// The server was already stopped.
+	#[error("The server was already stopped")]
+	AlreadyStopped,
+	/// The subscriber has unsubscribed or has disconnected.
+	#[error("The subscription was closed")]
+	SubscriptionClosed,
+	/// The connection to the server was lost before the request was answered.
+	#[error("The connection was lost before the request was answered")]
+	ConnectionLost,
 	/// Custom error.
 	#[error("Custom error: {0}")]
 	Custom(String),
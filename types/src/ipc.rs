@@ -0,0 +1,32 @@
+//! Shared IPC types
+
+/// Default maximum message size (10 MB).
+const DEFAULT_MAX_MESSAGE_SIZE_TEN_MB: u32 = 10 * 1024 * 1024;
+
+/// How JSON messages are delimited on an IPC byte stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Framing {
+	/// Each message is followed by a line feed (`\n`), as expected by geth-style clients. Empty
+	/// lines are ignored.
+	Newline,
+	/// Each message is preceded by its length in bytes, as a 32-bit big-endian integer.
+	LengthDelimited,
+	/// Each message is preceded by a `Content-Length: <bytes>` header and an empty line, as in the
+	/// Language Server Protocol. Other headers are ignored.
+	ContentLength,
+}
+
+/// IPC configuration.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct IpcConfig {
+	/// How messages are delimited.
+	pub framing: Framing,
+	/// Maximum size of a message in bytes.
+	pub max_message_size: u32,
+}
+
+impl Default for IpcConfig {
+	fn default() -> Self {
+		Self { framing: Framing::Newline, max_message_size: DEFAULT_MAX_MESSAGE_SIZE_TEN_MB }
+	}
+}
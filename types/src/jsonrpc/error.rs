@@ -178,6 +178,27 @@ impl Error {
 		Self::new(ErrorCode::InternalError)
 	}
 
+	/// Creates new `ServerError` indicating that the server is shutting down and won't process
+	/// the request.
+	pub fn server_shutting_down() -> Self {
+		Error { code: ErrorCode::ServerError(-32001), message: "Server is shutting down".to_owned(), data: None }
+	}
+
+	/// Creates new `ServerError` indicating that the server is too busy to process the request.
+	pub fn server_busy() -> Self {
+		Error {
+			code: ErrorCode::ServerError(-32002),
+			message: "Server is busy, try again later".to_owned(),
+			data: None,
+		}
+	}
+
+	/// Creates new `ServerError` indicating that the request exceeds the maximum size accepted by
+	/// the server.
+	pub fn request_too_large() -> Self {
+		Error { code: ErrorCode::ServerError(-32003), message: "Request is too large".to_owned(), data: None }
+	}
+
 	/// Creates new `InvalidRequest` with invalid version description
 	pub fn invalid_version() -> Self {
 		Error {
@@ -51,6 +51,7 @@ pub use self::id::Id;
 pub use self::params::Params;
 pub use self::request::{Call, MethodCall, Notification, Request};
 pub use self::response::{
-	Failure, Output, Response, SubscriptionId, SubscriptionNotif, SubscriptionNotifParams, Success,
+	Failure, Output, Response, SubscriptionClosed, SubscriptionClosedParams, SubscriptionClosedReason, SubscriptionId,
+	SubscriptionNotif, SubscriptionNotifParams, Success,
 };
 pub use self::version::Version;
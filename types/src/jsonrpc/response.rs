@@ -46,6 +46,8 @@ pub enum Response {
 	Batch(Vec<Output>),
 	/// Notification to an active subscription.
 	Notif(SubscriptionNotif),
+	/// Notification that a subscription has been closed by the server.
+	SubscriptionClosed(SubscriptionClosed),
 }
 
 impl fmt::Display for Response {
@@ -111,6 +113,40 @@ pub struct SubscriptionNotifParams {
 	pub result: JsonValue,
 }
 
+/// Server notification that a subscription has been closed and won't produce any notification
+/// anymore.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubscriptionClosed {
+	/// Protocol version
+	pub jsonrpc: Version,
+	/// A String containing the name of the method that was used for the subscription.
+	pub method: String,
+	/// Parameters of the notification.
+	pub params: SubscriptionClosedParams,
+}
+
+/// Field of a [`SubscriptionClosed`].
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubscriptionClosedParams {
+	/// Subscription id, as communicated during the subscription.
+	pub subscription: SubscriptionId,
+	/// Why the server has closed the subscription.
+	pub reason: SubscriptionClosedReason,
+}
+
+/// Reason why a server has closed a subscription.
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+#[serde(rename_all = "camelCase")]
+pub enum SubscriptionClosedReason {
+	/// The subscription has sent all its notifications.
+	Completed,
+	/// The subscription has been terminated because of an error.
+	Error(Error),
+}
+
 /// Id of a subscription, communicated by the server.
 #[derive(Debug, PartialEq, Clone, Hash, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -201,7 +237,10 @@ impl SubscriptionId {
 
 #[cfg(test)]
 mod tests {
-	use super::{Error, Failure, Id, Output, Response, Success, Version};
+	use super::{
+		Error, Failure, Id, Output, Response, SubscriptionClosed, SubscriptionClosedParams, SubscriptionClosedReason,
+		SubscriptionId, Success, Version,
+	};
 	use serde_json::Value;
 
 	#[test]
@@ -294,4 +333,26 @@ mod tests {
 		assert!(deserialized1.is_err(), "Empty string is not valid JSON, so we should get an error.");
 		assert_eq!(deserialized2.unwrap(), Response::Batch(vec![]));
 	}
+
+	#[test]
+	fn subscription_closed_roundtrip() {
+		let completed = r#"{"jsonrpc":"2.0","method":"hello","params":{"subscription":"abc","reason":"completed"}}"#;
+		let errored = r#"{"jsonrpc":"2.0","method":"hello","params":{"subscription":"abc","reason":{"error":{"code":-32603,"message":"Internal error"}}}}"#;
+
+		for (serialized, reason) in [
+			(completed, SubscriptionClosedReason::Completed),
+			(errored, SubscriptionClosedReason::Error(Error::internal_error())),
+		]
+		.iter()
+		.cloned()
+		{
+			let expected = Response::SubscriptionClosed(SubscriptionClosed {
+				jsonrpc: Version::V2,
+				method: "hello".to_owned(),
+				params: SubscriptionClosedParams { subscription: SubscriptionId::Str("abc".to_owned()), reason },
+			});
+			assert_eq!(serde_json::from_str::<Response>(serialized).unwrap(), expected);
+			assert_eq!(serde_json::to_string(&expected).unwrap(), serialized);
+		}
+	}
 }
@@ -76,8 +76,9 @@ pub enum BatchesEvent<'a, T> {
 
 	/// A batch has gotten all its requests answered and a response is ready to be sent out.
 	ReadyToSend {
-		/// Response to send out to the JSON-RPC client.
-		response: jsonrpc::Response,
+		/// Response to send out to the JSON-RPC client, or `None` if the batch only contained
+		/// notifications, in which case nothing must be sent back.
+		response: Option<jsonrpc::Response>,
 		/// User parameter passed when calling [`inject`](BatchesState::inject).
 		user_param: T,
 	},
@@ -94,7 +95,7 @@ pub struct BatchesElem<'a, T> {
 }
 
 /// Identifier of a request within a [`BatchesState`].
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct BatchesElemId {
 	/// Id of the batch within `BatchesState::batches`.
 	outer: u64,
@@ -153,9 +154,7 @@ impl<T> BatchesState<T> {
 						self.batches.remove(&batch_id).expect("key was grabbed from self.batches; qed");
 					let response =
 						batch.into_response().unwrap_or_else(|_| panic!("is_ready_to_respond returned true; qed"));
-					if let Some(response) = response {
-						return Some(BatchesEvent::ReadyToSend { response, user_param });
-					}
+					return Some(BatchesEvent::ReadyToSend { response, user_param });
 				}
 				WhatCanWeDo::Notification(notification) => {
 					return Some(BatchesEvent::Notification {
@@ -313,6 +312,7 @@ mod tests {
 			}
 			_ => panic!(),
 		}
+		assert!(matches!(state.next_event(), Some(BatchesEvent::ReadyToSend { response: None, .. })));
 		assert!(state.next_event().is_none());
 	}
 
@@ -355,7 +355,7 @@ mod tests {
 			Some(BatchesEvent::ReadyToSend { response, user_param }) => {
 				assert_eq!(user_param, 8889);
 				match response {
-					jsonrpc::Response::Single(jsonrpc::Output::Failure(f)) => {
+					Some(jsonrpc::Response::Single(jsonrpc::Output::Failure(f))) => {
 						assert_eq!(f.id, jsonrpc::Id::Num(123));
 					}
 					_ => panic!(),
@@ -370,6 +370,7 @@ mod tests {
 		let mut state = BatchesState::new();
 		assert!(state.next_event().is_none());
 		state.inject(jsonrpc::Request::Batch(Vec::new()), ());
+		assert!(matches!(state.next_event(), Some(BatchesEvent::ReadyToSend { response: None, .. })));
 		assert!(state.next_event().is_none());
 	}
 
@@ -409,6 +410,11 @@ mod tests {
 			_ => panic!(),
 		}
 
+		match state.next_event() {
+			Some(BatchesEvent::ReadyToSend { response: None, user_param: 2 }) => {}
+			_ => panic!(),
+		}
+
 		assert!(state.next_event().is_none());
 	}
 }
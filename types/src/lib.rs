@@ -8,8 +8,20 @@ extern crate alloc;
 /// JSON-RPC 2.0 specification related types.
 pub mod jsonrpc;
 
+/// Shared types for clients
+pub mod client;
+
 /// Shared error type.
 pub mod error;
 
 /// Shared types for HTTP
 pub mod http;
+
+/// Shared types for IPC
+pub mod ipc;
+
+/// Shared types for servers
+pub mod server;
+
+/// Shared types for WebSocket
+pub mod ws;
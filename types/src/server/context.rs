@@ -0,0 +1,130 @@
+//! Information about a request, made available to the method handlers.
+
+use crate::jsonrpc;
+
+use alloc::sync::Arc;
+use core::fmt;
+use std::{net::SocketAddr, sync::Mutex};
+
+/// Information about a request and about the connection it has been received on.
+#[derive(Clone, Debug)]
+pub struct RequestContext {
+	/// Id of the request, as sent by the client.
+	id: jsonrpc::Id,
+	/// Address of the client.
+	remote_addr: Option<SocketAddr>,
+	/// Identifier of the connection, unique within a server.
+	connection_id: Option<u64>,
+	/// Headers of the HTTP request the JSON-RPC request was part of.
+	headers: Option<Arc<http::HeaderMap>>,
+	/// Values attached to the connection.
+	extensions: ConnectionExtensions,
+}
+
+/// Typed values attached to a connection, shared by all the requests received on that connection.
+///
+/// Can be cloned. All the clones refer to the same values.
+#[derive(Clone, Default)]
+pub struct ConnectionExtensions {
+	inner: Arc<Mutex<http::Extensions>>,
+}
+
+impl RequestContext {
+	/// Creates a new context for a request received on a connection with the given extensions.
+	pub fn new(extensions: ConnectionExtensions) -> Self {
+		RequestContext { id: jsonrpc::Id::Null, remote_addr: None, connection_id: None, headers: None, extensions }
+	}
+
+	/// Sets the id of the request.
+	pub fn with_id(mut self, id: jsonrpc::Id) -> Self {
+		self.id = id;
+		self
+	}
+
+	/// Sets the address of the client.
+	pub fn with_remote_addr(mut self, remote_addr: SocketAddr) -> Self {
+		self.remote_addr = Some(remote_addr);
+		self
+	}
+
+	/// Sets the identifier of the connection.
+	pub fn with_connection_id(mut self, connection_id: u64) -> Self {
+		self.connection_id = Some(connection_id);
+		self
+	}
+
+	/// Sets the HTTP headers.
+	pub fn with_headers(mut self, headers: http::HeaderMap) -> Self {
+		self.headers = Some(Arc::new(headers));
+		self
+	}
+
+	/// Returns the id of the request, as sent by the client.
+	pub fn id(&self) -> &jsonrpc::Id {
+		&self.id
+	}
+
+	/// Returns the address of the client, if known.
+	pub fn remote_addr(&self) -> Option<SocketAddr> {
+		self.remote_addr
+	}
+
+	/// Returns the identifier of the connection, if the transport has a notion of long-lived
+	/// connection (e.g. WebSocket).
+	pub fn connection_id(&self) -> Option<u64> {
+		self.connection_id
+	}
+
+	/// Returns the headers of the HTTP request, if the request has been received over HTTP.
+	pub fn headers(&self) -> Option<&http::HeaderMap> {
+		self.headers.as_deref()
+	}
+
+	/// Returns the values attached to the connection.
+	pub fn extensions(&self) -> &ConnectionExtensions {
+		&self.extensions
+	}
+}
+
+impl ConnectionExtensions {
+	/// Attaches a value to the connection.
+	///
+	/// Returns the previous value of the same type, if any.
+	pub fn insert<T: Send + Sync + 'static>(&self, value: T) -> Option<T> {
+		self.inner.lock().expect("lock poisoned").insert(value)
+	}
+
+	/// Returns a copy of the value of type `T` attached to the connection, if any.
+	pub fn get<T: Clone + Send + Sync + 'static>(&self) -> Option<T> {
+		self.inner.lock().expect("lock poisoned").get::<T>().cloned()
+	}
+
+	/// Detaches the value of type `T` from the connection and returns it, if any.
+	pub fn remove<T: Send + Sync + 'static>(&self) -> Option<T> {
+		self.inner.lock().expect("lock poisoned").remove::<T>()
+	}
+}
+
+impl fmt::Debug for ConnectionExtensions {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.debug_struct("ConnectionExtensions").finish()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{ConnectionExtensions, RequestContext};
+
+	#[test]
+	fn extensions_are_shared_by_the_connection() {
+		let extensions = ConnectionExtensions::default();
+		let first = RequestContext::new(extensions.clone());
+		let second = RequestContext::new(extensions);
+
+		assert_eq!(first.extensions().insert(5u32), None);
+		assert_eq!(second.extensions().get::<u32>(), Some(5));
+		assert_eq!(second.extensions().insert(6u32), Some(5));
+		assert_eq!(first.extensions().remove::<u32>(), Some(6));
+		assert_eq!(second.extensions().get::<u32>(), None);
+	}
+}
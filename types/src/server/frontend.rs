@@ -0,0 +1,528 @@
+//! Transport-generic front-end and background task of the servers that don't support
+//! subscriptions.
+
+use crate::error::Error;
+use crate::jsonrpc::{self, DeserializeOwned, JsonValue, Serialize};
+use crate::server::{
+	method_callback, method_callback_with_context, next_queued_request, poll_waiting_requests, MethodCallback,
+	MethodConfig, MethodQueue, MethodQueueRx, Middleware, Pushed, RawServer, RawServerEvent, RawServerRequestId,
+	RequestContext, RpcModule, TransportServer,
+};
+
+use alloc::sync::Arc;
+use futures::{
+	channel::{mpsc, oneshot},
+	future::{BoxFuture, Either},
+	pin_mut,
+	prelude::*,
+};
+use parking_lot::Mutex;
+use std::collections::{HashMap, HashSet};
+
+/// Spawns the tasks of a server on the runtime it runs on.
+pub type Spawner = Arc<dyn Fn(BoxFuture<'static, ()>) + Send + Sync>;
+
+/// Handle to the background task of a server, that registers methods towards it.
+///
+/// This is the part of the servers that doesn't depend on the transport. It can be cloned.
+#[derive(Clone)]
+pub struct Frontend {
+	/// Channel to send requests to the background task.
+	to_back: mpsc::UnboundedSender<FrontToBack>,
+	/// List of methods (for RPC queries and notifications) that have been
+	/// registered. Serves no purpose except to check for duplicates.
+	registered_methods: Arc<Mutex<HashSet<String>>>,
+	/// Spawns the tasks that process the requests of the methods registered through
+	/// [`register_async_method`](Frontend::register_async_method) and [`register_module`](Frontend::register_module).
+	spawner: Spawner,
+}
+
+/// Notification method that's been registered.
+pub struct RegisteredNotification {
+	/// Receives notifications that the client sent to us.
+	queries_rx: mpsc::Receiver<jsonrpc::Params>,
+}
+
+/// Method that's been registered.
+pub struct RegisteredMethod {
+	/// Clone of [`Frontend::to_back`].
+	to_back: mpsc::UnboundedSender<FrontToBack>,
+	/// Receives requests that the client sent to us.
+	queries_rx: MethodQueueRx<RawServerRequestId>,
+}
+
+/// Active request that needs to be answered.
+pub struct IncomingRequest {
+	/// Clone of [`Frontend::to_back`].
+	to_back: mpsc::UnboundedSender<FrontToBack>,
+	/// Identifier of the request towards the server.
+	request_id: RawServerRequestId,
+	/// Parameters of the request.
+	params: jsonrpc::Params,
+	/// Information about the request and the connection it was received on.
+	context: RequestContext,
+}
+
+/// Message that the [`Frontend`] can send to the background task.
+enum FrontToBack {
+	/// Registers a notifications endpoint.
+	RegisterNotifications {
+		/// Name of the method.
+		name: String,
+		/// Where to send incoming notifications.
+		handler: mpsc::Sender<jsonrpc::Params>,
+		/// See the documentation of [`Frontend::register_notification`].
+		allow_losses: bool,
+	},
+
+	/// Registers a method. The server will then handle requests using this method.
+	RegisterMethod {
+		/// Name of the method.
+		name: String,
+		/// Where to send requests.
+		queue: MethodQueue<RawServerRequestId>,
+	},
+
+	/// Adds a middleware at the bottom of the stack of middlewares.
+	AddMiddleware(Arc<dyn Middleware>),
+
+	/// Send a response to a request that a client made.
+	AnswerRequest {
+		/// Request to answer.
+		request_id: RawServerRequestId,
+		/// Response to send back.
+		answer: Result<JsonValue, jsonrpc::Error>,
+	},
+
+	/// Starts shutting down the server. The background task stops accepting requests and
+	/// terminates once all the requests that were dispatched to the handlers have been answered.
+	Shutdown {
+		/// Notified when the background task has terminated.
+		done: oneshot::Sender<()>,
+	},
+
+	/// The shutdown deadline has expired; the background task must terminate without waiting for
+	/// the pending requests.
+	ForceShutdown,
+}
+
+impl Frontend {
+	/// Spawns the background task processing the requests received by `transport_server`, and
+	/// returns a handle to it.
+	pub fn start<T>(transport_server: T, spawner: Spawner) -> Self
+	where
+		T: TransportServer + Send + 'static,
+	{
+		// We use an unbounded channel because the only exchanged messages concern registering
+		// methods. The volume of messages is therefore very low and it doesn't make sense to have
+		// a backpressure mechanism.
+		// TODO: that's not true anymore ^
+		let (to_back, from_front) = mpsc::unbounded();
+
+		spawner(background_task(transport_server.into(), from_front).boxed());
+
+		Frontend { to_back, registered_methods: Arc::new(Mutex::new(HashSet::new())), spawner }
+	}
+
+	/// Stops the background task gracefully.
+	///
+	/// No new requests are processed, and the background task waits until `deadline` resolves for
+	/// the requests that are being handled to be answered. Afterwards, the responses are sent out
+	/// and the transport server is closed.
+	///
+	/// Returns an error if the server was already stopped.
+	pub async fn stop(&self, deadline: impl Future<Output = ()>) -> Result<(), Error> {
+		let (done_tx, mut done_rx) = oneshot::channel();
+		self.to_back.unbounded_send(FrontToBack::Shutdown { done: done_tx }).map_err(|_| Error::AlreadyStopped)?;
+
+		pin_mut!(deadline);
+		let done = match future::select(&mut done_rx, deadline).await {
+			Either::Left((done, _)) => done,
+			Either::Right(((), _)) => {
+				log::warn!("[frontend]: pending requests not answered before the deadline; forcing shutdown");
+				let _ = self.to_back.unbounded_send(FrontToBack::ForceShutdown);
+				done_rx.await
+			}
+		};
+
+		// The background task drops `done` without notifying it if it was already shutting down.
+		done.map_err(|_| Error::AlreadyStopped)
+	}
+
+	/// Adds a middleware, called around every method call made to the server.
+	///
+	/// Middlewares are called in the order they have been added when a request is received, and
+	/// in the reverse order when it is answered. See [`Middleware`] for more information.
+	///
+	/// Requests received before the middleware has been added don't go through it.
+	pub fn add_middleware(&self, middleware: impl Middleware) -> Result<(), Error> {
+		log::trace!("[frontend]: add_middleware");
+		self.to_back
+			.unbounded_send(FrontToBack::AddMiddleware(Arc::new(middleware)))
+			.map_err(|e| Error::Internal(e.into_send_error()))
+	}
+
+	/// Registers a notification method name towards the server.
+	///
+	/// Clients will then be able to call this method.
+	/// The returned object allows you to process incoming notifications.
+	///
+	/// If `allow_losses` is true, then the server is allowed to drop notifications if the
+	/// notifications handler (i.e. the code that uses [`RegisteredNotification`]) is too slow
+	/// to process notifications.
+	///
+	/// Returns an error if the method name was already registered.
+	pub fn register_notification(
+		&self,
+		method_name: String,
+		allow_losses: bool,
+	) -> Result<RegisteredNotification, Error> {
+		if !self.registered_methods.lock().insert(method_name.clone()) {
+			return Err(Error::MethodAlreadyRegistered(method_name));
+		}
+
+		log::trace!("[frontend]: register_notification={}", method_name);
+		let (tx, rx) = mpsc::channel(32);
+
+		self.to_back
+			.unbounded_send(FrontToBack::RegisterNotifications { name: method_name, handler: tx, allow_losses })
+			.map_err(|e| Error::Internal(e.into_send_error()))?;
+
+		Ok(RegisteredNotification { queries_rx: rx })
+	}
+
+	/// Registers a method towards the server.
+	///
+	/// Clients will then be able to call this method.
+	/// The returned object allows you to handle incoming requests.
+	///
+	/// If the handler is too slow to process requests, then the server automatically returns a
+	/// "server busy" error to the client. See also
+	/// [`register_method_with_config`](Frontend::register_method_with_config).
+	///
+	/// Returns an error if the method name was already registered.
+	pub fn register_method(&self, method_name: String) -> Result<RegisteredMethod, Error> {
+		self.register_method_with_config(method_name, MethodConfig::default())
+	}
+
+	/// Registers a method towards the server, with a custom configuration for its queue of
+	/// requests.
+	///
+	/// See [`register_method`](Frontend::register_method) for more information.
+	pub fn register_method_with_config(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+	) -> Result<RegisteredMethod, Error> {
+		if !self.registered_methods.lock().insert(method_name.clone()) {
+			return Err(Error::MethodAlreadyRegistered(method_name));
+		}
+
+		self.register_reserved_method(method_name, config)
+	}
+
+	/// Registers a method whose name has already been inserted in `registered_methods`.
+	fn register_reserved_method(&self, method_name: String, config: MethodConfig) -> Result<RegisteredMethod, Error> {
+		log::trace!("[frontend]: register_method={}, config={:?}", method_name, config);
+		let (queue, rx) = MethodQueue::new(config);
+
+		self.to_back
+			.unbounded_send(FrontToBack::RegisterMethod { name: method_name, queue })
+			.map_err(|e| Error::Internal(e.into_send_error()))?;
+
+		Ok(RegisteredMethod { to_back: self.to_back.clone(), queries_rx: rx })
+	}
+
+	/// Registers a method towards the server and lets the server drive its handler.
+	///
+	/// Each incoming request is processed in a separate task. The parameters of the request are
+	/// deserialized into `P` and passed to `callback`, then the `Result` that the returned
+	/// `Future` resolves to is serialized and sent back to the client.
+	///
+	/// If the parameters can't be deserialized into `P`, the client receives an "invalid params"
+	/// error and `callback` isn't called.
+	///
+	/// Returns an error if the method name was already registered.
+	pub fn register_async_method<F, Fut, P, T, E>(&self, method_name: String, callback: F) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.register_async_method_with_config(method_name, MethodConfig::default(), callback)
+	}
+
+	/// Registers a method towards the server and lets the server drive its handler, with a custom
+	/// configuration for its queue of requests.
+	///
+	/// See [`register_async_method`](Frontend::register_async_method) for more information.
+	pub fn register_async_method_with_config<F, Fut, P, T, E>(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.register_method_callback(method_name, config, method_callback(callback))
+	}
+
+	/// Same as [`register_async_method`](Frontend::register_async_method), except that `callback`
+	/// is also passed the [`RequestContext`] of each request.
+	pub fn register_async_method_with_context<F, Fut, P, T, E>(
+		&self,
+		method_name: String,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P, RequestContext) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.register_method_callback(method_name, MethodConfig::default(), method_callback_with_context(callback))
+	}
+
+	/// Registers all the methods of `module` towards the server. The subscriptions of the module
+	/// are ignored.
+	///
+	/// Returns an error and doesn't register anything if one of the method names was already
+	/// registered.
+	pub fn register_module(&self, module: &RpcModule) -> Result<(), Error> {
+		// All the names are reserved at once, so that a concurrent registration can't make this
+		// one fail halfway.
+		{
+			let mut registered_methods = self.registered_methods.lock();
+			if let Some((name, _)) = module.methods().find(|(name, _)| registered_methods.contains(*name)) {
+				return Err(Error::MethodAlreadyRegistered(name.to_owned()));
+			}
+			registered_methods.extend(module.methods().map(|(name, _)| name.to_owned()));
+		}
+
+		for (name, method) in module.methods() {
+			let registered = self.register_reserved_method(name.to_owned(), method.config())?;
+			self.spawn_method_handler(registered, method.callback());
+		}
+
+		Ok(())
+	}
+
+	/// Registers a method whose requests are each answered by `callback` in a separate task.
+	fn register_method_callback(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+		callback: MethodCallback,
+	) -> Result<(), Error> {
+		let registered = self.register_method_with_config(method_name, config)?;
+		self.spawn_method_handler(registered, callback);
+		Ok(())
+	}
+
+	/// Spawns a task that answers each request of `method` with `callback` in a separate task.
+	fn spawn_method_handler(&self, method: RegisteredMethod, callback: MethodCallback) {
+		let RegisteredMethod { to_back, queries_rx } = method;
+		let spawner = self.spawner.clone();
+		(self.spawner)(
+			async move {
+				// The loop ends when the background task shuts down and drops the sending side.
+				while let Some((request_id, params, context)) = next_queued_request(&queries_rx).await {
+					let request = IncomingRequest { to_back: to_back.clone(), request_id, params, context };
+					let callback = callback.clone();
+					spawner(
+						async move {
+							let answer = callback(request.params.clone(), request.context.clone()).await;
+							if let Err(err) = request.respond(answer).await {
+								log::error!("[frontend]: failed to respond to request: {:?}", err);
+							}
+						}
+						.boxed(),
+					);
+				}
+			}
+			.boxed(),
+		);
+	}
+}
+
+impl RegisteredNotification {
+	/// Returns the next notification.
+	pub async fn next(&mut self) -> jsonrpc::Params {
+		loop {
+			match self.queries_rx.next().await {
+				Some(v) => break v,
+				None => futures::pending!(),
+			}
+		}
+	}
+}
+
+impl RegisteredMethod {
+	/// Returns the next request.
+	pub async fn next(&mut self) -> IncomingRequest {
+		let (request_id, params, context) = loop {
+			match next_queued_request(&self.queries_rx).await {
+				Some(v) => break v,
+				None => futures::pending!(),
+			}
+		};
+		IncomingRequest { to_back: self.to_back.clone(), request_id, params, context }
+	}
+}
+
+impl IncomingRequest {
+	/// Returns the parameters of the request.
+	pub fn params(&self) -> &jsonrpc::Params {
+		&self.params
+	}
+
+	/// Returns information about the request and the connection it was received on.
+	pub fn context(&self) -> &RequestContext {
+		&self.context
+	}
+
+	/// Respond to the request.
+	pub async fn respond(mut self, response: impl Into<Result<JsonValue, jsonrpc::Error>>) -> Result<(), Error> {
+		self.to_back
+			.send(FrontToBack::AnswerRequest { request_id: self.request_id, answer: response.into() })
+			.await
+			.map_err(Error::Internal)
+	}
+}
+
+/// Function being run in the background that processes messages from the frontend.
+async fn background_task<T: TransportServer>(
+	mut server: RawServer<T>,
+	mut from_front: mpsc::UnboundedReceiver<FrontToBack>,
+) {
+	// List of notifications methods that the user has registered, and the channels to dispatch
+	// incoming notifications.
+	let mut registered_notifications: HashMap<String, (mpsc::Sender<_>, bool)> = HashMap::new();
+	// List of methods that the user has registered, and the channels to dispatch incoming
+	// requests.
+	let mut registered_methods: HashMap<String, MethodQueue<RawServerRequestId>> = HashMap::new();
+	// Number of requests that have been dispatched to a handler and not answered yet.
+	let mut pending_requests: usize = 0;
+	// If the server is shutting down, where to notify that the background task has terminated.
+	let mut shutdown: Option<oneshot::Sender<()>> = None;
+
+	loop {
+		if let (Some(_), 0) = (&shutdown, pending_requests) {
+			server.close().await;
+			log::trace!("[backend]: background_task terminated");
+			let _ = shutdown.take().expect("checked above; qed").send(());
+			return;
+		}
+
+		// We need to do a little transformation in order to destroy the borrow to `client`
+		// and `from_front`.
+		let outcome = {
+			// A request waiting for room in the queue of its method is answered like the other
+			// requests if it can't be queued.
+			let next_message = future::select(
+				from_front.next(),
+				future::poll_fn(|cx| poll_waiting_requests(registered_methods.values_mut(), cx)),
+			)
+			.map(|outcome| match outcome {
+				Either::Left((message, _)) => message,
+				Either::Right((request_id, _)) => {
+					Some(FrontToBack::AnswerRequest { request_id, answer: Err(jsonrpc::Error::internal_error()) })
+				}
+			});
+			let next_event = server.next_event();
+			pin_mut!(next_message);
+			pin_mut!(next_event);
+			match future::select(next_message, next_event).await {
+				Either::Left((v, _)) => Either::Left(v),
+				Either::Right((v, _)) => Either::Right(v),
+			}
+		};
+
+		match outcome {
+			Either::Left(None) => {
+				// Every handle to the server has been dropped; close the connections properly.
+				server.close().await;
+				log::trace!("[backend]: background_task terminated");
+				return;
+			}
+			Either::Left(Some(FrontToBack::AnswerRequest { request_id, answer })) => {
+				log::trace!("[backend]: answer_request: {:?} id: {:?}", answer, request_id);
+				pending_requests = pending_requests.saturating_sub(1);
+				if let Some(request) = server.request_by_id(&request_id) {
+					request.respond(answer);
+				}
+			}
+			Either::Left(Some(FrontToBack::Shutdown { done })) => {
+				// If the server is already shutting down, `done` is dropped and the caller gets
+				// notified that the server was already stopped.
+				if shutdown.is_none() {
+					log::trace!("[backend]: shutting down; {} pending request(s)", pending_requests);
+					shutdown = Some(done);
+				}
+			}
+			Either::Left(Some(FrontToBack::ForceShutdown)) => {
+				pending_requests = 0;
+			}
+			Either::Left(Some(FrontToBack::RegisterNotifications { name, handler, allow_losses })) => {
+				log::trace!("[backend]: register_notification: {:?}", name);
+				registered_notifications.insert(name, (handler, allow_losses));
+			}
+			Either::Left(Some(FrontToBack::RegisterMethod { name, queue })) => {
+				log::trace!("[backend]: register_method: {:?}", name);
+				registered_methods.insert(name, queue);
+			}
+			Either::Left(Some(FrontToBack::AddMiddleware(middleware))) => {
+				log::trace!("[backend]: add_middleware");
+				server.add_middleware(middleware);
+			}
+			Either::Right(RawServerEvent::Notification(_)) if shutdown.is_some() => {}
+			Either::Right(RawServerEvent::Request(request)) if shutdown.is_some() => {
+				log::trace!("[backend]: refusing request while shutting down: {:?}", request);
+				request.respond(Err(jsonrpc::Error::server_shutting_down()));
+			}
+			Either::Right(RawServerEvent::Notification(notification)) => {
+				log::trace!("[backend]: received notification: {:?}", notification);
+				if let Some((handler, allow_losses)) = registered_notifications.get_mut(notification.method()) {
+					let params: &jsonrpc::Params = notification.params().into();
+					// Note: we just ignore errors. It doesn't make sense logically speaking to
+					// unregister the notification here.
+					if *allow_losses {
+						if !matches!(handler.send(params.clone()).now_or_never(), Some(Ok(()))) {
+							server.metrics().notification_dropped(notification.method());
+						}
+					} else {
+						let _ = handler.send(params.clone()).await;
+					}
+				}
+			}
+			Either::Right(RawServerEvent::Request(request)) => {
+				if let Some(queue) = registered_methods.get_mut(request.method()) {
+					log::trace!("[backend]: received request: {:?}", request);
+					let params: &jsonrpc::Params = request.params().into();
+					match queue.push((request.id(), params.clone(), request.context().clone())) {
+						Pushed::Queued => pending_requests += 1,
+						Pushed::Refused(err) => request.respond(Err(err)),
+						Pushed::ShedOldest(oldest_id) => {
+							// From here on, `request` can't be used anymore as we need to borrow
+							// `server` in order to answer the oldest request.
+							log::debug!("[backend]: queue full; shedding request {:?}", oldest_id);
+							if let Some(oldest) = server.request_by_id(&oldest_id) {
+								oldest.respond(Err(jsonrpc::Error::server_busy()));
+							}
+						}
+					}
+				} else {
+					request.respond(Err(From::from(jsonrpc::ErrorCode::MethodNotFound)));
+				}
+			}
+		}
+	}
+}
@@ -0,0 +1,311 @@
+//! Counters and histograms maintained by the servers, exposed in the Prometheus text format.
+
+use crate::jsonrpc::{self, JsonValue};
+use crate::server::{Middleware, RequestContext};
+
+use alloc::{borrow::ToOwned as _, collections::BTreeMap, string::String, sync::Arc, vec::Vec};
+use core::{fmt::Write as _, time::Duration};
+use std::sync::{Mutex, MutexGuard};
+
+/// Upper bounds, in seconds, of the buckets of the handler latency histograms.
+const LATENCY_BUCKETS: [f64; 11] = [0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 5.0];
+
+/// Upper bounds of the buckets of the batch sizes histogram.
+const BATCH_SIZE_BUCKETS: [f64; 8] = [1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 500.0];
+
+/// Maximum number of distinct method names reported. The calls to the other methods are
+/// reported under [`OTHER_METHOD`], so that clients calling random methods can't make the
+/// metrics grow without bounds.
+const MAX_METHOD_LABELS: usize = 256;
+
+/// Method label used once [`MAX_METHOD_LABELS`] has been reached.
+const OTHER_METHOD: &str = "<other>";
+
+/// Metrics of a server.
+///
+/// Can be cloned. All the clones refer to the same metrics. Method calls are recorded by
+/// registering the metrics as a [`Middleware`]; the other events are recorded by the servers
+/// through the dedicated methods.
+#[derive(Clone)]
+pub struct ServerMetrics {
+	/// Prefix of the name of every metric, for example `jsonrpsee_http`.
+	namespace: Arc<str>,
+	inner: Arc<Mutex<MetricsState>>,
+}
+
+#[derive(Default)]
+struct MetricsState {
+	/// Number of calls, by method.
+	requests: BTreeMap<String, u64>,
+	/// Number of calls answered with an error, by method and error code.
+	errors: BTreeMap<(String, i64), u64>,
+	/// Time between the reception of a call and its answer, by method.
+	latency: BTreeMap<String, Histogram>,
+	/// Number of requests in the batches received.
+	batch_sizes: Option<Histogram>,
+	/// Number of open connections. `None` if the server doesn't keep connections open.
+	connections: Option<u64>,
+	/// Number of active subscriptions.
+	subscriptions: u64,
+	/// Number of notifications that have been dropped, by method.
+	dropped_notifications: BTreeMap<String, u64>,
+}
+
+/// Cumulative histogram with fixed buckets.
+struct Histogram {
+	/// Upper bounds of the buckets.
+	bounds: &'static [f64],
+	/// For each bucket, the number of observations less than or equal to its bound.
+	counts: Vec<u64>,
+	/// Sum of all the observations.
+	sum: f64,
+	/// Number of observations.
+	count: u64,
+}
+
+impl ServerMetrics {
+	/// Creates empty metrics whose names all start with `namespace`.
+	pub fn new(namespace: &str) -> Self {
+		ServerMetrics { namespace: namespace.into(), inner: Default::default() }
+	}
+
+	/// Creates empty metrics whose names all start with `namespace`, and that also report the
+	/// number of open connections. Meant for the servers whose connections are long-lived.
+	pub fn with_connections(namespace: &str) -> Self {
+		let metrics = ServerMetrics::new(namespace);
+		metrics.state().connections = Some(0);
+		metrics
+	}
+
+	/// Records the reception of a batch of `size` requests.
+	pub fn on_batch(&self, size: usize) {
+		let mut state = self.state();
+		state.batch_sizes.get_or_insert_with(|| Histogram::new(&BATCH_SIZE_BUCKETS)).observe(size as f64);
+	}
+
+	/// Records the opening of a connection. Does nothing if the metrics weren't created with
+	/// [`with_connections`](ServerMetrics::with_connections).
+	pub fn connection_opened(&self) {
+		if let Some(connections) = &mut self.state().connections {
+			*connections += 1;
+		}
+	}
+
+	/// Records the closing of a connection. Does nothing if the metrics weren't created with
+	/// [`with_connections`](ServerMetrics::with_connections).
+	pub fn connection_closed(&self) {
+		if let Some(connections) = &mut self.state().connections {
+			*connections = connections.saturating_sub(1);
+		}
+	}
+
+	/// Sets the number of active subscriptions.
+	pub fn set_active_subscriptions(&self, subscriptions: usize) {
+		self.state().subscriptions = subscriptions as u64;
+	}
+
+	/// Records that a notification of `method` has been dropped, either because its handler was
+	/// too slow or because it couldn't be delivered to a subscriber.
+	pub fn notification_dropped(&self, method: &str) {
+		let mut state = self.state();
+		let method = state.method_label(method);
+		*state.dropped_notifications.entry(method).or_insert(0) += 1;
+	}
+
+	/// Returns the metrics in the Prometheus text exposition format.
+	pub fn encode(&self) -> String {
+		let state = self.state();
+		let ns = &*self.namespace;
+		let mut out = String::new();
+
+		header(&mut out, ns, "requests_total", "counter", "Number of method calls received.");
+		for (method, count) in &state.requests {
+			let _ = writeln!(out, "{}_requests_total{{method=\"{}\"}} {}", ns, escape(method), count);
+		}
+
+		header(&mut out, ns, "errors_total", "counter", "Number of method calls answered with an error.");
+		for ((method, code), count) in &state.errors {
+			let _ = writeln!(out, "{}_errors_total{{method=\"{}\",code=\"{}\"}} {}", ns, escape(method), code, count);
+		}
+
+		header(&mut out, ns, "request_duration_seconds", "histogram", "Time taken to answer method calls.");
+		for (method, histogram) in &state.latency {
+			histogram.encode(
+				&mut out,
+				&[ns, "_request_duration_seconds"].concat(),
+				&format!("method=\"{}\"", escape(method)),
+			);
+		}
+
+		header(&mut out, ns, "batch_size", "histogram", "Number of requests in the batches received.");
+		if let Some(histogram) = &state.batch_sizes {
+			histogram.encode(&mut out, &[ns, "_batch_size"].concat(), "");
+		}
+
+		if let Some(connections) = state.connections {
+			header(&mut out, ns, "connections", "gauge", "Number of open connections.");
+			let _ = writeln!(out, "{}_connections {}", ns, connections);
+		}
+
+		header(&mut out, ns, "subscriptions", "gauge", "Number of active subscriptions.");
+		let _ = writeln!(out, "{}_subscriptions {}", ns, state.subscriptions);
+
+		header(&mut out, ns, "dropped_notifications_total", "counter", "Number of notifications dropped.");
+		for (method, count) in &state.dropped_notifications {
+			let _ = writeln!(out, "{}_dropped_notifications_total{{method=\"{}\"}} {}", ns, escape(method), count);
+		}
+
+		out
+	}
+
+	fn state(&self) -> MutexGuard<'_, MetricsState> {
+		self.inner.lock().expect("lock poisoned")
+	}
+
+	/// Records the answer to a call of `method`.
+	fn on_answer(&self, method: &str, code: Option<i64>, elapsed: Duration) {
+		let mut state = self.state();
+		let method = state.method_label(method);
+		if let Some(code) = code {
+			*state.errors.entry((method.clone(), code)).or_insert(0) += 1;
+		}
+		state.latency.entry(method).or_insert_with(|| Histogram::new(&LATENCY_BUCKETS)).observe(elapsed.as_secs_f64());
+	}
+}
+
+impl Middleware for ServerMetrics {
+	fn on_request(&self, method: &str, _: &jsonrpc::Params, _: &RequestContext) -> Result<(), jsonrpc::Error> {
+		let mut state = self.state();
+		let method = state.method_label(method);
+		*state.requests.entry(method).or_insert(0) += 1;
+		Ok(())
+	}
+
+	fn on_response(&self, method: &str, _: &jsonrpc::Params, _: &RequestContext, _: &JsonValue, elapsed: Duration) {
+		self.on_answer(method, None, elapsed);
+	}
+
+	fn on_error(
+		&self,
+		method: &str,
+		_: &jsonrpc::Params,
+		_: &RequestContext,
+		error: &jsonrpc::Error,
+		elapsed: Duration,
+	) {
+		self.on_answer(method, Some(error.code.code()), elapsed);
+	}
+}
+
+impl MetricsState {
+	/// Returns the label to use for `method`.
+	fn method_label(&self, method: &str) -> String {
+		if self.requests.len() < MAX_METHOD_LABELS || self.requests.contains_key(method) {
+			method.to_owned()
+		} else {
+			OTHER_METHOD.to_owned()
+		}
+	}
+}
+
+impl Histogram {
+	fn new(bounds: &'static [f64]) -> Self {
+		Histogram { bounds, counts: alloc::vec![0; bounds.len()], sum: 0.0, count: 0 }
+	}
+
+	fn observe(&mut self, value: f64) {
+		for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+			if value <= *bound {
+				*count += 1;
+			}
+		}
+		self.sum += value;
+		self.count += 1;
+	}
+
+	/// Writes the samples of the histogram. `labels` is either empty or a list of labels
+	/// without the surrounding braces.
+	fn encode(&self, out: &mut String, name: &str, labels: &str) {
+		let sep = if labels.is_empty() { "" } else { "," };
+		for (bound, count) in self.bounds.iter().zip(&self.counts) {
+			let _ = writeln!(out, "{}_bucket{{{}{}le=\"{}\"}} {}", name, labels, sep, bound, count);
+		}
+		let _ = writeln!(out, "{}_bucket{{{}{}le=\"+Inf\"}} {}", name, labels, sep, self.count);
+		let labels = if labels.is_empty() { String::new() } else { ["{", labels, "}"].concat() };
+		let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+		let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+	}
+}
+
+/// Writes the `HELP` and `TYPE` lines of a metric.
+fn header(out: &mut String, namespace: &str, name: &str, kind: &str, help: &str) {
+	let _ = writeln!(out, "# HELP {}_{} {}", namespace, name, help);
+	let _ = writeln!(out, "# TYPE {}_{} {}", namespace, name, kind);
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::ServerMetrics;
+	use crate::jsonrpc::{self, JsonValue};
+	use crate::server::{ConnectionExtensions, Middleware, RequestContext};
+	use core::time::Duration;
+
+	#[test]
+	fn encode_works() {
+		let metrics = ServerMetrics::with_connections("test");
+		let context = RequestContext::new(ConnectionExtensions::default());
+		let params = jsonrpc::Params::None;
+
+		metrics.on_request("add", &params, &context).unwrap();
+		metrics.on_response("add", &params, &context, &JsonValue::Null, Duration::from_millis(3));
+		metrics.on_request("a\"b", &params, &context).unwrap();
+		metrics.on_error("a\"b", &params, &context, &jsonrpc::Error::server_busy(), Duration::from_secs(10));
+		metrics.on_batch(3);
+		metrics.connection_opened();
+		metrics.connection_opened();
+		metrics.connection_closed();
+		metrics.set_active_subscriptions(4);
+		metrics.notification_dropped("notif");
+
+		let encoded = metrics.encode();
+		for line in &[
+			"# TYPE test_requests_total counter",
+			"test_requests_total{method=\"add\"} 1",
+			"test_requests_total{method=\"a\\\"b\"} 1",
+			"test_errors_total{method=\"a\\\"b\",code=\"-32002\"} 1",
+			"test_request_duration_seconds_bucket{method=\"add\",le=\"0.0025\"} 0",
+			"test_request_duration_seconds_bucket{method=\"add\",le=\"0.005\"} 1",
+			"test_request_duration_seconds_bucket{method=\"a\\\"b\",le=\"5\"} 0",
+			"test_request_duration_seconds_bucket{method=\"a\\\"b\",le=\"+Inf\"} 1",
+			"test_request_duration_seconds_count{method=\"add\"} 1",
+			"test_batch_size_bucket{le=\"2\"} 0",
+			"test_batch_size_bucket{le=\"5\"} 1",
+			"test_batch_size_sum 3",
+			"test_connections 1",
+			"test_subscriptions 4",
+			"test_dropped_notifications_total{method=\"notif\"} 1",
+		] {
+			assert!(encoded.lines().any(|l| l == *line), "missing {:?} in:\n{}", line, encoded);
+		}
+	}
+
+	#[test]
+	fn method_labels_are_bounded() {
+		let metrics = ServerMetrics::new("test");
+		let context = RequestContext::new(ConnectionExtensions::default());
+		for n in 0..super::MAX_METHOD_LABELS + 10 {
+			metrics.on_request(&n.to_string(), &jsonrpc::Params::None, &context).unwrap();
+		}
+		let encoded = metrics.encode();
+		assert_eq!(
+			encoded.lines().filter(|l| l.starts_with("test_requests_total")).count(),
+			super::MAX_METHOD_LABELS + 1
+		);
+		assert!(encoded.lines().any(|l| l == "test_requests_total{method=\"<other>\"} 10"));
+	}
+}
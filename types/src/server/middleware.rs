@@ -0,0 +1,221 @@
+//! Hooks called by the servers around every method call.
+
+use crate::jsonrpc::{self, JsonValue};
+use crate::server::RequestContext;
+
+use alloc::{sync::Arc, vec::Vec};
+use core::time::Duration;
+use std::time::Instant;
+
+/// Hooks called by a server around every method call, whatever the transport.
+///
+/// Can be used for logging, metrics, authorization, and so on. Several middlewares can be
+/// combined with a [`MiddlewareStack`]. Notifications don't go through the middlewares.
+pub trait Middleware: Send + Sync + 'static {
+	/// Called when a request is received, before it is dispatched to its handler.
+	///
+	/// Returning an error answers the request with this error, in which case the handler isn't
+	/// called.
+	fn on_request(
+		&self,
+		_method: &str,
+		_params: &jsonrpc::Params,
+		_context: &RequestContext,
+	) -> Result<(), jsonrpc::Error> {
+		Ok(())
+	}
+
+	/// Called when a request is answered successfully. `elapsed` is the time since the request
+	/// has been received.
+	fn on_response(
+		&self,
+		_method: &str,
+		_params: &jsonrpc::Params,
+		_context: &RequestContext,
+		_result: &JsonValue,
+		_elapsed: Duration,
+	) {
+	}
+
+	/// Called when a request is answered with an error, including an error returned by
+	/// [`on_request`](Middleware::on_request). `elapsed` is the time since the request has been
+	/// received.
+	fn on_error(
+		&self,
+		_method: &str,
+		_params: &jsonrpc::Params,
+		_context: &RequestContext,
+		_error: &jsonrpc::Error,
+		_elapsed: Duration,
+	) {
+	}
+}
+
+/// Ordered list of middlewares, itself a [`Middleware`].
+///
+/// [`on_request`](Middleware::on_request) is called on each middleware in the order they have
+/// been pushed, and stops at the first error. The other hooks are called in the reverse order.
+///
+/// If a middleware rejects a request, [`on_error`](Middleware::on_error) is immediately called
+/// on this middleware and on the ones before it, which are the only ones that have seen the
+/// request. The rejection must therefore not be passed to [`on_result`](MiddlewareStack::on_result).
+#[derive(Clone, Default)]
+pub struct MiddlewareStack {
+	layers: Vec<Arc<dyn Middleware>>,
+}
+
+impl MiddlewareStack {
+	/// Creates an empty stack.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Adds a middleware at the bottom of the stack, i.e. closest to the handlers.
+	pub fn push(&mut self, middleware: Arc<dyn Middleware>) {
+		self.layers.push(middleware);
+	}
+
+	/// Returns true if the stack doesn't contain any middleware.
+	pub fn is_empty(&self) -> bool {
+		self.layers.is_empty()
+	}
+
+	/// Calls [`on_response`](Middleware::on_response) or [`on_error`](Middleware::on_error)
+	/// depending on `result`.
+	pub fn on_result(
+		&self,
+		method: &str,
+		params: &jsonrpc::Params,
+		context: &RequestContext,
+		result: &Result<JsonValue, jsonrpc::Error>,
+		elapsed: Duration,
+	) {
+		match result {
+			Ok(value) => self.on_response(method, params, context, value, elapsed),
+			Err(err) => self.on_error(method, params, context, err, elapsed),
+		}
+	}
+}
+
+impl Middleware for MiddlewareStack {
+	fn on_request(
+		&self,
+		method: &str,
+		params: &jsonrpc::Params,
+		context: &RequestContext,
+	) -> Result<(), jsonrpc::Error> {
+		let started = Instant::now();
+		for (i, layer) in self.layers.iter().enumerate() {
+			if let Err(err) = layer.on_request(method, params, context) {
+				let elapsed = started.elapsed();
+				for layer in self.layers[..=i].iter().rev() {
+					layer.on_error(method, params, context, &err, elapsed);
+				}
+				return Err(err);
+			}
+		}
+		Ok(())
+	}
+
+	fn on_response(
+		&self,
+		method: &str,
+		params: &jsonrpc::Params,
+		context: &RequestContext,
+		result: &JsonValue,
+		elapsed: Duration,
+	) {
+		for layer in self.layers.iter().rev() {
+			layer.on_response(method, params, context, result, elapsed);
+		}
+	}
+
+	fn on_error(
+		&self,
+		method: &str,
+		params: &jsonrpc::Params,
+		context: &RequestContext,
+		error: &jsonrpc::Error,
+		elapsed: Duration,
+	) {
+		for layer in self.layers.iter().rev() {
+			layer.on_error(method, params, context, error, elapsed);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{Middleware, MiddlewareStack};
+	use crate::jsonrpc::{self, JsonValue};
+	use crate::server::{ConnectionExtensions, RequestContext};
+
+	use alloc::{string::String, sync::Arc, vec::Vec};
+	use core::time::Duration;
+	use std::sync::Mutex;
+
+	/// Records the hooks it is called with, and rejects the requests of `reject`.
+	struct Recorder {
+		name: &'static str,
+		reject: &'static str,
+		calls: Arc<Mutex<Vec<String>>>,
+	}
+
+	impl Middleware for Recorder {
+		fn on_request(&self, method: &str, _: &jsonrpc::Params, _: &RequestContext) -> Result<(), jsonrpc::Error> {
+			self.calls.lock().unwrap().push(format!("{} request {}", self.name, method));
+			if method == self.reject {
+				return Err(jsonrpc::Error::method_not_found());
+			}
+			Ok(())
+		}
+
+		fn on_response(&self, method: &str, _: &jsonrpc::Params, _: &RequestContext, _: &JsonValue, _: Duration) {
+			self.calls.lock().unwrap().push(format!("{} response {}", self.name, method));
+		}
+
+		fn on_error(&self, method: &str, _: &jsonrpc::Params, _: &RequestContext, _: &jsonrpc::Error, _: Duration) {
+			self.calls.lock().unwrap().push(format!("{} error {}", self.name, method));
+		}
+	}
+
+	#[test]
+	fn stack_calls_layers_in_order() {
+		let calls = Arc::new(Mutex::new(Vec::new()));
+		let mut stack = MiddlewareStack::new();
+		stack.push(Arc::new(Recorder { name: "outer", reject: "", calls: calls.clone() }));
+		stack.push(Arc::new(Recorder { name: "inner", reject: "secret", calls: calls.clone() }));
+		let context = RequestContext::new(ConnectionExtensions::default());
+		let params = jsonrpc::Params::None;
+
+		assert!(stack.on_request("hello", &params, &context).is_ok());
+		stack.on_result("hello", &params, &context, &Ok(JsonValue::Null), Duration::from_secs(1));
+		assert!(stack.on_request("secret", &params, &context).is_err());
+
+		assert_eq!(
+			*calls.lock().unwrap(),
+			[
+				"outer request hello",
+				"inner request hello",
+				"inner response hello",
+				"outer response hello",
+				"outer request secret",
+				"inner request secret",
+				"inner error secret",
+				"outer error secret",
+			]
+		);
+	}
+
+	#[test]
+	fn rejection_only_reaches_the_layers_that_saw_the_request() {
+		let calls = Arc::new(Mutex::new(Vec::new()));
+		let mut stack = MiddlewareStack::new();
+		stack.push(Arc::new(Recorder { name: "outer", reject: "secret", calls: calls.clone() }));
+		stack.push(Arc::new(Recorder { name: "inner", reject: "", calls: calls.clone() }));
+		let context = RequestContext::new(ConnectionExtensions::default());
+
+		assert!(stack.on_request("secret", &jsonrpc::Params::None, &context).is_err());
+		assert_eq!(*calls.lock().unwrap(), ["outer request secret", "outer error secret"]);
+	}
+}
@@ -0,0 +1,55 @@
+//! Shared server types
+
+mod context;
+mod frontend;
+mod metrics;
+mod middleware;
+mod module;
+mod queue;
+mod raw;
+
+pub use context::{ConnectionExtensions, RequestContext};
+pub use frontend::{Frontend, IncomingRequest, RegisteredMethod, RegisteredNotification, Spawner};
+pub use metrics::ServerMetrics;
+pub use middleware::{Middleware, MiddlewareStack};
+pub use module::{
+	method_callback, method_callback_with_context, MethodCallback, ModuleMethod, ModuleSubscription, RpcModule,
+};
+pub use queue::{next_queued_request, poll_waiting_requests, MethodQueue, MethodQueueRx, Pushed, QueuedRequest};
+pub use raw::{RawServer, RawServerEvent, RawServerRequest, RawServerRequestId, TransportServer, TransportServerEvent};
+
+/// Default capacity of the queue of requests of a registered method.
+const DEFAULT_METHOD_QUEUE_CAPACITY: usize = 32;
+
+/// What to do with an incoming request when the queue of its method is full.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+	/// Keep the incoming request aside until the handler makes room in the queue. The requests
+	/// to the other methods are still processed in the meantime.
+	///
+	/// At most [`queue_capacity`](MethodConfig::queue_capacity) requests are kept aside. Once
+	/// that many requests are waiting, the incoming requests are answered with a "server busy"
+	/// error, as with [`Reject`](OverflowPolicy::Reject).
+	Wait,
+	/// Answer the incoming request with a "server busy" error.
+	Reject,
+	/// Answer the oldest request of the queue with a "server busy" error, and queue the incoming
+	/// request instead.
+	DropOldest,
+}
+
+/// Configuration of a registered method.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MethodConfig {
+	/// Maximum number of requests waiting to be processed by the handler. A capacity of 0 is
+	/// treated as 1.
+	pub queue_capacity: usize,
+	/// What to do with an incoming request when the queue is full.
+	pub overflow_policy: OverflowPolicy,
+}
+
+impl Default for MethodConfig {
+	fn default() -> Self {
+		Self { queue_capacity: DEFAULT_METHOD_QUEUE_CAPACITY, overflow_policy: OverflowPolicy::Reject }
+	}
+}
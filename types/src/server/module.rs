@@ -0,0 +1,338 @@
+//! Transport-agnostic collection of methods and subscriptions.
+
+use crate::error::Error;
+use crate::jsonrpc::{self, DeserializeOwned, JsonValue, Serialize};
+use crate::server::{MethodConfig, RequestContext};
+
+use alloc::{borrow::ToOwned as _, string::String, sync::Arc, vec, vec::Vec};
+use futures::{channel::mpsc, future::BoxFuture, prelude::*};
+use hashbrown::HashMap;
+use std::sync::Mutex;
+
+/// Callback that answers a request of a method, given the parameters and the context of that
+/// request.
+pub type MethodCallback =
+	Arc<dyn Fn(jsonrpc::Params, RequestContext) -> BoxFuture<'static, Result<JsonValue, jsonrpc::Error>> + Send + Sync>;
+
+/// Turns an asynchronous closure into a [`MethodCallback`].
+///
+/// The parameters of the request are deserialized into `P` and passed to `callback`, then the
+/// `Result` that the returned `Future` resolves to is serialized. If the parameters can't be
+/// deserialized into `P`, the callback answers with an "invalid params" error and `callback`
+/// isn't called.
+pub fn method_callback<F, Fut, P, T, E>(callback: F) -> MethodCallback
+where
+	F: Fn(P) -> Fut + Send + Sync + 'static,
+	Fut: Future<Output = Result<T, E>> + Send + 'static,
+	P: DeserializeOwned + Send + 'static,
+	T: Serialize,
+	E: Into<jsonrpc::Error>,
+{
+	method_callback_with_context(move |params, _: RequestContext| callback(params))
+}
+
+/// Same as [`method_callback`], except that `callback` is also passed the [`RequestContext`] of
+/// the request.
+pub fn method_callback_with_context<F, Fut, P, T, E>(callback: F) -> MethodCallback
+where
+	F: Fn(P, RequestContext) -> Fut + Send + Sync + 'static,
+	Fut: Future<Output = Result<T, E>> + Send + 'static,
+	P: DeserializeOwned + Send + 'static,
+	T: Serialize,
+	E: Into<jsonrpc::Error>,
+{
+	Arc::new(move |params: jsonrpc::Params, context: RequestContext| match params.parse::<P>() {
+		Ok(params) => callback(params, context)
+			.map(|result| match result {
+				Ok(value) => jsonrpc::to_value(value).map_err(|_| jsonrpc::Error::internal_error()),
+				Err(err) => Err(err.into()),
+			})
+			.boxed(),
+		Err(err) => future::ready(Err(err)).boxed(),
+	})
+}
+
+/// Collection of methods and subscriptions that isn't tied to any transport.
+///
+/// Methods and subscriptions are registered once, then the module can be registered on any
+/// number of servers, for example on both an HTTP and a WebSocket server.
+#[derive(Clone, Default)]
+pub struct RpcModule {
+	/// Methods of the module, by name.
+	methods: HashMap<String, ModuleMethod>,
+	/// Subscriptions of the module, by name of the subscribe method. Also contains the name of
+	/// the unsubscribe method.
+	subscriptions: HashMap<String, (String, ModuleSubscription)>,
+}
+
+/// Method registered in an [`RpcModule`].
+#[derive(Clone)]
+pub struct ModuleMethod {
+	/// Configuration of the queue of requests of the method.
+	config: MethodConfig,
+	/// Answers the requests.
+	callback: MethodCallback,
+}
+
+/// Pub-sub subscription registered in an [`RpcModule`].
+///
+/// Can be cloned. All the clones send out notifications to the same subscribers.
+#[derive(Clone, Default)]
+pub struct ModuleSubscription {
+	/// For each server the module has been registered on, where to send the notifications.
+	senders: Arc<Mutex<Vec<mpsc::UnboundedSender<JsonValue>>>>,
+}
+
+impl RpcModule {
+	/// Creates an empty module.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Registers a method whose requests are answered by `callback`.
+	///
+	/// See [`method_callback`] for how the parameters and the result are processed.
+	///
+	/// Returns an error if the method name was already registered.
+	pub fn register_method<F, Fut, P, T, E>(&mut self, method_name: String, callback: F) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.register_method_with_config(method_name, MethodConfig::default(), callback)
+	}
+
+	/// Same as [`register_method`](RpcModule::register_method), with a custom configuration for
+	/// the queue of requests of the method.
+	pub fn register_method_with_config<F, Fut, P, T, E>(
+		&mut self,
+		method_name: String,
+		config: MethodConfig,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.insert_method(method_name, config, method_callback(callback))
+	}
+
+	/// Same as [`register_method`](RpcModule::register_method), except that `callback` is also
+	/// passed the [`RequestContext`] of each request.
+	pub fn register_method_with_context<F, Fut, P, T, E>(
+		&mut self,
+		method_name: String,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P, RequestContext) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.insert_method(method_name, MethodConfig::default(), method_callback_with_context(callback))
+	}
+
+	/// Registers a subscription.
+	///
+	/// The returned object allows you to send out notifications to the clients subscribed on any
+	/// of the servers the module is registered on.
+	///
+	/// Returns an error if one of the method names was already registered.
+	pub fn register_subscription(
+		&mut self,
+		subscribe_method_name: String,
+		unsubscribe_method_name: String,
+	) -> Result<ModuleSubscription, Error> {
+		if subscribe_method_name == unsubscribe_method_name || self.contains(&subscribe_method_name) {
+			return Err(Error::MethodAlreadyRegistered(subscribe_method_name));
+		}
+		if self.contains(&unsubscribe_method_name) {
+			return Err(Error::MethodAlreadyRegistered(unsubscribe_method_name));
+		}
+
+		let subscription = ModuleSubscription::default();
+		self.subscriptions.insert(subscribe_method_name, (unsubscribe_method_name, subscription.clone()));
+		Ok(subscription)
+	}
+
+	/// Moves all the methods and subscriptions of `other` into this module.
+	///
+	/// Returns an error and leaves this module untouched if `other` has a method name in common
+	/// with this module.
+	pub fn merge(&mut self, other: RpcModule) -> Result<(), Error> {
+		if let Some(name) = other.method_names().find(|name| self.contains(name)) {
+			return Err(Error::MethodAlreadyRegistered(name.to_owned()));
+		}
+
+		self.methods.extend(other.methods);
+		self.subscriptions.extend(other.subscriptions);
+		Ok(())
+	}
+
+	/// Returns the names of all the methods of the module, including the subscribe and
+	/// unsubscribe methods.
+	pub fn method_names(&self) -> impl Iterator<Item = &str> {
+		self.methods
+			.keys()
+			.map(String::as_str)
+			.chain(self.subscriptions.iter().flat_map(|(sub, (unsub, _))| vec![sub.as_str(), unsub.as_str()]))
+	}
+
+	/// Returns the methods of the module, with their names.
+	pub fn methods(&self) -> impl Iterator<Item = (&str, &ModuleMethod)> {
+		self.methods.iter().map(|(name, method)| (name.as_str(), method))
+	}
+
+	/// Returns the subscriptions of the module, with the names of their subscribe and unsubscribe
+	/// methods.
+	pub fn subscriptions(&self) -> impl Iterator<Item = (&str, &str, &ModuleSubscription)> {
+		self.subscriptions.iter().map(|(sub, (unsub, subscription))| (sub.as_str(), unsub.as_str(), subscription))
+	}
+
+	/// Adds a method to the module, unless the name is already registered.
+	fn insert_method(
+		&mut self,
+		method_name: String,
+		config: MethodConfig,
+		callback: MethodCallback,
+	) -> Result<(), Error> {
+		if self.contains(&method_name) {
+			return Err(Error::MethodAlreadyRegistered(method_name));
+		}
+
+		self.methods.insert(method_name, ModuleMethod { config, callback });
+		Ok(())
+	}
+
+	/// Returns true if `method_name` is one of the methods of the module.
+	fn contains(&self, method_name: &str) -> bool {
+		self.method_names().any(|name| name == method_name)
+	}
+}
+
+impl ModuleMethod {
+	/// Returns the configuration of the queue of requests of the method.
+	pub fn config(&self) -> MethodConfig {
+		self.config
+	}
+
+	/// Returns the callback that answers the requests.
+	pub fn callback(&self) -> MethodCallback {
+		self.callback.clone()
+	}
+}
+
+impl ModuleSubscription {
+	/// Sends out a value to all the subscribing clients, on all the servers the module is
+	/// registered on.
+	pub fn send(&self, value: JsonValue) {
+		let mut senders = self.senders.lock().expect("lock poisoned");
+		senders.retain(|sender| sender.unbounded_send(value.clone()).is_ok());
+	}
+
+	/// Returns a new stream of all the values passed to [`send`](ModuleSubscription::send) from
+	/// now on.
+	///
+	/// This is meant to be used by servers when the module is registered on them.
+	pub fn notifications(&self) -> mpsc::UnboundedReceiver<JsonValue> {
+		let (tx, rx) = mpsc::unbounded();
+		self.senders.lock().expect("lock poisoned").push(tx);
+		rx
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::RpcModule;
+	use crate::error::Error;
+	use crate::jsonrpc::{self, JsonValue};
+	use crate::server::{ConnectionExtensions, RequestContext};
+	use futures::StreamExt as _;
+
+	#[test]
+	fn duplicate_names_are_rejected() {
+		let mut module = RpcModule::new();
+		module.register_method("hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>(()) }).unwrap();
+		module.register_subscription("subscribe".to_owned(), "unsubscribe".to_owned()).unwrap();
+
+		let err = module.register_method("unsubscribe".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>(()) });
+		assert!(matches!(err, Err(Error::MethodAlreadyRegistered(name)) if name == "unsubscribe"));
+		let err = module.register_subscription("foo".to_owned(), "hello".to_owned());
+		assert!(matches!(err, Err(Error::MethodAlreadyRegistered(name)) if name == "hello"));
+		let err = module.register_subscription("foo".to_owned(), "foo".to_owned());
+		assert!(matches!(err, Err(Error::MethodAlreadyRegistered(name)) if name == "foo"));
+	}
+
+	#[test]
+	fn merge_works() {
+		let mut module = RpcModule::new();
+		module.register_method("hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>(()) }).unwrap();
+
+		let mut other = RpcModule::new();
+		other.register_subscription("subscribe".to_owned(), "unsubscribe".to_owned()).unwrap();
+		let mut conflicting = other.clone();
+		conflicting.register_method("hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>(()) }).unwrap();
+
+		assert!(matches!(module.merge(conflicting), Err(Error::MethodAlreadyRegistered(name)) if name == "hello"));
+		assert_eq!(module.method_names().count(), 1);
+
+		module.merge(other).unwrap();
+		let mut names = module.method_names().collect::<Vec<_>>();
+		names.sort();
+		assert_eq!(names, vec!["hello", "subscribe", "unsubscribe"]);
+	}
+
+	#[test]
+	fn method_callback_works() {
+		let mut module = RpcModule::new();
+		module
+			.register_method("add".to_owned(), |(a, b): (u64, u64)| async move { Ok::<_, jsonrpc::Error>(a + b) })
+			.unwrap();
+		let (_, add) = module.methods().next().unwrap();
+
+		let context = RequestContext::new(ConnectionExtensions::default());
+		let params = jsonrpc::Params::Array(vec![1.into(), 2.into()]);
+		assert_eq!(futures::executor::block_on(add.callback()(params, context.clone())), Ok(JsonValue::from(3)));
+		let params = jsonrpc::Params::Array(vec!["1".into(), 2.into()]);
+		assert_eq!(
+			futures::executor::block_on(add.callback()(params, context)).unwrap_err().code,
+			jsonrpc::ErrorCode::InvalidParams
+		);
+	}
+
+	#[test]
+	fn method_callback_with_context_works() {
+		let mut module = RpcModule::new();
+		module
+			.register_method_with_context("id".to_owned(), |_: (), context: RequestContext| async move {
+				Ok::<_, jsonrpc::Error>(context.id().clone())
+			})
+			.unwrap();
+		let (_, method) = module.methods().next().unwrap();
+
+		let context = RequestContext::new(ConnectionExtensions::default()).with_id(jsonrpc::Id::Num(7));
+		let result = futures::executor::block_on(method.callback()(jsonrpc::Params::None, context));
+		assert_eq!(result, Ok(JsonValue::from(7)));
+	}
+
+	#[test]
+	fn subscription_notifications_reach_every_stream() {
+		let mut module = RpcModule::new();
+		let subscription = module.register_subscription("subscribe".to_owned(), "unsubscribe".to_owned()).unwrap();
+		let (_, _, registered) = module.subscriptions().next().unwrap();
+		let mut first = registered.notifications();
+		let mut second = registered.notifications();
+
+		subscription.send(JsonValue::from("hello"));
+		assert_eq!(futures::executor::block_on(first.next()), Some(JsonValue::from("hello")));
+		assert_eq!(futures::executor::block_on(second.next()), Some(JsonValue::from("hello")));
+	}
+}
@@ -0,0 +1,213 @@
+//! Queue of the requests of a method registered on a server.
+
+use crate::jsonrpc;
+use crate::server::{MethodConfig, OverflowPolicy, RequestContext};
+
+use alloc::{collections::VecDeque, sync::Arc};
+use core::task::{Context, Poll};
+use futures::{channel::mpsc, prelude::*};
+use parking_lot::Mutex;
+
+/// Request waiting in the queue of a registered method, identified by `I` within the server.
+pub type QueuedRequest<I> = (I, jsonrpc::Params, RequestContext);
+
+/// Receiving side of the queue of requests of a registered method.
+///
+/// Shared with the background task of the server, so that it can shed the oldest requests of the
+/// queue.
+pub type MethodQueueRx<I> = Arc<Mutex<mpsc::Receiver<QueuedRequest<I>>>>;
+
+/// Queue of requests of a registered method, as seen from the background task of a server.
+pub struct MethodQueue<I> {
+	/// Where to send requests.
+	tx: mpsc::Sender<QueuedRequest<I>>,
+	/// Receiving side of `tx`.
+	rx: MethodQueueRx<I>,
+	/// What to do with a request when the queue is full.
+	overflow_policy: OverflowPolicy,
+	/// Requests waiting for room in the queue with [`OverflowPolicy::Wait`], oldest first.
+	waiting: VecDeque<QueuedRequest<I>>,
+	/// Maximum length of `waiting`.
+	max_waiting: usize,
+}
+
+/// What happened to a request passed to [`MethodQueue::push`].
+#[derive(Debug, PartialEq)]
+pub enum Pushed<I> {
+	/// The request will be processed by the handler of the method.
+	Queued,
+	/// The request must be answered with the given error.
+	Refused(jsonrpc::Error),
+	/// The request will be processed by the handler of the method, in place of the oldest request
+	/// of the queue. The latter must be answered with a "server busy" error.
+	ShedOldest(I),
+}
+
+impl<I> MethodQueue<I> {
+	/// Creates a queue with the given configuration. Returns the receiving side of the queue,
+	/// which the handler of the method reads the requests from.
+	pub fn new(config: MethodConfig) -> (Self, MethodQueueRx<I>) {
+		// The capacity of the channel is `buffer` plus one slot for the only sender.
+		let (tx, rx) = mpsc::channel(config.queue_capacity.saturating_sub(1));
+		let rx = Arc::new(Mutex::new(rx));
+		let queue = MethodQueue {
+			tx,
+			rx: rx.clone(),
+			overflow_policy: config.overflow_policy,
+			waiting: VecDeque::new(),
+			max_waiting: config.queue_capacity.max(1),
+		};
+		(queue, rx)
+	}
+
+	/// Passes a request to the handler of the method, applying the overflow policy if the queue
+	/// is full.
+	pub fn push(&mut self, request: QueuedRequest<I>) -> Pushed<I> {
+		// The requests already waiting for room in the queue go first.
+		if !self.waiting.is_empty() {
+			return self.wait(request);
+		}
+
+		let err = match self.tx.try_send(request) {
+			Ok(()) => return Pushed::Queued,
+			Err(err) if err.is_disconnected() => return Pushed::Refused(jsonrpc::Error::internal_error()),
+			Err(err) => err,
+		};
+
+		match self.overflow_policy {
+			OverflowPolicy::Wait => self.wait(err.into_inner()),
+			OverflowPolicy::Reject => Pushed::Refused(jsonrpc::Error::server_busy()),
+			OverflowPolicy::DropOldest => {
+				let oldest = self.rx.lock().try_recv();
+				if let Err(err) = self.tx.try_send(err.into_inner()) {
+					// Removing the oldest request normally makes room for the incoming one. If it
+					// didn't, the incoming request waits for the handler.
+					self.waiting.push_back(err.into_inner());
+				}
+				match oldest {
+					Ok((oldest_id, _, _)) => Pushed::ShedOldest(oldest_id),
+					Err(_) => Pushed::Queued,
+				}
+			}
+		}
+	}
+
+	/// Keeps a request aside until there is room in the queue, unless too many requests are
+	/// already waiting.
+	fn wait(&mut self, request: QueuedRequest<I>) -> Pushed<I> {
+		if self.waiting.len() >= self.max_waiting {
+			return Pushed::Refused(jsonrpc::Error::server_busy());
+		}
+		self.waiting.push_back(request);
+		Pushed::Queued
+	}
+
+	/// Moves the requests waiting for room in the queue to the queue, as room frees up.
+	///
+	/// Resolves with the id of a waiting request that can't be queued anymore, because the handler
+	/// of the method has been dropped. This request must be answered with an "internal error".
+	pub fn poll_waiting(&mut self, cx: &mut Context<'_>) -> Poll<I> {
+		while !self.waiting.is_empty() {
+			let failed = match self.tx.poll_ready(cx) {
+				Poll::Ready(Ok(())) => {
+					let request = self.waiting.pop_front().expect("checked above; qed");
+					match self.tx.try_send(request) {
+						Ok(()) => continue,
+						Err(err) => err.into_inner(),
+					}
+				}
+				Poll::Ready(Err(_)) => self.waiting.pop_front().expect("checked above; qed"),
+				Poll::Pending => break,
+			};
+			let (request_id, _, _) = failed;
+			return Poll::Ready(request_id);
+		}
+		Poll::Pending
+	}
+}
+
+/// Calls [`MethodQueue::poll_waiting`] on each of `queues`.
+pub fn poll_waiting_requests<'a, I: 'a>(
+	queues: impl IntoIterator<Item = &'a mut MethodQueue<I>>,
+	cx: &mut Context<'_>,
+) -> Poll<I> {
+	for queue in queues {
+		if let Poll::Ready(request_id) = queue.poll_waiting(cx) {
+			return Poll::Ready(request_id);
+		}
+	}
+	Poll::Pending
+}
+
+/// Returns the next request of a queue, or `None` once the server has dropped the queue.
+pub async fn next_queued_request<I>(rx: &MethodQueueRx<I>) -> Option<QueuedRequest<I>> {
+	future::poll_fn(|cx| rx.lock().poll_next_unpin(cx)).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{next_queued_request, MethodQueue, MethodQueueRx, Pushed, QueuedRequest};
+	use crate::jsonrpc;
+	use crate::server::{ConnectionExtensions, MethodConfig, OverflowPolicy, RequestContext};
+	use core::task::Poll;
+	use futures::{executor::block_on, future, FutureExt as _};
+
+	fn request(id: u32) -> QueuedRequest<u32> {
+		(id, jsonrpc::Params::None, RequestContext::new(ConnectionExtensions::default()))
+	}
+
+	fn queue(overflow_policy: OverflowPolicy) -> (MethodQueue<u32>, MethodQueueRx<u32>) {
+		MethodQueue::new(MethodConfig { queue_capacity: 2, overflow_policy })
+	}
+
+	#[test]
+	fn reject_refuses_when_full() {
+		let (mut queue, _rx) = queue(OverflowPolicy::Reject);
+		assert_eq!(queue.push(request(1)), Pushed::Queued);
+		assert_eq!(queue.push(request(2)), Pushed::Queued);
+		assert_eq!(queue.push(request(3)), Pushed::Refused(jsonrpc::Error::server_busy()));
+	}
+
+	#[test]
+	fn drop_oldest_sheds_the_oldest_request() {
+		let (mut queue, rx) = queue(OverflowPolicy::DropOldest);
+		assert_eq!(queue.push(request(1)), Pushed::Queued);
+		assert_eq!(queue.push(request(2)), Pushed::Queued);
+		assert_eq!(queue.push(request(3)), Pushed::ShedOldest(1));
+
+		let ids = block_on(async {
+			vec![next_queued_request(&rx).await.unwrap().0, next_queued_request(&rx).await.unwrap().0]
+		});
+		assert_eq!(ids, vec![2, 3]);
+	}
+
+	#[test]
+	fn wait_refuses_when_too_many_requests_wait() {
+		let (mut queue, _rx) = queue(OverflowPolicy::Wait);
+		for id in 1..=4 {
+			assert_eq!(queue.push(request(id)), Pushed::Queued);
+		}
+		assert_eq!(queue.push(request(5)), Pushed::Refused(jsonrpc::Error::server_busy()));
+	}
+
+	#[test]
+	fn wait_keeps_requests_aside_until_room_frees_up() {
+		let (mut queue, rx) = queue(OverflowPolicy::Wait);
+		for id in 1..=4 {
+			assert_eq!(queue.push(request(id)), Pushed::Queued);
+		}
+
+		let mut received = Vec::new();
+		block_on(future::poll_fn(|cx| {
+			while received.len() < 4 {
+				assert!(queue.poll_waiting(cx).is_pending());
+				match next_queued_request(&rx).boxed().poll_unpin(cx) {
+					Poll::Ready(Some((id, _, _))) => received.push(id),
+					_ => return Poll::Pending,
+				}
+			}
+			Poll::Ready(())
+		}));
+		assert_eq!(received, vec![1, 2, 3, 4]);
+	}
+}
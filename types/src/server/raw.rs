@@ -0,0 +1,326 @@
+//! Transport-generic core of the servers that don't support subscriptions.
+
+use crate::jsonrpc::{
+	self,
+	wrapped::{batches, Notification, Params},
+};
+use crate::server::{ConnectionExtensions, Middleware, MiddlewareStack, RequestContext, ServerMetrics};
+
+use alloc::sync::Arc;
+use core::{fmt, future::Future, hash::Hash, pin::Pin};
+use std::{collections::HashMap, time::Instant};
+
+/// Event that a [`TransportServer`] can generate.
+#[derive(Debug, PartialEq)]
+pub enum TransportServerEvent<T> {
+	/// A new request has arrived on the wire.
+	///
+	/// This generates a new "request object" within the state of the [`TransportServer`] that is
+	/// identified through the returned `id`. You can then use the other methods of the
+	/// [`TransportServer`] trait in order to manipulate that request.
+	Request {
+		/// Identifier of the request within the state of the [`TransportServer`].
+		id: T,
+		/// Body of the request.
+		request: jsonrpc::Request,
+	},
+
+	/// A request has been cancelled, most likely because the client has closed the connection.
+	///
+	/// The corresponding request is no longer valid to manipulate.
+	Closed(T),
+}
+
+/// Transport that receives JSON-RPC requests and sends back their responses, on top of which a
+/// [`RawServer`] is built.
+pub trait TransportServer {
+	/// Identifier of a request within the state of the transport server.
+	type RequestId: Clone + PartialEq + Eq + Hash + fmt::Debug + Send + Sync;
+
+	/// Returns the next event that the raw server wants to notify us.
+	fn next_request<'a>(
+		&'a mut self,
+	) -> Pin<Box<dyn Future<Output = TransportServerEvent<Self::RequestId>> + Send + 'a>>;
+
+	/// Sends back a response and destroys the request.
+	///
+	/// You can pass `None` in order to destroy the request object without sending back anything.
+	///
+	/// > **Note**: While this method returns a `Future` that must be driven to completion,
+	/// >           implementations must be aware that the entire requests processing logic is
+	/// >           blocked for as long as this `Future` is pending. As an example, you shouldn't
+	/// >           use this `Future` to send back a TCP message, because if the remote is
+	/// >           unresponsive and the buffers full, the `Future` would then wait for a long time.
+	fn finish<'a>(
+		&'a mut self,
+		request_id: &'a Self::RequestId,
+		response: Option<&'a jsonrpc::Response>,
+	) -> Pin<Box<dyn Future<Output = Result<(), ()>> + Send + 'a>>;
+
+	/// Returns information about the connection that a request has been received on, or `None`
+	/// if the request has been destroyed.
+	fn request_context(&self, request_id: &Self::RequestId) -> Option<&RequestContext>;
+
+	/// Returns the metrics of the transport server.
+	fn metrics(&self) -> &ServerMetrics;
+
+	/// Closes the transport server. All the pending requests are destroyed.
+	///
+	/// Does nothing by default.
+	fn close<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+		Box::pin(async {})
+	}
+}
+
+/// Wraps around a [`TransportServer`] and adds capabilities.
+pub struct RawServer<T: TransportServer> {
+	/// Internal "raw" server.
+	raw: T,
+
+	/// List of requests that are in the progress of being answered. Each batch is associated with
+	/// the raw request ID, or with `None` if this raw request has been closed.
+	///
+	/// See the documentation of [`BatchesState`][batches::BatchesState] for more information.
+	batches: batches::BatchesState<Option<T::RequestId>>,
+
+	/// Middlewares called around every request.
+	middleware: MiddlewareStack,
+
+	/// For each request that has been returned by `next_event` and not answered yet, when it
+	/// has been returned. Entries are removed when the request is answered, which always happens
+	/// before its batch is removed from `batches`.
+	started: HashMap<batches::BatchesElemId, Instant>,
+}
+
+/// Identifier of a request within a [`RawServer`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RawServerRequestId {
+	inner: batches::BatchesElemId,
+}
+
+/// Event generated by a [`RawServer`].
+///
+/// > **Note**: Holds a borrow of the `RawServer`. Therefore, must be dropped before the `RawServer` can
+/// >           be dropped.
+#[derive(Debug)]
+pub enum RawServerEvent<'a, R> {
+	/// Request is a notification.
+	Notification(Notification),
+
+	/// Request is a method call.
+	Request(RawServerRequest<'a, R>),
+}
+
+/// Request received by a [`RawServer`].
+pub struct RawServerRequest<'a, R> {
+	/// Reference to the request within `self.batches`.
+	inner: batches::BatchesElem<'a, Option<R>>,
+
+	/// Context of the message this request is part of.
+	context: RequestContext,
+
+	/// Reference to the corresponding field in `RawServer`.
+	middleware: &'a MiddlewareStack,
+
+	/// Reference to the corresponding field in `RawServer`.
+	started: &'a mut HashMap<batches::BatchesElemId, Instant>,
+}
+
+impl<T: TransportServer> RawServer<T> {
+	/// Starts a [`RawServer`] using the given raw server internally.
+	pub fn new(raw: T) -> Self {
+		// The metrics are the outermost middleware, so that they see every call.
+		let mut middleware = MiddlewareStack::new();
+		middleware.push(Arc::new(raw.metrics().clone()));
+		RawServer { raw, batches: batches::BatchesState::new(), middleware, started: HashMap::new() }
+	}
+
+	/// Returns the metrics of the server.
+	pub fn metrics(&self) -> &ServerMetrics {
+		self.raw.metrics()
+	}
+
+	/// Adds a middleware at the bottom of the stack of middlewares called around every request.
+	pub fn add_middleware(&mut self, middleware: Arc<dyn Middleware>) {
+		self.middleware.push(middleware);
+	}
+
+	/// Returns a `Future` resolving to the next event that this server generates.
+	pub async fn next_event(&mut self) -> RawServerEvent<'_, T::RequestId> {
+		let request_id = loop {
+			match self.batches.next_event() {
+				None => {}
+				Some(batches::BatchesEvent::Notification { notification, .. }) => {
+					return RawServerEvent::Notification(notification)
+				}
+				Some(batches::BatchesEvent::Request(inner)) => {
+					let request_id = RawServerRequestId { inner: inner.id() };
+					if self.start_request(&request_id) {
+						break request_id;
+					}
+					continue;
+				}
+				Some(batches::BatchesEvent::ReadyToSend { response, user_param: Some(raw_request_id) }) => {
+					let _ = self.raw.finish(&raw_request_id, response.as_ref()).await;
+					continue;
+				}
+				Some(batches::BatchesEvent::ReadyToSend { response: _, user_param: None }) => {
+					// This situation happens if the connection has been closed by the client.
+					continue;
+				}
+			};
+
+			match self.raw.next_request().await {
+				TransportServerEvent::Request { id, request } => {
+					if let jsonrpc::Request::Batch(batch) = &request {
+						self.raw.metrics().on_batch(batch.len());
+					}
+					self.batches.inject(request, Some(id))
+				}
+				TransportServerEvent::Closed(raw_id) => {
+					// The client has a closed their connection. We eliminate all traces of the
+					// raw request ID from our state.
+					// TODO: this has an O(n) complexity; make sure that this is not attackable
+					for ud in self.batches.batches() {
+						if ud.as_ref() == Some(&raw_id) {
+							*ud = None;
+						}
+					}
+				}
+			};
+		};
+		RawServerEvent::Request(self.request_by_id(&request_id).unwrap())
+	}
+
+	/// Returns a request previously returned by [`next_event`](RawServer::next_event) by its id.
+	///
+	/// Note that previous notifications don't have an ID and can't be accessed with this method.
+	///
+	/// Returns `None` if the request ID is invalid or if the request has already been answered in
+	/// the past.
+	pub fn request_by_id(&mut self, id: &RawServerRequestId) -> Option<RawServerRequest<'_, T::RequestId>> {
+		let mut inner = self.batches.request_by_id(id.inner)?;
+		let context = match inner.user_param() {
+			Some(raw_request_id) => self.raw.request_context(raw_request_id).cloned(),
+			None => None,
+		}
+		// The connection has been closed; it doesn't matter what context we return.
+		.unwrap_or_else(|| RequestContext::new(ConnectionExtensions::default()))
+		.with_id(inner.request_id().clone());
+		Some(RawServerRequest { inner, context, middleware: &self.middleware, started: &mut self.started })
+	}
+
+	/// Sends out the responses that are ready, then closes the underlying transport.
+	///
+	/// Requests that haven't been returned by [`next_event`](RawServer::next_event) yet are
+	/// answered with an error indicating that the server is shutting down. Requests that are
+	/// still waiting for an answer are destroyed.
+	pub async fn close(&mut self) {
+		loop {
+			match self.batches.next_event() {
+				None => break,
+				Some(batches::BatchesEvent::Notification { .. }) => {}
+				Some(batches::BatchesEvent::Request(inner)) => {
+					inner.set_response(Err(jsonrpc::Error::server_shutting_down()));
+				}
+				Some(batches::BatchesEvent::ReadyToSend { response, user_param: Some(raw_request_id) }) => {
+					let _ = self.raw.finish(&raw_request_id, response.as_ref()).await;
+				}
+				Some(batches::BatchesEvent::ReadyToSend { response: _, user_param: None }) => {}
+			}
+		}
+
+		// The requests that are still waiting for an answer are destroyed.
+		self.started.clear();
+		self.raw.close().await;
+	}
+
+	/// Records the start of a request and passes it to the middlewares.
+	///
+	/// Returns false and answers the request if a middleware rejects it.
+	fn start_request(&mut self, id: &RawServerRequestId) -> bool {
+		let request = match self.request_by_id(id) {
+			Some(request) => request,
+			None => return false,
+		};
+		let started = Instant::now();
+
+		let rejection = {
+			let params: &jsonrpc::Params = request.params().into();
+			request.middleware.on_request(request.method(), params, request.context()).err()
+		};
+		match rejection {
+			// The middlewares have already been notified of the rejection.
+			Some(err) => {
+				request.respond(Err(err));
+				false
+			}
+			None => {
+				request.started.insert(id.inner, started);
+				true
+			}
+		}
+	}
+}
+
+impl<T: TransportServer> From<T> for RawServer<T> {
+	fn from(inner: T) -> Self {
+		RawServer::new(inner)
+	}
+}
+
+impl<'a, R> RawServerRequest<'a, R> {
+	/// Returns the id of the request.
+	///
+	/// If this request object is dropped, you can retrieve it again later by calling
+	/// [`request_by_id`](RawServer::request_by_id).
+	pub fn id(&self) -> RawServerRequestId {
+		RawServerRequestId { inner: self.inner.id() }
+	}
+
+	/// Returns the id that the client sent out.
+	// TODO: can return None, which is wrong
+	pub fn request_id(&self) -> &jsonrpc::Id {
+		self.inner.request_id()
+	}
+
+	/// Returns the method of this request.
+	pub fn method(&self) -> &str {
+		self.inner.method()
+	}
+
+	/// Returns the parameters of the request, as a `jsonrpc::Params`.
+	pub fn params(&self) -> Params<'_> {
+		self.inner.params()
+	}
+
+	/// Returns information about the request and the connection it was received on.
+	pub fn context(&self) -> &RequestContext {
+		&self.context
+	}
+
+	/// Send back a response.
+	///
+	/// If this request is part of a batch:
+	///
+	/// - If all requests of the batch have been responded to, then the response is actively
+	///   sent out.
+	/// - Otherwise, this response is buffered.
+	pub fn respond(self, response: Result<jsonrpc::JsonValue, jsonrpc::Error>) {
+		if let Some(started) = self.started.remove(&self.inner.id()) {
+			let params: &jsonrpc::Params = self.params().into();
+			self.middleware.on_result(self.method(), params, &self.context, &response, started.elapsed());
+		}
+		self.inner.set_response(response);
+	}
+}
+
+impl<'a, R> fmt::Debug for RawServerRequest<'a, R> {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("RawServerRequest")
+			.field("request_id", &self.request_id())
+			.field("method", &self.method())
+			.field("params", &self.params())
+			.finish()
+	}
+}
@@ -0,0 +1,182 @@
+//! Shared WebSocket types
+
+use std::time::{Duration, Instant};
+
+/// Default maximum request body size (10 MB).
+const DEFAULT_MAX_BODY_SIZE_TEN_MB: u32 = 10 * 1024 * 1024;
+/// Default maximum number of concurrent connections.
+const DEFAULT_MAX_CONNECTIONS: u32 = 100;
+/// Default maximum number of requests in flight on a single connection.
+const DEFAULT_MAX_IN_FLIGHT_REQUESTS: u32 = 256;
+/// Default time to wait for a pong after sending a ping.
+const DEFAULT_PONG_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// WebSocket server configuration.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct WsServerConfig {
+	/// Maximum size in bytes of a message received from a client.
+	///
+	/// A larger message is answered with a "Request is too large" error, then the connection is
+	/// closed.
+	pub max_request_body_size: u32,
+	/// Maximum number of concurrent connections.
+	///
+	/// Additional clients are rejected during the handshake with `503 Service Unavailable`.
+	pub max_connections: u32,
+	/// Maximum number of requests that a single connection can have in flight.
+	///
+	/// A request is in flight until it has been answered, and a batch counts as a single request.
+	/// Additional requests are answered with a "Server is busy" error.
+	pub max_in_flight_requests: u32,
+	/// Detection of dead and idle connections.
+	pub keep_alive: KeepAliveConfig,
+}
+
+impl Default for WsServerConfig {
+	fn default() -> Self {
+		Self {
+			max_request_body_size: DEFAULT_MAX_BODY_SIZE_TEN_MB,
+			max_connections: DEFAULT_MAX_CONNECTIONS,
+			max_in_flight_requests: DEFAULT_MAX_IN_FLIGHT_REQUESTS,
+			keep_alive: KeepAliveConfig::default(),
+		}
+	}
+}
+
+/// Configuration of the detection of dead and idle WebSocket connections.
+///
+/// Everything is disabled by default.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct KeepAliveConfig {
+	/// Interval at which pings are sent to the peer. `None` disables the pings.
+	pub ping_interval: Option<Duration>,
+	/// Time after which the connection is closed if the peer hasn't answered a ping with a pong.
+	pub pong_timeout: Duration,
+	/// Time after which the connection is closed if no message has been sent or received. Pings
+	/// and pongs don't count as messages. `None` disables the timeout.
+	pub idle_timeout: Option<Duration>,
+}
+
+impl Default for KeepAliveConfig {
+	fn default() -> Self {
+		Self { ping_interval: None, pong_timeout: DEFAULT_PONG_TIMEOUT, idle_timeout: None }
+	}
+}
+
+/// What a connection must do, as decided by [`KeepAlive::poll`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum KeepAliveEvent {
+	/// Send a ping to the peer.
+	SendPing,
+	/// The peer hasn't answered a ping in time. The connection must be dropped.
+	PongTimeout,
+	/// No message has been exchanged for too long. The connection must be closed.
+	IdleTimeout,
+}
+
+/// Keep-alive state of a WebSocket connection.
+///
+/// The connection reports what happens on it, and calls [`KeepAlive::poll`] once the instant
+/// returned by [`KeepAlive::deadline`] has been reached.
+#[derive(Debug)]
+pub struct KeepAlive {
+	/// Configuration.
+	config: KeepAliveConfig,
+	/// When to send the next ping, if pings are enabled.
+	next_ping: Option<Instant>,
+	/// When the pong answering the last ping must have been received, if a ping is unanswered.
+	pong_deadline: Option<Instant>,
+	/// When the connection becomes idle, if the idle timeout is enabled.
+	idle_deadline: Option<Instant>,
+}
+
+impl KeepAlive {
+	/// Creates the state of a connection opened at `now`.
+	pub fn new(config: KeepAliveConfig, now: Instant) -> Self {
+		KeepAlive {
+			config,
+			next_ping: config.ping_interval.map(|interval| now + interval),
+			pong_deadline: None,
+			idle_deadline: config.idle_timeout.map(|timeout| now + timeout),
+		}
+	}
+
+	/// Returns the instant at which [`KeepAlive::poll`] must be called, if any.
+	pub fn deadline(&self) -> Option<Instant> {
+		[self.next_ping, self.pong_deadline, self.idle_deadline].iter().flatten().min().copied()
+	}
+
+	/// Reports that a message has been sent or received at `now`.
+	pub fn on_message(&mut self, now: Instant) {
+		if let Some(timeout) = self.config.idle_timeout {
+			self.idle_deadline = Some(now + timeout);
+		}
+	}
+
+	/// Reports that a pong has been received.
+	pub fn on_pong(&mut self) {
+		self.pong_deadline = None;
+	}
+
+	/// Returns what the connection must do at `now`, if anything.
+	pub fn poll(&mut self, now: Instant) -> Option<KeepAliveEvent> {
+		if matches!(self.pong_deadline, Some(deadline) if deadline <= now) {
+			return Some(KeepAliveEvent::PongTimeout);
+		}
+		if matches!(self.idle_deadline, Some(deadline) if deadline <= now) {
+			return Some(KeepAliveEvent::IdleTimeout);
+		}
+		match (self.next_ping, self.config.ping_interval) {
+			(Some(next_ping), Some(interval)) if next_ping <= now => {
+				self.next_ping = Some(now + interval);
+				// An unanswered ping keeps its deadline.
+				self.pong_deadline.get_or_insert(now + self.config.pong_timeout);
+				Some(KeepAliveEvent::SendPing)
+			}
+			_ => None,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{KeepAlive, KeepAliveConfig, KeepAliveEvent};
+	use std::time::{Duration, Instant};
+
+	#[test]
+	fn disabled_by_default() {
+		let keep_alive = KeepAlive::new(KeepAliveConfig::default(), Instant::now());
+		assert_eq!(keep_alive.deadline(), None);
+	}
+
+	#[test]
+	fn pings_and_pong_timeout() {
+		let secs = Duration::from_secs;
+		let config = KeepAliveConfig { ping_interval: Some(secs(10)), pong_timeout: secs(5), idle_timeout: None };
+		let start = Instant::now();
+		let mut keep_alive = KeepAlive::new(config, start);
+
+		assert_eq!(keep_alive.deadline(), Some(start + secs(10)));
+		assert_eq!(keep_alive.poll(start + secs(9)), None);
+		assert_eq!(keep_alive.poll(start + secs(10)), Some(KeepAliveEvent::SendPing));
+		assert_eq!(keep_alive.deadline(), Some(start + secs(15)));
+		keep_alive.on_pong();
+		assert_eq!(keep_alive.deadline(), Some(start + secs(20)));
+
+		assert_eq!(keep_alive.poll(start + secs(20)), Some(KeepAliveEvent::SendPing));
+		assert_eq!(keep_alive.poll(start + secs(25)), Some(KeepAliveEvent::PongTimeout));
+	}
+
+	#[test]
+	fn idle_timeout() {
+		let secs = Duration::from_secs;
+		let config = KeepAliveConfig { idle_timeout: Some(secs(10)), ..Default::default() };
+		let start = Instant::now();
+		let mut keep_alive = KeepAlive::new(config, start);
+
+		keep_alive.on_message(start + secs(5));
+		assert_eq!(keep_alive.deadline(), Some(start + secs(15)));
+		assert_eq!(keep_alive.poll(start + secs(10)), None);
+		assert_eq!(keep_alive.poll(start + secs(15)), Some(KeepAliveEvent::IdleTimeout));
+	}
+}
@@ -26,7 +26,7 @@
 
 //! Access control based on http headers
 
-use crate::http::cors::{AccessControlAllowHeaders, AccessControlAllowOrigin};
+use crate::http::cors::{AccessControlAllowHeaders, AccessControlAllowOrigin, AllowCors};
 use crate::http::hosts::{AllowHosts, Host};
 use crate::http::{cors, hosts, hyper_helpers};
 use hyper::{self, header};
@@ -44,12 +44,55 @@ pub struct AccessControl {
 impl AccessControl {
 	/// Validate incoming request by http HOST
 	pub fn deny_host(&self, request: &hyper::Request<hyper::Body>) -> bool {
-		!hosts::is_host_valid(hyper_helpers::read_header_value(request.headers(), "host"), &self.allow_hosts)
+		// HTTP/2 requests carry the host in the `:authority` pseudo-header instead of `Host`.
+		let host = hyper_helpers::read_header_value(request.headers(), "host")
+			.or_else(|| request.uri().authority().map(|authority| authority.as_str()));
+		!hosts::is_host_valid(host, &self.allow_hosts)
 	}
 
 	/// Validate incoming request by CORS origin
 	pub fn deny_cors_origin(&self, request: &hyper::Request<hyper::Body>) -> bool {
-		let header = cors::get_cors_allow_origin(
+		self.cors_allow_origin(request) == AllowCors::Invalid && !self.continue_on_invalid_cors
+	}
+
+	/// Validate incoming request by CORS header
+	pub fn deny_cors_header(&self, request: &hyper::Request<hyper::Body>) -> bool {
+		self.cors_allow_headers(request) == AllowCors::Invalid && !self.continue_on_invalid_cors
+	}
+
+	/// Returns the CORS headers to add to the response to `request`.
+	///
+	/// The map is empty if the request doesn't need CORS headers, which is the case when it has
+	/// no `Origin` header or when the origin isn't allowed. The headers that only matter to
+	/// preflight requests are included if `request` is an `OPTIONS` request.
+	pub fn cors_headers(&self, request: &hyper::Request<hyper::Body>) -> header::HeaderMap {
+		let mut headers = header::HeaderMap::new();
+		let allow_origin = match self.cors_allow_origin(request) {
+			AllowCors::Ok(origin) => origin,
+			AllowCors::NotRequired | AllowCors::Invalid => return headers,
+		};
+
+		headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+		headers.insert(header::VARY, header::HeaderValue::from_static("origin"));
+		if request.method() == hyper::Method::OPTIONS {
+			headers.insert(header::ACCESS_CONTROL_ALLOW_METHODS, header::HeaderValue::from_static("OPTIONS, POST"));
+			if let AllowCors::Ok(allow_headers) = self.cors_allow_headers(request) {
+				let allow_headers =
+					allow_headers.iter().filter_map(|value| value.to_str().ok()).collect::<Vec<_>>().join(", ");
+				if let Ok(value) = header::HeaderValue::from_str(&allow_headers) {
+					headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, value);
+				}
+			}
+			if let Some(max_age) = self.cors_max_age {
+				headers.insert(header::ACCESS_CONTROL_MAX_AGE, max_age.into());
+			}
+		}
+		headers
+	}
+
+	/// Returns the value of the `Access-Control-Allow-Origin` header to send back.
+	fn cors_allow_origin(&self, request: &hyper::Request<hyper::Body>) -> AllowCors<header::HeaderValue> {
+		cors::get_cors_allow_origin(
 			hyper_helpers::read_header_value(request.headers(), "origin"),
 			hyper_helpers::read_header_value(request.headers(), "host"),
 			&self.cors_allow_origin,
@@ -63,22 +106,20 @@ impl AccessControl {
 				Null => header::HeaderValue::from_static("null"),
 				Any => header::HeaderValue::from_static("*"),
 			}
-		});
-		header == cors::AllowCors::Invalid && !self.continue_on_invalid_cors
+		})
 	}
 
-	/// Validate incoming request by CORS header
-	pub fn deny_cors_header(&self, request: &hyper::Request<hyper::Body>) -> bool {
+	/// Returns the values of the `Access-Control-Allow-Headers` header to send back.
+	fn cors_allow_headers(&self, request: &hyper::Request<hyper::Body>) -> AllowCors<Vec<header::HeaderValue>> {
 		let headers = request.headers().keys().map(|name| name.as_str());
 		let requested_headers = hyper_helpers::read_header_values(request.headers(), "access-control-request-headers")
 			.filter_map(|val| val.to_str().ok())
 			.flat_map(|val| val.split(", "))
 			.flat_map(|val| val.split(','));
 
-		let header = cors::get_cors_allow_headers(headers, requested_headers, &self.cors_allow_headers, |name| {
+		cors::get_cors_allow_headers(headers, requested_headers, &self.cors_allow_headers, |name| {
 			header::HeaderValue::from_str(name).unwrap_or_else(|_| header::HeaderValue::from_static("unknown"))
-		});
-		header == cors::AllowCors::Invalid && !self.continue_on_invalid_cors
+		})
 	}
 }
 
@@ -0,0 +1,210 @@
+//! Framing of JSON messages on IPC byte streams.
+
+use futures::io::{AsyncBufRead, AsyncBufReadExt as _, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+use jsonrpsee_types::{error::GenericTransportError, ipc::Framing};
+use std::{convert::TryFrom, io, str};
+
+/// Maximum size of a header line with [`Framing::ContentLength`].
+const MAX_HEADER_LINE_SIZE: u32 = 1024;
+
+/// Reads the next message from `reader`.
+///
+/// Returns `Ok(None)` if the stream has been closed between two messages, and an error if the
+/// message is larger than `max_message_size` bytes.
+pub async fn read_message<R>(
+	reader: &mut R,
+	framing: Framing,
+	max_message_size: u32,
+) -> Result<Option<Vec<u8>>, GenericTransportError<io::Error>>
+where
+	R: AsyncBufRead + Unpin,
+{
+	match framing {
+		Framing::Newline => loop {
+			match read_line(reader, max_message_size).await? {
+				None => return Ok(None),
+				Some(line) if line.iter().all(u8::is_ascii_whitespace) => {}
+				Some(line) => return Ok(Some(line)),
+			}
+		},
+		Framing::LengthDelimited => {
+			if reader.fill_buf().await.map_err(GenericTransportError::Inner)?.is_empty() {
+				return Ok(None);
+			}
+			let mut length = [0; 4];
+			reader.read_exact(&mut length).await.map_err(GenericTransportError::Inner)?;
+			let length = u32::from_be_bytes(length);
+			if length > max_message_size {
+				return Err(GenericTransportError::TooLarge);
+			}
+			let mut message = vec![0; length as usize];
+			reader.read_exact(&mut message).await.map_err(GenericTransportError::Inner)?;
+			Ok(Some(message))
+		}
+		Framing::ContentLength => {
+			let mut length = None;
+			loop {
+				let line = match read_line(reader, MAX_HEADER_LINE_SIZE).await? {
+					Some(line) => line,
+					None if length.is_none() => return Ok(None),
+					None => return Err(GenericTransportError::Inner(io::ErrorKind::UnexpectedEof.into())),
+				};
+				if line.is_empty() {
+					// Empty lines before the headers are tolerated.
+					if length.is_some() {
+						break;
+					}
+					continue;
+				}
+				let header = str::from_utf8(&line).ok().and_then(|line| {
+					let mut parts = line.splitn(2, ':');
+					Some((parts.next()?.trim(), parts.next()?.trim()))
+				});
+				match header {
+					Some((name, value)) if name.eq_ignore_ascii_case("content-length") => {
+						length = Some(value.parse::<u64>().map_err(|_| invalid_data("invalid Content-Length"))?);
+					}
+					Some(_) => {}
+					None => return Err(invalid_data("invalid header")),
+				}
+			}
+			let length = length.expect("the loop only ends once the length is known; qed");
+			if length > u64::from(max_message_size) {
+				return Err(GenericTransportError::TooLarge);
+			}
+			let mut message = vec![0; length as usize];
+			reader.read_exact(&mut message).await.map_err(GenericTransportError::Inner)?;
+			Ok(Some(message))
+		}
+	}
+}
+
+/// Writes a message to `writer` and flushes it.
+///
+/// With [`Framing::Newline`], `message` must not contain any line feed, which is always the case
+/// of JSON serialized by `serde_json::to_vec`.
+pub async fn write_message<W>(writer: &mut W, framing: Framing, message: &[u8]) -> io::Result<()>
+where
+	W: AsyncWrite + Unpin,
+{
+	match framing {
+		Framing::Newline => {
+			debug_assert!(!message.contains(&b'\n'));
+			writer.write_all(message).await?;
+			writer.write_all(b"\n").await?;
+		}
+		Framing::LengthDelimited => {
+			let length = u32::try_from(message.len())
+				.map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too large"))?;
+			writer.write_all(&length.to_be_bytes()).await?;
+			writer.write_all(message).await?;
+		}
+		Framing::ContentLength => {
+			writer.write_all(format!("Content-Length: {}\r\n\r\n", message.len()).as_bytes()).await?;
+			writer.write_all(message).await?;
+		}
+	}
+	writer.flush().await
+}
+
+/// Reads a line terminated by `\n` or `\r\n` from `reader`, without its terminator.
+///
+/// Returns `Ok(None)` if the stream has been closed before the start of the line.
+async fn read_line<R>(reader: &mut R, max_size: u32) -> Result<Option<Vec<u8>>, GenericTransportError<io::Error>>
+where
+	R: AsyncBufRead + Unpin,
+{
+	let mut line = Vec::new();
+	loop {
+		let buffer = reader.fill_buf().await.map_err(GenericTransportError::Inner)?;
+		if buffer.is_empty() {
+			if line.is_empty() {
+				return Ok(None);
+			}
+			return Err(GenericTransportError::Inner(io::ErrorKind::UnexpectedEof.into()));
+		}
+		let (chunk, found) = match buffer.iter().position(|b| *b == b'\n') {
+			Some(pos) => (&buffer[..pos], true),
+			None => (buffer, false),
+		};
+		if line.len() + chunk.len() > max_size as usize {
+			return Err(GenericTransportError::TooLarge);
+		}
+		line.extend_from_slice(chunk);
+		let consumed = chunk.len() + if found { 1 } else { 0 };
+		reader.consume_unpin(consumed);
+		if found {
+			break;
+		}
+	}
+	if line.last() == Some(&b'\r') {
+		line.pop();
+	}
+	Ok(Some(line))
+}
+
+fn invalid_data(error: &str) -> GenericTransportError<io::Error> {
+	GenericTransportError::Inner(io::Error::new(io::ErrorKind::InvalidData, error))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{read_message, write_message, Framing};
+	use futures::{executor::block_on, io::Cursor};
+	use jsonrpsee_types::error::GenericTransportError;
+
+	const ALL_FRAMINGS: &[Framing] = &[Framing::Newline, Framing::LengthDelimited, Framing::ContentLength];
+
+	#[test]
+	fn messages_roundtrip() {
+		for framing in ALL_FRAMINGS {
+			let mut stream = Vec::new();
+			block_on(write_message(&mut stream, *framing, br#"{"a":1}"#)).unwrap();
+			block_on(write_message(&mut stream, *framing, br#"{"b":2}"#)).unwrap();
+
+			let mut reader = Cursor::new(stream);
+			assert_eq!(block_on(read_message(&mut reader, *framing, 1024)).unwrap().unwrap(), br#"{"a":1}"#);
+			assert_eq!(block_on(read_message(&mut reader, *framing, 1024)).unwrap().unwrap(), br#"{"b":2}"#);
+			assert!(block_on(read_message(&mut reader, *framing, 1024)).unwrap().is_none());
+		}
+	}
+
+	#[test]
+	fn newline_framing_skips_empty_lines() {
+		let mut reader = Cursor::new(b"\r\n\n{\"a\":1}\r\n".to_vec());
+		assert_eq!(block_on(read_message(&mut reader, Framing::Newline, 1024)).unwrap().unwrap(), br#"{"a":1}"#);
+		assert!(block_on(read_message(&mut reader, Framing::Newline, 1024)).unwrap().is_none());
+	}
+
+	#[test]
+	fn content_length_framing_parses_headers() {
+		let stream =
+			b"\r\ncontent-length: 7\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{\"a\":1}";
+		let mut reader = Cursor::new(stream.to_vec());
+		assert_eq!(block_on(read_message(&mut reader, Framing::ContentLength, 1024)).unwrap().unwrap(), br#"{"a":1}"#);
+		assert!(block_on(read_message(&mut reader, Framing::ContentLength, 1024)).unwrap().is_none());
+
+		let invalid: &[&[u8]] = &[
+			b"Content-Length: abc\r\n\r\n{}",
+			b"Content-Type: json\r\n\r\n{}",
+			b"Content-Length: 7\r\n",
+			b"oops\r\n\r\n",
+		];
+		for stream in invalid {
+			let mut reader = Cursor::new(stream.to_vec());
+			let err = block_on(read_message(&mut reader, Framing::ContentLength, 1024)).unwrap_err();
+			assert!(matches!(err, GenericTransportError::Inner(_)));
+		}
+	}
+
+	#[test]
+	fn too_large_message_is_rejected() {
+		for framing in ALL_FRAMINGS {
+			let mut stream = Vec::new();
+			block_on(write_message(&mut stream, *framing, br#"{"a":1}"#)).unwrap();
+			let mut reader = Cursor::new(stream);
+			let err = block_on(read_message(&mut reader, *framing, 4)).unwrap_err();
+			assert!(matches!(err, GenericTransportError::TooLarge));
+		}
+	}
+}
@@ -1 +1,3 @@
 pub mod http;
+pub mod ipc;
+pub mod tls;
@@ -0,0 +1,195 @@
+//! TLS utilities for the `jsonrpsee` servers
+
+use rustls::internal::pemfile;
+use rustls::{
+	AllowAnyAuthenticatedClient, Certificate, ClientConfig, NoClientAuth, PrivateKey, RootCertStore, ServerConfig,
+};
+use std::io;
+
+/// ALPN identifier of HTTP/2.
+pub const ALPN_HTTP2: &[u8] = b"h2";
+/// ALPN identifier of HTTP/1.1.
+pub const ALPN_HTTP1: &[u8] = b"http/1.1";
+
+/// Certificate chain and private key that a server presents to its TLS clients.
+#[derive(Clone)]
+pub struct TlsServerConfig {
+	/// Certificate chain, starting with the certificate of the server.
+	cert_chain: Vec<Certificate>,
+	/// Private key matching the first certificate of the chain.
+	private_key: PrivateKey,
+	/// If `Some`, the clients must present a certificate issued by one of these authorities.
+	client_auth_roots: Option<RootCertStore>,
+}
+
+/// Root certificates that a client trusts, and certificate that it presents to the servers
+/// that ask for one.
+#[derive(Clone)]
+pub struct TlsClientConfig {
+	/// Certificate authorities trusted in addition to the Mozilla root certificates.
+	root_certs: Vec<Certificate>,
+	/// Whether to trust the Mozilla root certificates.
+	webpki_roots: bool,
+	/// Certificate chain and private key of the client, if any.
+	client_auth: Option<(Vec<Certificate>, PrivateKey)>,
+}
+
+/// Error while loading a [`TlsServerConfig`] or a [`TlsClientConfig`].
+#[derive(Debug, thiserror::Error)]
+pub enum TlsConfigError {
+	/// The certificate chain isn't valid PEM or doesn't contain any certificate.
+	#[error("Invalid or empty PEM certificate chain")]
+	InvalidCertificate,
+	/// The private key isn't a valid PEM PKCS#8 or RSA private key.
+	#[error("Invalid or missing PEM private key")]
+	InvalidPrivateKey,
+	/// The private key is unsupported or doesn't match the certificate.
+	#[error("Invalid certificate or private key: {0}")]
+	Rustls(#[source] rustls::TLSError),
+	/// A root certificate couldn't be parsed.
+	#[error("Invalid root certificate")]
+	InvalidRootCertificate,
+}
+
+impl TlsServerConfig {
+	/// Loads a certificate chain and a private key (PKCS#8 or RSA), both in the PEM format.
+	pub fn from_pem(cert_chain: &[u8], private_key: &[u8]) -> Result<Self, TlsConfigError> {
+		let (cert_chain, private_key) = parse_cert_and_key(cert_chain, private_key)?;
+
+		// Make sure that the key is usable, so that building the server configuration later on
+		// can't fail.
+		ServerConfig::new(NoClientAuth::new())
+			.set_single_cert(cert_chain.clone(), private_key.clone())
+			.map_err(TlsConfigError::Rustls)?;
+
+		Ok(TlsServerConfig { cert_chain, private_key, client_auth_roots: None })
+	}
+
+	/// Requires the clients to present a certificate issued by one of the given certificate
+	/// authorities, in the PEM format.
+	///
+	/// By default, the clients aren't authenticated.
+	pub fn client_auth_pem(mut self, root_certs: &[u8]) -> Result<Self, TlsConfigError> {
+		let mut roots = RootCertStore::empty();
+		for cert in parse_root_certs(root_certs)? {
+			roots.add(&cert).map_err(|_| TlsConfigError::InvalidRootCertificate)?;
+		}
+		self.client_auth_roots = Some(roots);
+		Ok(self)
+	}
+
+	/// Builds the `rustls` configuration of the server, offering the given ALPN protocols by order
+	/// of preference.
+	pub fn server_config(&self, alpn_protocols: &[&[u8]]) -> ServerConfig {
+		let mut config = match &self.client_auth_roots {
+			Some(roots) => ServerConfig::new(AllowAnyAuthenticatedClient::new(roots.clone())),
+			None => ServerConfig::new(NoClientAuth::new()),
+		};
+		config
+			.set_single_cert(self.cert_chain.clone(), self.private_key.clone())
+			.expect("the certificate and the key are checked in `from_pem`; qed");
+		config.set_protocols(&alpn_protocols.iter().map(|protocol| protocol.to_vec()).collect::<Vec<_>>());
+		config
+	}
+}
+
+impl TlsClientConfig {
+	/// Creates a configuration that trusts the Mozilla root certificates and doesn't present any
+	/// client certificate.
+	pub fn new() -> Self {
+		TlsClientConfig { root_certs: Vec::new(), webpki_roots: true, client_auth: None }
+	}
+
+	/// Trusts the given certificate authorities, in the PEM format, in addition to the ones that
+	/// are already trusted.
+	pub fn add_root_certs_pem(mut self, root_certs: &[u8]) -> Result<Self, TlsConfigError> {
+		let certs = parse_root_certs(root_certs)?;
+		// Make sure that the certificates are usable, so that building the client configuration
+		// later on can't fail.
+		let mut roots = RootCertStore::empty();
+		for cert in &certs {
+			roots.add(cert).map_err(|_| TlsConfigError::InvalidRootCertificate)?;
+		}
+		self.root_certs.extend(certs);
+		Ok(self)
+	}
+
+	/// Stops trusting the Mozilla root certificates, so that only the certificate authorities
+	/// passed to [`TlsClientConfig::add_root_certs_pem`] are trusted.
+	pub fn without_webpki_roots(mut self) -> Self {
+		self.webpki_roots = false;
+		self
+	}
+
+	/// Presents the given certificate chain and private key (PKCS#8 or RSA), both in the PEM
+	/// format, to the servers that authenticate their clients.
+	pub fn client_auth_pem(mut self, cert_chain: &[u8], private_key: &[u8]) -> Result<Self, TlsConfigError> {
+		let (cert_chain, private_key) = parse_cert_and_key(cert_chain, private_key)?;
+		ClientConfig::new()
+			.set_single_client_cert(cert_chain.clone(), private_key.clone())
+			.map_err(TlsConfigError::Rustls)?;
+		self.client_auth = Some((cert_chain, private_key));
+		Ok(self)
+	}
+
+	/// Builds the `rustls` configuration of the client, offering the given ALPN protocols by order
+	/// of preference.
+	pub fn client_config(&self, alpn_protocols: &[&[u8]]) -> ClientConfig {
+		let mut config = ClientConfig::new();
+		if self.webpki_roots {
+			config.root_store.add_server_trust_anchors(&webpki_roots::TLS_SERVER_ROOTS);
+		}
+		for cert in &self.root_certs {
+			config.root_store.add(cert).expect("the certificates are checked in `add_root_certs_pem`; qed");
+		}
+		if let Some((cert_chain, private_key)) = &self.client_auth {
+			config
+				.set_single_client_cert(cert_chain.clone(), private_key.clone())
+				.expect("the certificate and the key are checked in `client_auth_pem`; qed");
+		}
+		config.set_protocols(&alpn_protocols.iter().map(|protocol| protocol.to_vec()).collect::<Vec<_>>());
+		config
+	}
+}
+
+impl Default for TlsClientConfig {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Parses a non-empty list of PEM certificate authorities.
+fn parse_root_certs(root_certs: &[u8]) -> Result<Vec<Certificate>, TlsConfigError> {
+	pemfile::certs(&mut io::Cursor::new(root_certs))
+		.ok()
+		.filter(|certs| !certs.is_empty())
+		.ok_or(TlsConfigError::InvalidRootCertificate)
+}
+
+/// Parses a PEM certificate chain and the matching PKCS#8 or RSA private key.
+fn parse_cert_and_key(cert_chain: &[u8], private_key: &[u8]) -> Result<(Vec<Certificate>, PrivateKey), TlsConfigError> {
+	let cert_chain = pemfile::certs(&mut io::Cursor::new(cert_chain))
+		.ok()
+		.filter(|certs| !certs.is_empty())
+		.ok_or(TlsConfigError::InvalidCertificate)?;
+	let private_key = pemfile::pkcs8_private_keys(&mut io::Cursor::new(private_key))
+		.ok()
+		.filter(|keys| !keys.is_empty())
+		.or_else(|| pemfile::rsa_private_keys(&mut io::Cursor::new(private_key)).ok())
+		.and_then(|keys| keys.into_iter().next())
+		.ok_or(TlsConfigError::InvalidPrivateKey)?;
+	Ok((cert_chain, private_key))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::{TlsClientConfig, TlsConfigError, TlsServerConfig};
+
+	#[test]
+	fn invalid_pem_is_rejected() {
+		let err = TlsServerConfig::from_pem(b"not a certificate", b"not a key");
+		assert!(matches!(err, Err(TlsConfigError::InvalidCertificate)));
+		let err = TlsClientConfig::new().add_root_certs_pem(b"not a certificate");
+		assert!(matches!(err, Err(TlsConfigError::InvalidRootCertificate)));
+	}
+}
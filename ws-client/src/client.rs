@@ -25,18 +25,24 @@
 // DEALINGS IN THE SOFTWARE.
 
 use crate::jsonrpc_transport;
-use crate::manager::{RequestManager, RequestStatus};
+use crate::manager::{CancelKey, RequestManager, RequestStatus, SubscribeRequest};
+use crate::transport::Incoming;
 use futures::{
 	channel::{mpsc, oneshot},
+	future::BoxFuture,
 	prelude::*,
 	sink::SinkExt,
 };
 use jsonrpsee_types::{
+	client::{parse_batch_results, BatchEntry, Client},
 	error::Error,
 	jsonrpc::{self, JsonValue, SubscriptionId},
+	ws::{KeepAlive, KeepAliveConfig, KeepAliveEvent},
 };
 use std::convert::TryInto;
-use std::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{io, marker::PhantomData};
 
 /// Client that can be cloned.
@@ -50,6 +56,9 @@ pub struct WsClient {
 	to_back: mpsc::Sender<FrontToBack>,
 	/// Config.
 	config: WsConfig,
+	/// Key with which the next method call or batch can be given up, shared by the clones of
+	/// the client. Wraps around when overflowing.
+	next_cancel_key: Arc<AtomicU64>,
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -63,6 +72,44 @@ pub struct WsConfig {
 	pub max_request_body_size: usize,
 	/// Request timeout
 	pub request_timeout: Option<Duration>,
+	/// Detection of dead and idle connections. Once detected, the client is terminated, unless
+	/// it reconnects to the server after losing the connection.
+	pub keep_alive: KeepAliveConfig,
+	/// Reconnection to the server once the connection is lost. If `None`, the client is
+	/// terminated instead.
+	pub reconnect: Option<ReconnectConfig>,
+}
+
+/// Policy to reconnect to the server once the connection is lost.
+///
+/// While the client reconnects, calls wait until a new connection is established. Active
+/// subscriptions are established again with their original parameters, and the notifications
+/// sent by the server in between are missed, see [`WsSubscription::next_event`].
+#[derive(Copy, Clone, Debug)]
+pub struct ReconnectConfig {
+	/// Delay before the first attempt to reconnect.
+	pub initial_delay: Duration,
+	/// Maximum delay between two attempts. The delay doubles after each failed attempt.
+	pub max_delay: Duration,
+	/// Number of failed attempts after which the client is terminated, or `None` to never give up.
+	pub max_attempts: Option<u32>,
+	/// Whether method calls, batches and subscription requests waiting for a response are sent
+	/// again once reconnected. Otherwise they fail with [`Error::ConnectionLost`].
+	///
+	/// The server might have handled them before the connection was lost, so only enable this if
+	/// handling a request twice is harmless.
+	pub retry_pending_requests: bool,
+}
+
+impl Default for ReconnectConfig {
+	fn default() -> Self {
+		Self {
+			initial_delay: Duration::from_millis(500),
+			max_delay: Duration::from_secs(30),
+			max_attempts: None,
+			retry_pending_requests: false,
+		}
+	}
 }
 
 impl Default for WsConfig {
@@ -72,6 +119,8 @@ impl Default for WsConfig {
 			subscription_channel_capacity: 4,
 			max_request_body_size: 10 * 1024 * 1024,
 			request_timeout: None,
+			keep_alive: KeepAliveConfig::default(),
+			reconnect: None,
 		}
 	}
 }
@@ -80,14 +129,50 @@ impl Default for WsConfig {
 pub struct WsSubscription<Notif> {
 	/// Channel to send requests to the background task.
 	to_back: mpsc::Sender<FrontToBack>,
-	/// Channel from which we receive notifications from the server, as undecoded `JsonValue`s.
-	notifs_rx: mpsc::Receiver<JsonValue>,
+	/// Channel from which we receive notifications from the server, as undecoded `JsonValue`s,
+	/// followed by the reason why the subscription has ended.
+	notifs_rx: mpsc::Receiver<SubscriptionMessage>,
+	/// Why the subscription has ended, once it has.
+	end: Option<SubscriptionEnd>,
 	/// Subscription ID,
 	id: SubscriptionId,
 	/// Marker in order to pin the `Notif` parameter.
 	marker: PhantomData<Notif>,
 }
 
+/// Reason why a [`WsSubscription`] has ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionEnd {
+	/// The server has sent all the notifications of the subscription.
+	Completed,
+	/// The server has terminated the subscription because of an error.
+	Error(jsonrpc::Error),
+	/// The notifications weren't read fast enough and the subscription channel was full.
+	Lagged,
+	/// The connection to the server has been closed.
+	Disconnected,
+}
+
+/// Event of a [`WsSubscription`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SubscriptionEvent<Notif> {
+	/// Notification from the server.
+	Notification(Notif),
+	/// The client has reconnected to the server and subscribed again. The notifications sent by
+	/// the server in between have been missed.
+	Resubscribed,
+}
+
+/// Message sent by the background task to a [`WsSubscription`].
+pub enum SubscriptionMessage {
+	/// Notification from the server.
+	Notification(JsonValue),
+	/// The subscription has been established again, under a new ID, after reconnecting.
+	Resubscribed(SubscriptionId),
+	/// The subscription has ended. This is the last message.
+	End(SubscriptionEnd),
+}
+
 /// Message that the [`Client`] can send to the background task.
 enum FrontToBack {
 	/// Send a one-shot notification to the server. The server doesn't give back any feedback.
@@ -106,6 +191,18 @@ enum FrontToBack {
 		params: jsonrpc::Params,
 		/// One-shot channel where to send back the outcome of that request.
 		send_back: oneshot::Sender<Result<JsonValue, Error>>,
+		/// Key with which the request is given up, see [`FrontToBack::RequestCancelled`].
+		cancel_key: CancelKey,
+	},
+
+	/// Send a batch of method calls and notifications to the server.
+	Batch {
+		/// Method calls and notifications of the batch.
+		batch: Vec<BatchEntry>,
+		/// One-shot channel where to send back the results of the method calls, in order.
+		send_back: oneshot::Sender<Result<Vec<Result<JsonValue, Error>>, Error>>,
+		/// Key with which the batch is given up, see [`FrontToBack::RequestCancelled`].
+		cancel_key: CancelKey,
 	},
 
 	/// Send a subscription request to the server.
@@ -119,15 +216,39 @@ enum FrontToBack {
 		/// When we get a response from the server about that subscription, we send the result on
 		/// this channel. If the subscription succeeds, we return a `Receiver` that will receive
 		/// notifications.
-		send_back: oneshot::Sender<Result<(mpsc::Receiver<JsonValue>, SubscriptionId), Error>>,
+		send_back: oneshot::Sender<Result<(mpsc::Receiver<SubscriptionMessage>, SubscriptionId), Error>>,
 	},
 
 	/// When a subscription channel is closed, we send this message to the background
 	/// task to mark it ready for garbage collection.
-	// NOTE: It is not possible to cancel pending subscriptions or pending requests.
-	// Such operations will be blocked until a response is received or the background
-	// thread has been terminated.
+	// NOTE: A pending subscription can't be cancelled before the server answers. Once it does,
+	// the background task unsubscribes if the frontend has given up on the subscription.
 	SubscriptionClosed(SubscriptionId),
+
+	/// The request or batch with the given key has been given up, because its future was dropped
+	/// or has timed out. The background task forgets about it.
+	RequestCancelled(CancelKey),
+}
+
+/// Tells the background task that the request with the given key has been given up when
+/// dropped, unless the outcome of the request has been received.
+struct CancelGuard(Option<(mpsc::Sender<FrontToBack>, CancelKey)>);
+
+impl CancelGuard {
+	/// The outcome of the request has been received.
+	fn disarm(mut self) {
+		self.0 = None;
+	}
+}
+
+impl Drop for CancelGuard {
+	fn drop(&mut self) {
+		if let Some((mut to_back, cancel_key)) = self.0.take() {
+			// Each sender has a guaranteed slot in the channel, so this goes through even if the
+			// channel is full.
+			let _ = to_back.try_send(FrontToBack::RequestCancelled(cancel_key));
+		}
+	}
 }
 
 impl WsClient {
@@ -135,16 +256,37 @@ impl WsClient {
 	///
 	/// Fails when the URL is invalid.
 	pub async fn new(remote_addr: impl AsRef<str>, config: WsConfig) -> Result<Self, Error> {
-		let (sender, receiver) = jsonrpc_transport::websocket_connection(remote_addr.as_ref())
+		Self::connect(remote_addr.as_ref(), config, None).await
+	}
+
+	/// Initializes a new WebSocket client that uses the given TLS configuration for `wss://`
+	/// URLs, for example in order to trust additional root certificates.
+	///
+	/// Fails when the URL is invalid.
+	pub async fn with_tls_config(
+		remote_addr: impl AsRef<str>,
+		config: WsConfig,
+		tls_config: rustls::ClientConfig,
+	) -> Result<Self, Error> {
+		Self::connect(remote_addr.as_ref(), config, Some(Arc::new(tls_config))).await
+	}
+
+	async fn connect(
+		remote_addr: &str,
+		config: WsConfig,
+		tls_config: Option<Arc<rustls::ClientConfig>>,
+	) -> Result<Self, Error> {
+		let (sender, receiver) = jsonrpc_transport::websocket_connection(remote_addr, tls_config.clone())
 			.await
 			.map_err(|e| Error::TransportError(Box::new(e)))?;
 
 		let (to_back, from_front) = mpsc::channel(config.request_channel_capacity);
+		let remote_addr = remote_addr.to_owned();
 
 		async_std::task::spawn(async move {
-			background_task(sender, receiver, from_front, config).await;
+			background_task(sender, receiver, from_front, config, remote_addr, tls_config).await;
 		});
-		Ok(Self { to_back, config })
+		Ok(Self { to_back, config, next_cancel_key: Arc::new(AtomicU64::new(0)) })
 	}
 
 	/// Send a notification to the server.
@@ -171,13 +313,79 @@ impl WsClient {
 		let method = method.into();
 		let params = params.into();
 		log::trace!("[frontend]: send request: method={:?}, params={:?}", method, params);
-		let (send_back_tx, send_back_rx) = oneshot::channel();
-		self.to_back
-			.clone()
-			.send(FrontToBack::StartRequest { method, params, send_back: send_back_tx })
-			.await
-			.map_err(Error::Internal)?;
+		loop {
+			let (send_back_tx, send_back_rx) = oneshot::channel();
+			let cancel_key = self.next_cancel_key();
+			self.to_back
+				.clone()
+				.send(FrontToBack::StartRequest {
+					method: method.clone(),
+					params: params.clone(),
+					send_back: send_back_tx,
+					cancel_key,
+				})
+				.await
+				.map_err(Error::Internal)?;
+
+			match self.wait_for_response(send_back_rx, cancel_key).await {
+				Err(Error::ConnectionLost) if self.retry_pending_requests() => {
+					log::debug!("[frontend]: connection lost; retry request={:?}", method);
+				}
+				json_value => return jsonrpc::from_value(json_value?).map_err(Error::ParseError),
+			}
+		}
+	}
+
+	/// Send a batch of method calls and notifications to the server in a single message.
+	///
+	/// The results are the undecoded `JsonValue`s, see [`Client::batch_request`] for how they
+	/// relate to the entries of `batch`.
+	pub async fn batch_request(&self, batch: Vec<BatchEntry>) -> Result<Vec<Result<JsonValue, Error>>, Error> {
+		// An empty batch is an invalid request.
+		if batch.is_empty() {
+			return Ok(Vec::new());
+		}
+
+		log::trace!("[frontend]: send batch: {:?}", batch);
+		loop {
+			let (send_back_tx, send_back_rx) = oneshot::channel();
+			let cancel_key = self.next_cancel_key();
+			self.to_back
+				.clone()
+				.send(FrontToBack::Batch { batch: batch.clone(), send_back: send_back_tx, cancel_key })
+				.await
+				.map_err(Error::Internal)?;
 
+			match self.wait_for_response(send_back_rx, cancel_key).await {
+				Err(Error::ConnectionLost) if self.retry_pending_requests() => {
+					log::debug!("[frontend]: connection lost; retry batch");
+				}
+				results => return results,
+			}
+		}
+	}
+
+	/// Whether the calls that fail because the connection was lost are sent again.
+	fn retry_pending_requests(&self) -> bool {
+		matches!(self.config.reconnect, Some(ReconnectConfig { retry_pending_requests: true, .. }))
+	}
+
+	/// Returns a key to give up on a method call or batch with, that no other pending one uses.
+	fn next_cancel_key(&self) -> CancelKey {
+		// NOTE: `fetch_add` wraps on overflow which is intended.
+		self.next_cancel_key.fetch_add(1, Ordering::Relaxed)
+	}
+
+	/// Waits for the background task to send back the outcome of the request with the given
+	/// cancellation key, for at most [`WsConfig::request_timeout`].
+	///
+	/// If this future is dropped or times out, the background task forgets about the request.
+	async fn wait_for_response<T>(
+		&self,
+		send_back_rx: oneshot::Receiver<Result<T, Error>>,
+		cancel_key: CancelKey,
+	) -> Result<T, Error> {
+		let cancel_guard = CancelGuard(Some((self.to_back.clone(), cancel_key)));
 		let send_back_rx_out = if let Some(duration) = self.config.request_timeout {
 			let timeout = async_std::task::sleep(duration);
 			futures::pin_mut!(send_back_rx, timeout);
@@ -188,16 +396,15 @@ impl WsClient {
 		} else {
 			send_back_rx.await
 		};
+		cancel_guard.disarm();
 
-		let json_value = match send_back_rx_out {
-			Ok(Ok(v)) => v,
-			Ok(Err(err)) => return Err(err),
+		match send_back_rx_out {
+			Ok(out) => out,
 			Err(_) => {
 				let err = io::Error::new(io::ErrorKind::Other, "background task closed");
-				return Err(Error::TransportError(Box::new(err)));
+				Err(Error::TransportError(Box::new(err)))
 			}
-		};
-		jsonrpc::from_value(json_value).map_err(Error::ParseError)
+		}
 	}
 
 	/// Send a subscription request to the server.
@@ -218,28 +425,96 @@ impl WsClient {
 		}
 
 		log::trace!("[frontend]: subscribe: {:?}, unsubscribe: {:?}", subscribe_method, unsubscribe_method);
-		let (send_back_tx, send_back_rx) = oneshot::channel();
-		self.to_back
-			.clone()
-			.send(FrontToBack::Subscribe {
-				subscribe_method,
-				unsubscribe_method,
-				params: params.into(),
-				send_back: send_back_tx,
-			})
-			.await
-			.map_err(Error::Internal)?;
+		let params = params.into();
+		let (notifs_rx, id) = loop {
+			let (send_back_tx, send_back_rx) = oneshot::channel();
+			self.to_back
+				.clone()
+				.send(FrontToBack::Subscribe {
+					subscribe_method: subscribe_method.clone(),
+					unsubscribe_method: unsubscribe_method.clone(),
+					params: params.clone(),
+					send_back: send_back_tx,
+				})
+				.await
+				.map_err(Error::Internal)?;
 
-		let (notifs_rx, id) = match send_back_rx.await {
-			Ok(Ok(val)) => val,
-			Ok(Err(err)) => return Err(err),
-			Err(_) => {
-				let err = io::Error::new(io::ErrorKind::Other, "background task closed");
-				return Err(Error::TransportError(Box::new(err)));
+			match send_back_rx.await {
+				Ok(Ok(val)) => break val,
+				Ok(Err(Error::ConnectionLost)) if self.retry_pending_requests() => {
+					log::debug!("[frontend]: connection lost; retry subscribe={:?}", subscribe_method);
+				}
+				Ok(Err(err)) => return Err(err),
+				Err(_) => {
+					let err = io::Error::new(io::ErrorKind::Other, "background task closed");
+					return Err(Error::TransportError(Box::new(err)));
+				}
 			}
 		};
 
-		Ok(WsSubscription { to_back: self.to_back.clone(), notifs_rx, marker: PhantomData, id })
+		Ok(WsSubscription { to_back: self.to_back.clone(), notifs_rx, end: None, marker: PhantomData, id })
+	}
+}
+
+/// [`Client`] that can also subscribe to notifications from the server.
+pub trait SubscriptionClient: Client {
+	/// Send a subscription request to the server.
+	///
+	/// The `subscribe_method` and `params` are used to ask for the subscription towards the
+	/// server. The `unsubscribe_method` is used to close the subscription.
+	fn subscribe<'a, Notif>(
+		&'a self,
+		subscribe_method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+		unsubscribe_method: impl Into<String>,
+	) -> BoxFuture<'a, Result<WsSubscription<Notif>, Error>>
+	where
+		Notif: Send + 'a;
+}
+
+impl Client for WsClient {
+	fn notification<'a>(
+		&'a self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> BoxFuture<'a, Result<(), Error>> {
+		let (method, params) = (method.into(), params.into());
+		Box::pin(WsClient::notification(self, method, params))
+	}
+
+	fn request<'a, T>(
+		&'a self,
+		method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+	) -> BoxFuture<'a, Result<T, Error>>
+	where
+		T: jsonrpc::DeserializeOwned + Send + 'a,
+	{
+		let (method, params) = (method.into(), params.into());
+		Box::pin(WsClient::request(self, method, params))
+	}
+
+	fn batch_request<'a, T>(&'a self, batch: Vec<BatchEntry>) -> BoxFuture<'a, Result<Vec<Result<T, Error>>, Error>>
+	where
+		T: jsonrpc::DeserializeOwned + Send + 'a,
+	{
+		Box::pin(async move { WsClient::batch_request(self, batch).await.map(parse_batch_results) })
+	}
+}
+
+impl SubscriptionClient for WsClient {
+	fn subscribe<'a, Notif>(
+		&'a self,
+		subscribe_method: impl Into<String>,
+		params: impl Into<jsonrpc::Params>,
+		unsubscribe_method: impl Into<String>,
+	) -> BoxFuture<'a, Result<WsSubscription<Notif>, Error>>
+	where
+		Notif: Send + 'a,
+	{
+		let (subscribe_method, params, unsubscribe_method) =
+			(subscribe_method.into(), params.into(), unsubscribe_method.into());
+		Box::pin(WsClient::subscribe(self, subscribe_method, params, unsubscribe_method))
 	}
 }
 
@@ -247,25 +522,66 @@ impl<Notif> WsSubscription<Notif>
 where
 	Notif: jsonrpc::DeserializeOwned,
 {
-	/// Returns the next notification from the stream
-	/// This may return `None` if the subscription has been terminated, may happen if the channel becomes full or dropped.
+	/// Returns the next notification from the stream.
+	/// Once the subscription has ended, returns why: the server may have completed or terminated
+	/// it, the channel may have become full, or the connection may have been closed.
+	///
+	/// Ignores any malformed packet. The notifications missed while the client was reconnecting
+	/// go unnoticed; use [`next_event`](WsSubscription::next_event) to be told about them.
+	pub async fn next(&mut self) -> Result<Notif, SubscriptionEnd> {
+		loop {
+			if let SubscriptionEvent::Notification(notif) = self.next_event().await? {
+				return Ok(notif);
+			}
+		}
+	}
+
+	/// Returns the next notification from the stream, or [`SubscriptionEvent::Resubscribed`] if
+	/// notifications have been missed because the client has reconnected to the server.
+	/// Once the subscription has ended, returns why, like [`next`](WsSubscription::next).
 	///
 	/// Ignores any malformed packet.
-	pub async fn next(&mut self) -> Option<Notif> {
+	pub async fn next_event(&mut self) -> Result<SubscriptionEvent<Notif>, SubscriptionEnd> {
 		loop {
+			if let Some(end) = &self.end {
+				return Err(end.clone());
+			}
 			match self.notifs_rx.next().await {
-				Some(n) => match jsonrpc::from_value(n) {
-					Ok(parsed) => return Some(parsed),
+				Some(SubscriptionMessage::Notification(n)) => match jsonrpc::from_value(n) {
+					Ok(parsed) => return Ok(SubscriptionEvent::Notification(parsed)),
 					Err(e) => log::error!("Subscription response error: {:?}", e),
 				},
-				None => return None,
+				Some(SubscriptionMessage::Resubscribed(id)) => {
+					self.id = id;
+					return Ok(SubscriptionEvent::Resubscribed);
+				}
+				Some(SubscriptionMessage::End(end)) => self.end = Some(end),
+				None => self.end = Some(SubscriptionEnd::Disconnected),
 			}
 		}
 	}
 }
 
+impl From<jsonrpc::SubscriptionClosedReason> for SubscriptionEnd {
+	fn from(reason: jsonrpc::SubscriptionClosedReason) -> Self {
+		match reason {
+			jsonrpc::SubscriptionClosedReason::Completed => SubscriptionEnd::Completed,
+			jsonrpc::SubscriptionClosedReason::Error(err) => SubscriptionEnd::Error(err),
+		}
+	}
+}
+
 impl<Notif> Drop for WsSubscription<Notif> {
 	fn drop(&mut self) {
+		// Closing the channel lets the background task know that this subscription is gone, as the
+		// ID might belong to another subscription once reconnected.
+		self.notifs_rx.close();
+		// The subscription might have been established again under a new ID.
+		while let Ok(message) = self.notifs_rx.try_recv() {
+			if let SubscriptionMessage::Resubscribed(id) = message {
+				self.id = id;
+			}
+		}
 		// We can't actually guarantee that this goes through. If the background task is busy, then
 		// the channel's buffer will be full, and our unsubscription request will never make it.
 		// However, when a notification arrives, the background task will realize that the channel
@@ -275,17 +591,129 @@ impl<Notif> Drop for WsSubscription<Notif> {
 	}
 }
 
+/// Why [`run_connection`] has returned.
+enum ConnectionEnd {
+	/// The client must be terminated.
+	Terminate,
+	/// The connection to the server has been lost.
+	Lost,
+}
+
 /// Function being run in the background that processes messages from the frontend.
 async fn background_task(
 	mut sender: jsonrpc_transport::Sender,
-	receiver: jsonrpc_transport::Receiver,
+	mut receiver: jsonrpc_transport::Receiver,
 	mut frontend: mpsc::Receiver<FrontToBack>,
 	config: WsConfig,
+	remote_addr: String,
+	tls_config: Option<Arc<rustls::ClientConfig>>,
 ) {
 	let mut manager = RequestManager::new();
 
+	loop {
+		if let ConnectionEnd::Terminate =
+			run_connection(&mut sender, receiver, &mut frontend, &mut manager, &config).await
+		{
+			return;
+		}
+		let reconnect_config = match config.reconnect {
+			Some(reconnect_config) => reconnect_config,
+			None => return,
+		};
+
+		let mut subscriptions = manager.reset();
+		loop {
+			log::debug!("[backend]: connection lost; reconnecting to {}", remote_addr);
+			let connection = reconnect(&remote_addr, &tls_config, reconnect_config).await;
+			let (new_sender, new_receiver) = match connection {
+				Some(connection) => connection,
+				None => {
+					log::error!("[backend]: failed to reconnect; terminate client");
+					return;
+				}
+			};
+			sender = new_sender;
+			receiver = new_receiver;
+			match resubscribe(&mut sender, &mut manager, subscriptions).await {
+				Ok(()) => break,
+				Err(remaining) => {
+					subscriptions = remaining;
+					subscriptions.extend(manager.reset());
+				}
+			}
+		}
+	}
+}
+
+/// Connects to the server again, according to `config`.
+///
+/// Returns `None` if every attempt has failed.
+async fn reconnect(
+	remote_addr: &str,
+	tls_config: &Option<Arc<rustls::ClientConfig>>,
+	config: ReconnectConfig,
+) -> Option<(jsonrpc_transport::Sender, jsonrpc_transport::Receiver)> {
+	let mut delay = config.initial_delay;
+	let mut attempts = 0;
+	loop {
+		if matches!(config.max_attempts, Some(max_attempts) if attempts >= max_attempts) {
+			return None;
+		}
+		async_std::task::sleep(delay).await;
+		attempts += 1;
+		match jsonrpc_transport::websocket_connection(remote_addr, tls_config.clone()).await {
+			Ok(connection) => return Some(connection),
+			Err(e) => log::debug!("[backend]: reconnection attempt {} failed: {:?}", attempts, e),
+		}
+		delay = std::cmp::min(delay.saturating_mul(2), config.max_delay);
+	}
+}
+
+/// Sends the requests of `subscriptions` on a new connection, in order to establish them again.
+/// The subscriptions dropped by the frontend are discarded.
+///
+/// Returns the subscriptions that couldn't be sent if the new connection has failed.
+async fn resubscribe(
+	sender: &mut jsonrpc_transport::Sender,
+	manager: &mut RequestManager,
+	subscriptions: Vec<(mpsc::Sender<SubscriptionMessage>, SubscribeRequest)>,
+) -> Result<(), Vec<(mpsc::Sender<SubscriptionMessage>, SubscribeRequest)>> {
+	let mut subscriptions = subscriptions.into_iter();
+	while let Some((sink, request)) = subscriptions.next() {
+		if sink.is_closed() {
+			continue;
+		}
+		log::trace!("[backend]: resubscribe: {:?}", request.subscribe_method);
+		match sender.start_subscription(request.subscribe_method.clone(), request.params.clone()).await {
+			Ok(id) => {
+				if manager.insert_pending_resubscription(id, sink, request).is_err() {
+					log::error!("[backend]: duplicate request ID {}; dropping subscription", id);
+				}
+			}
+			Err(err) => {
+				log::warn!("[backend]: resubscription failed: {:?}", err);
+				let mut remaining = vec![(sink, request)];
+				remaining.extend(subscriptions);
+				return Err(remaining);
+			}
+		}
+	}
+	Ok(())
+}
+
+/// Processes the messages from the frontend and from the server, until the connection is lost or
+/// the client must be terminated.
+async fn run_connection(
+	sender: &mut jsonrpc_transport::Sender,
+	receiver: jsonrpc_transport::Receiver,
+	frontend: &mut mpsc::Receiver<FrontToBack>,
+	manager: &mut RequestManager,
+	config: &WsConfig,
+) -> ConnectionEnd {
+	let mut keep_alive = KeepAlive::new(config.keep_alive, Instant::now());
+
 	let backend_event = futures::stream::unfold(receiver, |mut receiver| async {
-		let res = receiver.next_response().await;
+		let res = receiver.next_incoming().await;
 		Some((res, receiver))
 	});
 
@@ -294,26 +722,47 @@ async fn background_task(
 	loop {
 		let next_frontend = frontend.next();
 		let next_backend = backend_event.next();
-		futures::pin_mut!(next_frontend, next_backend);
+		let keep_alive_deadline = sleep_until(keep_alive.deadline()).fuse();
+		futures::pin_mut!(next_frontend, next_backend, keep_alive_deadline);
 
 		futures::select! {
+			_ = keep_alive_deadline => match keep_alive.poll(Instant::now()) {
+				Some(KeepAliveEvent::SendPing) => {
+					if let Err(e) = sender.send_ping().await {
+						log::error!("Error: {:?} connection lost", e);
+						return ConnectionEnd::Lost;
+					}
+				}
+				Some(KeepAliveEvent::PongTimeout) => {
+					log::error!("[backend]: no pong received in time; connection lost");
+					return ConnectionEnd::Lost;
+				}
+				Some(KeepAliveEvent::IdleTimeout) => {
+					log::debug!("[backend]: connection idle; terminate client");
+					let _ = sender.close().await;
+					return ConnectionEnd::Terminate;
+				}
+				None => (),
+			},
 			event = next_frontend => match event {
 				// User dropped the sender side of the channel.
 				None => {
 					log::trace!("[backend]: frontend channel dropped; terminate client");
-					break
+					return ConnectionEnd::Terminate;
 				}
 				// User called `notification` on the front-end
 				Some(FrontToBack::Notification { method, params }) => {
 					log::trace!("[backend]: client prepares to send notification");
+					keep_alive.on_message(Instant::now());
 					let _ = sender.send_notification(method, params).await;
 				}
 				// User called `request` on the front-end
-				Some(FrontToBack::StartRequest { method, params, send_back }) => {
+				Some(FrontToBack::StartRequest { method, params, send_back, cancel_key }) => {
 					log::trace!("[backend]: client prepares to send request={:?}", method);
+					keep_alive.on_message(Instant::now());
 					match sender.start_request(method, params).await {
 						Ok(id) => {
-							if let Err(send_back) = manager.insert_pending_call(id, send_back) {
+							if let Err(send_back) = manager.insert_pending_call(id, cancel_key, send_back) {
 								let _ = send_back.send(Err(Error::DuplicateRequestId));
 							}
 						}
@@ -323,6 +772,26 @@ async fn background_task(
 						}
 					}
 				}
+				// User called `batch_request` on the front-end
+				Some(FrontToBack::Batch { batch, send_back, cancel_key }) => {
+					log::trace!("[backend]: client prepares to send batch of {} entries", batch.len());
+					keep_alive.on_message(Instant::now());
+					match sender.start_batch(batch).await {
+						// The server doesn't answer a batch made only of notifications.
+						Ok(ids) if ids.is_empty() => {
+							let _ = send_back.send(Ok(Vec::new()));
+						}
+						Ok(ids) => {
+							if let Err(send_back) = manager.insert_pending_batch(ids, cancel_key, send_back) {
+								let _ = send_back.send(Err(Error::DuplicateRequestId));
+							}
+						}
+						Err(err) => {
+							log::warn!("[backend]: client batch failed: {:?}", err);
+							let _ = send_back.send(Err(Error::TransportError(Box::new(err))));
+						}
+					}
+				}
 				// User called `subscribe` on the front-end.
 				Some(FrontToBack::Subscribe { subscribe_method, unsubscribe_method, params, send_back }) => {
 					log::trace!(
@@ -330,9 +799,11 @@ async fn background_task(
 						subscribe_method,
 						unsubscribe_method
 					);
-					match sender.start_subscription(subscribe_method, params).await {
+					keep_alive.on_message(Instant::now());
+					match sender.start_subscription(subscribe_method.clone(), params.clone()).await {
 						Ok(id) => {
-							if let Err(send_back) = manager.insert_pending_subscription(id, send_back, unsubscribe_method) {
+							let request = SubscribeRequest { subscribe_method, params, unsubscribe_method };
+							if let Err(send_back) = manager.insert_pending_subscription(id, send_back, request) {
 								let _ = send_back.send(Err(Error::DuplicateRequestId));
 							}
 						}
@@ -342,16 +813,25 @@ async fn background_task(
 						}
 					}
 				}
+				// User gave up on a request or a batch.
+				Some(FrontToBack::RequestCancelled(cancel_key)) => {
+					let removed = manager.remove_cancelled(cancel_key);
+					log::trace!("[backend]: forgot {} cancelled request(s)", removed);
+				}
 				// User dropped a subscription.
 				Some(FrontToBack::SubscriptionClosed(sub_id)) => {
 					log::trace!("Closing in subscription: {:?}", sub_id);
 					// NOTE: The subscription may have been closed earlier if
 					// the channel was full or disconnected.
 					if let Some(request_id) = manager.get_request_id_by_subscription_id(&sub_id) {
-						if let Some((_sink, unsubscribe_method)) = manager.remove_subscription(request_id, sub_id.clone()) {
+						// After reconnecting, the ID might belong to another subscription.
+						if !matches!(manager.as_subscription_mut(&request_id), Some(sink) if sink.is_closed()) {
+							continue;
+						}
+						if let Some((_sink, request)) = manager.remove_subscription(request_id, sub_id.clone()) {
 							if let Ok(json_sub_id) = jsonrpc::to_value(sub_id) {
 								let params = jsonrpc::Params::Array(vec![json_sub_id]);
-								let _ = sender.start_request(unsubscribe_method, params).await;
+								let _ = sender.start_request(request.unsubscribe_method, params).await;
 							}
 						}
 					}
@@ -359,71 +839,125 @@ async fn background_task(
 			},
 			event = next_backend => match event {
 				None => {
-					log::trace!("[backend]: backend channel dropped; terminate client");
-					break;
+					log::trace!("[backend]: backend channel dropped; connection lost");
+					return ConnectionEnd::Lost;
 				}
-				Some(Ok(jsonrpc::Response::Single(response))) => {
-					match process_response(&mut manager, response, config.subscription_channel_capacity) {
-						Ok(Some((unsubscribe, params))) => {
-							if let Err(e) = sender.start_request(unsubscribe, params).await {
-								log::error!("Failed to send unsubscription response: {:?}", e);
+				Some(Ok(Incoming::Pong)) => keep_alive.on_pong(),
+				Some(Ok(Incoming::Response(response))) => {
+					keep_alive.on_message(Instant::now());
+					let next_request_id = sender.next_request_id();
+					match response {
+						jsonrpc::Response::Single(response) => {
+							match process_response(manager, response, config.subscription_channel_capacity, next_request_id) {
+								Ok(Some((unsubscribe, params))) => {
+									if let Err(e) = sender.start_request(unsubscribe, params).await {
+										log::error!("Failed to send unsubscription response: {:?}", e);
+									}
+								}
+								Ok(None) => (),
+								Err(e) => {
+									log::error!("Error: {:?} terminating client", e);
+									return ConnectionEnd::Terminate;
+								}
 							}
 						}
-						Ok(None) => (),
-						Err(e) => {
-							log::error!("Error: {:?} terminating client", e);
-							return;
-						}
-					}
-				}
-				Some(Ok(jsonrpc::Response::Batch(responses))) => {
-					// if any request fails, throw away entire batch.
-					for response in responses {
-						match process_response(&mut manager, response, config.subscription_channel_capacity) {
-							Ok(Some((unsubscribe, params))) => {
-								if let Err(e) = sender.start_request(unsubscribe, params).await {
-									log::error!("Failed to send unsubscription response: {:?}", e);
+						jsonrpc::Response::Batch(responses) => {
+							// The batches whose method calls are answered by this response. A
+							// server answers all the method calls of a batch at once.
+							let mut batch_ids: Vec<_> = responses
+								.iter()
+								.filter_map(|response| response.id().as_number())
+								.filter_map(|id| manager.get_batch_id(id))
+								.collect();
+							batch_ids.sort_unstable();
+							batch_ids.dedup();
+							// A response that can't be handled doesn't affect the other ones.
+							for response in responses {
+								match process_response(manager, response, config.subscription_channel_capacity, next_request_id) {
+									Ok(Some((unsubscribe, params))) => {
+										if let Err(e) = sender.start_request(unsubscribe, params).await {
+											log::error!("Failed to send unsubscription response: {:?}", e);
+										}
+									}
+									Ok(None) => (),
+									Err(e) => log::error!("Ignoring response of batch: {:?}", e),
 								}
 							}
-							Ok(None) => (),
-							Err(e) => {
-								log::error!("Error: {:?} terminating client", e);
-								return;
+							// Method calls that the server didn't answer are reported as failed.
+							for batch_id in batch_ids {
+								if let Some((send_back, results)) = manager.complete_pending_batch(batch_id) {
+									let _ = send_back.send(Ok(results));
+								}
 							}
 						}
-					}
-				}
-				Some(Ok(jsonrpc::Response::Notif(notif))) => {
-					let sub_id = notif.params.subscription;
-					let request_id = match manager.get_request_id_by_subscription_id(&sub_id) {
-						Some(r) => r,
-						None => {
-							log::error!("Subscription ID: {:?} not found", sub_id);
-							continue;
-						}
-					};
+						jsonrpc::Response::Notif(notif) => {
+							let sub_id = notif.params.subscription;
+							let request_id = match manager.get_request_id_by_subscription_id(&sub_id) {
+								Some(r) => r,
+								None => {
+									log::error!("Subscription ID: {:?} not found", sub_id);
+									continue;
+								}
+							};
 
-					match manager.as_subscription_mut(&request_id) {
-						Some(send_back_sink) => {
-							if let Err(e) = send_back_sink.try_send(notif.params.result) {
-								log::error!("Dropping subscription {:?} error: {:?}", sub_id, e);
-								manager.remove_subscription(request_id, sub_id).expect("subscription is active; checked above");
+							match manager.as_subscription_mut(&request_id) {
+								Some(send_back_sink) => {
+									if let Err(e) = send_back_sink.try_send(SubscriptionMessage::Notification(notif.params.result)) {
+										log::error!("Dropping subscription {:?} error: {:?}", sub_id, e);
+										let (sink, _) = manager.remove_subscription(request_id, sub_id).expect("subscription is active; checked above");
+										if e.is_full() {
+											end_subscription(sink, SubscriptionEnd::Lagged);
+										}
+									}
+								}
+								None => {
+									log::error!("Subscription ID: {:?} not an active subscription", sub_id);
+								},
+							}
+						}
+						// The server closed a subscription; the subscription stream ends with its reason.
+						jsonrpc::Response::SubscriptionClosed(closed) => {
+							let sub_id = closed.params.subscription;
+							log::debug!("[backend]: server closed subscription {:?}: {:?}", sub_id, closed.params.reason);
+							// The subscription is established again once reconnected to the restarted server.
+							let shutting_down = jsonrpc::SubscriptionClosedReason::Error(jsonrpc::Error::server_shutting_down());
+							if config.reconnect.is_some() && closed.params.reason == shutting_down {
+								continue;
+							}
+							match manager.get_request_id_by_subscription_id(&sub_id) {
+								Some(request_id) => {
+									let (sink, _) = manager.remove_subscription(request_id, sub_id).expect("subscription is active; checked above");
+									end_subscription(sink, closed.params.reason.into());
+								}
+								None => log::error!("Subscription ID: {:?} not found", sub_id),
 							}
 						}
-						None => {
-							log::error!("Subscription ID: {:?} not an active subscription", sub_id);
-						},
 					}
 				}
 				Some(Err(e)) => {
-					log::error!("Error: {:?} terminating client", e);
-					return;
+					log::error!("Error: {:?} connection lost", e);
+					return ConnectionEnd::Lost;
 				}
 			},
 		}
 	}
 }
 
+/// Resolves once `deadline` is reached, or never if `deadline` is `None`.
+async fn sleep_until(deadline: Option<Instant>) {
+	match deadline {
+		Some(deadline) => async_std::task::sleep(deadline.saturating_duration_since(Instant::now())).await,
+		None => future::pending().await,
+	}
+}
+
+/// Sends the reason why a subscription has ended as its last message.
+fn end_subscription(sink: mpsc::Sender<SubscriptionMessage>, end: SubscriptionEnd) {
+	// Each sender has a guaranteed slot in the channel, so a new sender can deliver this message
+	// even if the channel is full.
+	let _ = sink.clone().try_send(SubscriptionMessage::End(end));
+}
+
 /// Process a response from the server.
 ///
 /// Returns `Ok(_)` if the response was successful or if the error could be handled.
@@ -432,6 +966,7 @@ fn process_response(
 	manager: &mut RequestManager,
 	response: jsonrpc::Output,
 	subscription_capacity: usize,
+	next_request_id: u64,
 ) -> Result<Option<(String, jsonrpc::Params)>, Error> {
 	let response_id = *response.id().as_number().ok_or(Error::InvalidRequestId)?;
 
@@ -439,55 +974,88 @@ fn process_response(
 		RequestStatus::PendingMethodCall => {
 			let send_back_oneshot = manager.complete_pending_call(response_id).ok_or(Error::InvalidRequestId)?;
 			let response = response.try_into().map_err(Error::Request);
-			match send_back_oneshot.send(response) {
-				Err(Err(e)) => Err(e),
-				Err(Ok(_)) => Err(Error::Custom("Frontend channel closed".into())),
-				Ok(_) => Ok(None),
-			}
+			// The caller might have given up on the request, which isn't an error.
+			let _ = send_back_oneshot.send(response);
+			Ok(None)
 		}
 		RequestStatus::PendingSubscription => {
-			let (send_back_oneshot, unsubscribe_method) =
+			let (send_back_oneshot, request) =
 				manager.complete_pending_subscription(response_id).ok_or(Error::InvalidRequestId)?;
 			let json_sub_id: JsonValue = match response.try_into() {
 				Ok(response) => response,
 				Err(e) => {
-					return match send_back_oneshot.send(Err(Error::Request(e))) {
-						Err(Err(e)) => Err(e),
-						Err(Ok(_)) => unreachable!("Error sent above; qed"),
-						_ => Ok(None),
-					};
+					let _ = send_back_oneshot.send(Err(Error::Request(e)));
+					return Ok(None);
 				}
 			};
 
 			let sub_id: SubscriptionId = match jsonrpc::from_value(json_sub_id.clone()) {
 				Ok(sub_id) => sub_id,
 				Err(_) => {
-					return match send_back_oneshot.send(Err(Error::InvalidSubscriptionId)) {
-						Err(Err(e)) => Err(e),
-						Err(Ok(_)) => unreachable!("Error sent above; qed"),
-						_ => Ok(None),
-					}
+					let _ = send_back_oneshot.send(Err(Error::InvalidSubscriptionId));
+					return Ok(None);
 				}
 			};
 
 			let (subscribe_tx, subscribe_rx) = mpsc::channel(subscription_capacity);
-			if manager.insert_subscription(response_id, sub_id.clone(), subscribe_tx, unsubscribe_method).is_ok() {
+			if manager.insert_subscription(response_id, sub_id.clone(), subscribe_tx, request).is_ok() {
 				match send_back_oneshot.send(Ok((subscribe_rx, sub_id.clone()))) {
 					Ok(_) => Ok(None),
+					// The caller has given up on the subscription.
 					Err(_) => {
-						let (_, unsubscribe_method) =
+						let (_, request) =
 							manager.remove_subscription(response_id, sub_id).expect("Subscription inserted above; qed");
 						let params = jsonrpc::Params::Array(vec![json_sub_id]);
-						Ok(Some((unsubscribe_method, params)))
+						Ok(Some((request.unsubscribe_method, params)))
 					}
 				}
 			} else {
-				match send_back_oneshot.send(Err(Error::InvalidSubscriptionId)) {
-					Err(Err(e)) => Err(e),
-					Err(Ok(_)) => unreachable!("Error sent above; qed"),
-					_ => Ok(None),
+				let _ = send_back_oneshot.send(Err(Error::InvalidSubscriptionId));
+				Ok(None)
+			}
+		}
+		RequestStatus::PendingResubscription => {
+			let (sink, request) =
+				manager.complete_pending_resubscription(response_id).ok_or(Error::InvalidRequestId)?;
+			let json_sub_id: JsonValue = match response.try_into() {
+				Ok(response) => response,
+				Err(e) => {
+					end_subscription(sink, SubscriptionEnd::Error(e));
+					return Ok(None);
 				}
+			};
+			let sub_id: SubscriptionId = match jsonrpc::from_value(json_sub_id.clone()) {
+				Ok(sub_id) => sub_id,
+				Err(_) => {
+					log::error!("Invalid subscription ID: {:?}; dropping subscription", json_sub_id);
+					return Ok(None);
+				}
+			};
+
+			// The frontend has dropped the subscription in the meantime.
+			if sink.is_closed() {
+				let params = jsonrpc::Params::Array(vec![json_sub_id]);
+				return Ok(Some((request.unsubscribe_method, params)));
+			}
+			// Each sender has a guaranteed slot in the channel, see `end_subscription`.
+			let _ = sink.clone().try_send(SubscriptionMessage::Resubscribed(sub_id.clone()));
+			if manager.insert_subscription(response_id, sub_id, sink, request).is_err() {
+				log::error!("Duplicate subscription ID: {:?}; dropping subscription", json_sub_id);
+			}
+			Ok(None)
+		}
+		RequestStatus::PendingBatchCall => {
+			let response = response.try_into().map_err(Error::Request);
+			if let Some((send_back, results)) = manager.complete_pending_batch_call(response_id, response) {
+				// The caller might have given up on the batch, which isn't an error.
+				let _ = send_back.send(Ok(results));
 			}
+			Ok(None)
+		}
+		// The request has been sent but isn't pending anymore, so the caller has given up on it.
+		RequestStatus::Invalid if response_id < next_request_id => {
+			log::debug!("[backend]: ignoring response to cancelled request ID {}", response_id);
+			Ok(None)
 		}
 		RequestStatus::Subscription | RequestStatus::Invalid => Err(Error::InvalidRequestId),
 	}
@@ -2,12 +2,16 @@
 //!
 //! Wraps the underlying WebSocket transport with specific JSONRPC details.
 
-use crate::transport::{self, WsConnectError, WsNewDnsError};
-use jsonrpsee_types::jsonrpc;
+use crate::transport::{self, Incoming, WsConnectError, WsNewDnsError};
+use jsonrpsee_types::{client::BatchEntry, jsonrpc};
+use std::sync::Arc;
 
 /// Creates a new JSONRPC WebSocket connection, represented as a Sender and Receiver pair.
-pub async fn websocket_connection(target: impl AsRef<str>) -> Result<(Sender, Receiver), WsNewDnsError> {
-	let (sender, receiver) = transport::websocket_connection(target.as_ref()).await?;
+pub async fn websocket_connection(
+	target: impl AsRef<str>,
+	tls_config: Option<Arc<rustls::ClientConfig>>,
+) -> Result<(Sender, Receiver), WsNewDnsError> {
+	let (sender, receiver) = transport::websocket_connection(target.as_ref(), tls_config).await?;
 	Ok((Sender::new(sender), Receiver::new(receiver)))
 }
 
@@ -87,6 +91,48 @@ impl Sender {
 	) -> Result<u64, WsConnectError> {
 		self.start_impl(method, params).await
 	}
+
+	/// Sends a batch of method calls and notifications to the server but it doesn't wait for the
+	/// responses. Instead, you have keep the request IDs and use the [`Receiver`] to get the
+	/// responses.
+	///
+	/// Returns `Ok(request_ids)`, the request IDs of the method calls in the order of the batch,
+	/// if the batch was successfully sent otherwise `Err(_)`.
+	pub async fn start_batch(&mut self, batch: Vec<BatchEntry>) -> Result<Vec<u64>, WsConnectError> {
+		let mut ids = Vec::new();
+		let calls = batch
+			.into_iter()
+			.map(|entry| {
+				if let BatchEntry::Notification { .. } = entry {
+					return entry.into_call(0);
+				}
+				let id = self.request_id;
+				self.request_id = id.wrapping_add(1);
+				ids.push(id);
+				entry.into_call(id)
+			})
+			.collect();
+
+		self.transport.send_request(jsonrpc::Request::Batch(calls)).await?;
+
+		Ok(ids)
+	}
+
+	/// Returns the request ID of the next method call. The method calls sent so far have lower
+	/// IDs, as long as the IDs haven't wrapped around.
+	pub fn next_request_id(&self) -> u64 {
+		self.request_id
+	}
+
+	/// Sends a ping to the server.
+	pub async fn send_ping(&mut self) -> Result<(), WsConnectError> {
+		self.transport.send_ping().await
+	}
+
+	/// Closes the connection.
+	pub async fn close(&mut self) -> Result<(), WsConnectError> {
+		self.transport.close().await
+	}
 }
 
 /// JSONRPC WebSocket receiver.
@@ -104,4 +150,9 @@ impl Receiver {
 	pub async fn next_response(&mut self) -> Result<jsonrpc::Response, WsConnectError> {
 		self.transport.next_response().await
 	}
+
+	/// Reads the next response or pong.
+	pub async fn next_incoming(&mut self) -> Result<Incoming, WsConnectError> {
+		self.transport.next_incoming().await
+	}
 }
@@ -12,4 +12,10 @@ pub mod transport;
 #[cfg(test)]
 mod tests;
 
-pub use client::{WsClient, WsConfig, WsSubscription};
+pub use client::{
+	ReconnectConfig, SubscriptionClient, SubscriptionEnd, SubscriptionEvent, WsClient, WsConfig, WsSubscription,
+};
+pub use jsonrpsee_types::{
+	client::{BatchEntry, Client},
+	ws::KeepAliveConfig,
+};
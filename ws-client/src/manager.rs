@@ -5,18 +5,46 @@
 //!     (the specs allow number, string or null but this crate only supports numbers)
 //!     * Subscription ID - unique ID generated by server
 
+use crate::client::SubscriptionMessage;
 use fnv::FnvHashMap;
 use futures::channel::{mpsc, oneshot};
 use jsonrpsee_types::{
 	error::Error,
-	jsonrpc::{JsonValue, SubscriptionId},
+	jsonrpc::{self, JsonValue, SubscriptionId},
 };
 use std::collections::hash_map::{Entry, HashMap};
 
 enum Kind {
-	PendingMethodCall(PendingCallOneshot),
-	PendingSubscription((PendingSubscriptionOneshot, UnsubscribeMethod)),
-	Subscription((SubscriptionSink, UnsubscribeMethod)),
+	PendingMethodCall((PendingCallOneshot, CancelKey)),
+	PendingSubscription((PendingSubscriptionOneshot, SubscribeRequest)),
+	Subscription((SubscriptionSink, SubscribeRequest)),
+	/// Subscription that is being established again after reconnecting.
+	PendingResubscription((SubscriptionSink, SubscribeRequest)),
+	/// Method call of the batch identified by the request ID of its first method call.
+	PendingBatchCall(RequestId),
+}
+
+/// Subscription request, kept in order to unsubscribe or to subscribe again after reconnecting.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubscribeRequest {
+	/// Method used to subscribe.
+	pub subscribe_method: String,
+	/// Parameters of the subscription.
+	pub params: jsonrpc::Params,
+	/// Method used to unsubscribe.
+	pub unsubscribe_method: String,
+}
+
+/// Batch whose method calls are waiting for a response.
+struct PendingBatch {
+	/// Request IDs of the method calls, in the order of the batch.
+	ids: Vec<RequestId>,
+	/// Results of the method calls that have been answered.
+	results: FnvHashMap<RequestId, Result<JsonValue, Error>>,
+	/// Where to send back the results once every method call has been answered.
+	send_back: PendingBatchOneshot,
+	/// Key with which the frontend gives up on the batch.
+	cancel_key: CancelKey,
 }
 
 /// Indicates the status of a given request/response.
@@ -27,15 +55,22 @@ pub enum RequestStatus {
 	PendingSubscription,
 	/// An active subscription.
 	Subscription,
+	/// The subscription is being established again after reconnecting.
+	PendingResubscription,
+	/// The method call is part of a batch and is waiting for a response.
+	PendingBatchCall,
 	/// Invalid request ID.
 	Invalid,
 }
 
 type PendingCallOneshot = oneshot::Sender<Result<JsonValue, Error>>;
-type PendingSubscriptionOneshot = oneshot::Sender<Result<(mpsc::Receiver<JsonValue>, SubscriptionId), Error>>;
-type SubscriptionSink = mpsc::Sender<JsonValue>;
-type UnsubscribeMethod = String;
+type PendingSubscriptionOneshot = oneshot::Sender<Result<(mpsc::Receiver<SubscriptionMessage>, SubscriptionId), Error>>;
+type SubscriptionSink = mpsc::Sender<SubscriptionMessage>;
+type PendingBatchOneshot = oneshot::Sender<Result<Vec<Result<JsonValue, Error>>, Error>>;
 type RequestId = u64;
+/// Key with which the frontend gives up on a method call or a batch, chosen by the frontend
+/// before the request ID is known.
+pub type CancelKey = u64;
 
 /// Manages and monitors JSONRPC v2 method calls and subscriptions.
 pub struct RequestManager {
@@ -44,19 +79,128 @@ pub struct RequestManager {
 	requests: FnvHashMap<RequestId, Kind>,
 	/// Reverse lookup, to find a request ID in constant time by `subscription ID` instead of looking through all requests.
 	subscriptions: HashMap<SubscriptionId, RequestId>,
+	/// Batches that are waiting for responses, by request ID of their first method call.
+	batches: FnvHashMap<RequestId, PendingBatch>,
+	/// Request ID of each pending method call, and of the first method call of each pending
+	/// batch, by the key with which the frontend can give up on it.
+	cancel_keys: FnvHashMap<CancelKey, RequestId>,
 }
 
 impl RequestManager {
 	pub fn new() -> Self {
-		Self { requests: FnvHashMap::default(), subscriptions: HashMap::new() }
+		Self {
+			requests: FnvHashMap::default(),
+			subscriptions: HashMap::new(),
+			batches: FnvHashMap::default(),
+			cancel_keys: FnvHashMap::default(),
+		}
+	}
+
+	/// Tries to insert a new pending batch made of the method calls with the request IDs `ids`,
+	/// that the frontend gives up on with `cancel_key`.
+	///
+	/// Returns `Ok` if the pending batch was successfully inserted otherwise `Err`.
+	pub fn insert_pending_batch(
+		&mut self,
+		ids: Vec<RequestId>,
+		cancel_key: CancelKey,
+		send_back: PendingBatchOneshot,
+	) -> Result<(), PendingBatchOneshot> {
+		let batch_id = match ids.first() {
+			Some(id) => *id,
+			None => return Err(send_back),
+		};
+		let mut unique_ids = ids.clone();
+		unique_ids.sort_unstable();
+		unique_ids.dedup();
+		if unique_ids.len() != ids.len()
+			|| ids.iter().any(|id| self.requests.contains_key(id))
+			|| self.cancel_keys.contains_key(&cancel_key)
+		{
+			return Err(send_back);
+		}
+
+		for id in &ids {
+			self.requests.insert(*id, Kind::PendingBatchCall(batch_id));
+		}
+		self.cancel_keys.insert(cancel_key, batch_id);
+		self.batches.insert(batch_id, PendingBatch { ids, results: FnvHashMap::default(), send_back, cancel_key });
+		Ok(())
+	}
+
+	/// Tries to complete a method call of a pending batch.
+	///
+	/// Returns `Some` with the results of the batch, in order, if every method call of the batch
+	/// has now been answered, otherwise `None`.
+	pub fn complete_pending_batch_call(
+		&mut self,
+		request_id: RequestId,
+		result: Result<JsonValue, Error>,
+	) -> Option<(PendingBatchOneshot, Vec<Result<JsonValue, Error>>)> {
+		let batch_id = match self.requests.get(&request_id) {
+			Some(Kind::PendingBatchCall(batch_id)) => *batch_id,
+			_ => return None,
+		};
+		self.requests.remove(&request_id);
+		let batch = self.batches.get_mut(&batch_id).expect("batch calls always refer to a batch; qed");
+		batch.results.insert(request_id, result);
+		if batch.results.len() == batch.ids.len() {
+			self.complete_pending_batch(batch_id)
+		} else {
+			None
+		}
+	}
+
+	/// Completes the pending batch identified by `batch_id`, see
+	/// [`get_batch_id`](RequestManager::get_batch_id). The method calls that haven't been
+	/// answered yet are reported as failed.
+	///
+	/// Returns `Some` with the results of the batch, in order, if the batch was pending
+	/// otherwise `None`.
+	pub fn complete_pending_batch(
+		&mut self,
+		batch_id: RequestId,
+	) -> Option<(PendingBatchOneshot, Vec<Result<JsonValue, Error>>)> {
+		let PendingBatch { ids, mut results, send_back, cancel_key } = self.batches.remove(&batch_id)?;
+		self.cancel_keys.remove(&cancel_key);
+		let results = ids
+			.into_iter()
+			.map(|id| {
+				results.remove(&id).unwrap_or_else(|| {
+					self.requests.remove(&id);
+					Err(Error::Custom(format!("Server didn't answer request ID: {}", id)))
+				})
+			})
+			.collect();
+		Some((send_back, results))
+	}
+
+	/// Returns the request ID that identifies the pending batch that contains the method call
+	/// `request_id`.
+	///
+	/// Returns `Some` if `request_id` is a method call of a pending batch otherwise `None`.
+	pub fn get_batch_id(&self, request_id: &RequestId) -> Option<RequestId> {
+		match self.requests.get(request_id) {
+			Some(Kind::PendingBatchCall(batch_id)) => Some(*batch_id),
+			_ => None,
+		}
 	}
 
-	/// Tries to insert a new pending call.
+	/// Tries to insert a new pending call, that the frontend gives up on with `cancel_key`.
 	///
 	/// Returns `Ok` if the pending request was successfully inserted otherwise `Err`.
-	pub fn insert_pending_call(&mut self, id: u64, send_back: PendingCallOneshot) -> Result<(), PendingCallOneshot> {
+	pub fn insert_pending_call(
+		&mut self,
+		id: u64,
+		cancel_key: CancelKey,
+		send_back: PendingCallOneshot,
+	) -> Result<(), PendingCallOneshot> {
+		if self.cancel_keys.contains_key(&cancel_key) {
+			return Err(send_back);
+		}
 		if let Entry::Vacant(v) = self.requests.entry(id) {
-			v.insert(Kind::PendingMethodCall(send_back));
+			v.insert(Kind::PendingMethodCall((send_back, cancel_key)));
+			self.cancel_keys.insert(cancel_key, id);
 			Ok(())
 		} else {
 			Err(send_back)
@@ -70,10 +214,10 @@ impl RequestManager {
 		&mut self,
 		id: RequestId,
 		send_back: PendingSubscriptionOneshot,
-		unsubscribe_method: UnsubscribeMethod,
+		request: SubscribeRequest,
 	) -> Result<(), PendingSubscriptionOneshot> {
 		if let Entry::Vacant(v) = self.requests.entry(id) {
-			v.insert(Kind::PendingSubscription((send_back, unsubscribe_method)));
+			v.insert(Kind::PendingSubscription((send_back, request)));
 			Ok(())
 		} else {
 			Err(send_back)
@@ -88,12 +232,12 @@ impl RequestManager {
 		request_id: RequestId,
 		subscription_id: SubscriptionId,
 		send_back: SubscriptionSink,
-		unsubscribe_method: String,
+		request: SubscribeRequest,
 	) -> Result<(), SubscriptionSink> {
-		if let (Entry::Vacant(request), Entry::Vacant(subscription)) =
+		if let (Entry::Vacant(entry), Entry::Vacant(subscription)) =
 			(self.requests.entry(request_id), self.subscriptions.entry(subscription_id))
 		{
-			request.insert(Kind::Subscription((send_back, unsubscribe_method)));
+			entry.insert(Kind::Subscription((send_back, request)));
 			subscription.insert(request_id);
 			Ok(())
 		} else {
@@ -107,7 +251,7 @@ impl RequestManager {
 	pub fn complete_pending_subscription(
 		&mut self,
 		request_id: RequestId,
-	) -> Option<(PendingSubscriptionOneshot, UnsubscribeMethod)> {
+	) -> Option<(PendingSubscriptionOneshot, SubscribeRequest)> {
 		match self.requests.entry(request_id) {
 			Entry::Occupied(request) if matches!(request.get(), Kind::PendingSubscription(_)) => {
 				let (_req_id, kind) = request.remove_entry();
@@ -121,6 +265,72 @@ impl RequestManager {
 		}
 	}
 
+	/// Tries to insert a subscription that is being established again after reconnecting.
+	///
+	/// Returns `Ok` if the pending resubscription was successfully inserted otherwise `Err`.
+	pub fn insert_pending_resubscription(
+		&mut self,
+		id: RequestId,
+		sink: SubscriptionSink,
+		request: SubscribeRequest,
+	) -> Result<(), (SubscriptionSink, SubscribeRequest)> {
+		if let Entry::Vacant(v) = self.requests.entry(id) {
+			v.insert(Kind::PendingResubscription((sink, request)));
+			Ok(())
+		} else {
+			Err((sink, request))
+		}
+	}
+
+	/// Tries to complete a pending resubscription.
+	///
+	/// Returns `Some` if the resubscription was completed otherwise `None`.
+	pub fn complete_pending_resubscription(
+		&mut self,
+		request_id: RequestId,
+	) -> Option<(SubscriptionSink, SubscribeRequest)> {
+		match self.requests.entry(request_id) {
+			Entry::Occupied(request) if matches!(request.get(), Kind::PendingResubscription(_)) => {
+				let (_req_id, kind) = request.remove_entry();
+				if let Kind::PendingResubscription(resubscription) = kind {
+					Some(resubscription)
+				} else {
+					unreachable!("Pending resubscription is Pending resubscription checked above; qed");
+				}
+			}
+			_ => None,
+		}
+	}
+
+	/// Fails every pending method call, subscription and batch with [`Error::ConnectionLost`],
+	/// and removes every subscription.
+	///
+	/// Returns the subscriptions, active or being established again, in order to establish them
+	/// again on a new connection.
+	pub fn reset(&mut self) -> Vec<(SubscriptionSink, SubscribeRequest)> {
+		self.subscriptions.clear();
+		self.cancel_keys.clear();
+		for (_, batch) in self.batches.drain() {
+			let _ = batch.send_back.send(Err(Error::ConnectionLost));
+		}
+		let mut subscriptions = Vec::new();
+		for (_, kind) in self.requests.drain() {
+			match kind {
+				Kind::PendingMethodCall((send_back, _)) => {
+					let _ = send_back.send(Err(Error::ConnectionLost));
+				}
+				Kind::PendingSubscription((send_back, _)) => {
+					let _ = send_back.send(Err(Error::ConnectionLost));
+				}
+				Kind::Subscription(subscription) | Kind::PendingResubscription(subscription) => {
+					subscriptions.push(subscription)
+				}
+				Kind::PendingBatchCall(_) => (),
+			}
+		}
+		subscriptions
+	}
+
 	/// Tries to complete a pending call..
 	///
 	/// Returns `Some` if the call was completed otherwise `None`.
@@ -128,7 +338,8 @@ impl RequestManager {
 		match self.requests.entry(request_id) {
 			Entry::Occupied(request) if matches!(request.get(), Kind::PendingMethodCall(_)) => {
 				let (_req_id, kind) = request.remove_entry();
-				if let Kind::PendingMethodCall(send_back) = kind {
+				if let Kind::PendingMethodCall((send_back, cancel_key)) = kind {
+					self.cancel_keys.remove(&cancel_key);
 					Some(send_back)
 				} else {
 					unreachable!("Pending call is Pending call checked above; qed");
@@ -145,7 +356,7 @@ impl RequestManager {
 		&mut self,
 		request_id: RequestId,
 		subscription_id: SubscriptionId,
-	) -> Option<(SubscriptionSink, UnsubscribeMethod)> {
+	) -> Option<(SubscriptionSink, SubscribeRequest)> {
 		match (self.requests.entry(request_id), self.subscriptions.entry(subscription_id)) {
 			(Entry::Occupied(request), Entry::Occupied(subscription))
 				if matches!(request.get(), Kind::Subscription(_)) =>
@@ -162,12 +373,34 @@ impl RequestManager {
 		}
 	}
 
+	/// Removes the pending method call or batch that the frontend has given up on with
+	/// `cancel_key`.
+	///
+	/// Returns the number of removed method calls, which is zero if the method call or batch
+	/// isn't pending anymore.
+	pub fn remove_cancelled(&mut self, cancel_key: CancelKey) -> usize {
+		let request_id = match self.cancel_keys.remove(&cancel_key) {
+			Some(request_id) => request_id,
+			None => return 0,
+		};
+		if let Some(batch) = self.batches.remove(&request_id) {
+			let requests = &mut self.requests;
+			return batch.ids.iter().filter(|id| requests.remove(id).is_some()).count();
+		}
+		match self.requests.remove(&request_id) {
+			Some(_) => 1,
+			None => 0,
+		}
+	}
+
 	/// Returns the status of a request ID
 	pub fn request_status(&mut self, id: &RequestId) -> RequestStatus {
 		self.requests.get(id).map_or(RequestStatus::Invalid, |kind| match kind {
 			Kind::PendingMethodCall(_) => RequestStatus::PendingMethodCall,
 			Kind::PendingSubscription(_) => RequestStatus::PendingSubscription,
 			Kind::Subscription(_) => RequestStatus::Subscription,
+			Kind::PendingResubscription(_) => RequestStatus::PendingResubscription,
+			Kind::PendingBatchCall(_) => RequestStatus::PendingBatchCall,
 		})
 	}
 
@@ -192,46 +425,103 @@ impl RequestManager {
 
 #[cfg(test)]
 mod tests {
-	use super::{Error, RequestManager};
+	use super::{Error, RequestManager, RequestStatus, SubscribeRequest, SubscriptionMessage};
 	use futures::channel::{mpsc, oneshot};
-	use jsonrpsee_types::jsonrpc::{JsonValue, SubscriptionId};
+	use jsonrpsee_types::jsonrpc::{JsonValue, Params, SubscriptionId};
+
+	fn subscribe_request(unsubscribe_method: &str) -> SubscribeRequest {
+		SubscribeRequest {
+			subscribe_method: "subscribe_method".into(),
+			params: Params::None,
+			unsubscribe_method: unsubscribe_method.into(),
+		}
+	}
 
 	#[test]
 	fn insert_remove_pending_request_works() {
 		let (request_tx, _) = oneshot::channel::<Result<JsonValue, Error>>();
 
 		let mut manager = RequestManager::new();
-		assert!(manager.insert_pending_call(0, request_tx).is_ok());
+		assert!(manager.insert_pending_call(0, 0, request_tx).is_ok());
 		assert!(manager.complete_pending_call(0).is_some());
 	}
 
 	#[test]
 	fn insert_remove_subscription_works() {
-		let (pending_sub_tx, _) = oneshot::channel::<Result<(mpsc::Receiver<JsonValue>, SubscriptionId), Error>>();
-		let (sub_tx, _) = mpsc::channel::<JsonValue>(1);
+		let (pending_sub_tx, _) =
+			oneshot::channel::<Result<(mpsc::Receiver<SubscriptionMessage>, SubscriptionId), Error>>();
+		let (sub_tx, _) = mpsc::channel::<SubscriptionMessage>(1);
 		let mut manager = RequestManager::new();
-		assert!(manager.insert_pending_subscription(1, pending_sub_tx, "unsubscribe_method".into()).is_ok());
-		let (_send_back_oneshot, unsubscribe_method) = manager.complete_pending_subscription(1).unwrap();
 		assert!(manager
-			.insert_subscription(1, SubscriptionId::Str("uniq_id_from_server".to_string()), sub_tx, unsubscribe_method)
+			.insert_pending_subscription(1, pending_sub_tx, subscribe_request("unsubscribe_method"))
+			.is_ok());
+		let (_send_back_oneshot, request) = manager.complete_pending_subscription(1).unwrap();
+		assert!(manager
+			.insert_subscription(1, SubscriptionId::Str("uniq_id_from_server".to_string()), sub_tx, request)
 			.is_ok());
 
 		assert!(manager.as_subscription_mut(&1).is_some());
 		assert!(manager.remove_subscription(1, SubscriptionId::Str("uniq_id_from_server".to_string())).is_some());
 	}
 
+	#[test]
+	fn insert_complete_pending_batch_works() {
+		let (batch_tx, _) = oneshot::channel::<Result<Vec<Result<JsonValue, Error>>, Error>>();
+		let mut manager = RequestManager::new();
+		assert!(manager.insert_pending_batch(vec![4, 5, 6], 4, batch_tx).is_ok());
+		assert_eq!(manager.get_batch_id(&6), Some(4));
+
+		assert!(manager.complete_pending_batch_call(6, Ok(JsonValue::from(6))).is_none());
+		assert!(manager.complete_pending_batch_call(6, Ok(JsonValue::from(6))).is_none());
+		assert!(manager.complete_pending_batch_call(4, Ok(JsonValue::from(4))).is_none());
+		let (_send_back, results) = manager.complete_pending_batch_call(5, Ok(JsonValue::from(5))).unwrap();
+		let results: Vec<_> = results.into_iter().map(Result::unwrap).collect();
+		assert_eq!(results, vec![JsonValue::from(4), JsonValue::from(5), JsonValue::from(6)]);
+		assert!(manager.get_batch_id(&4).is_none());
+
+		// The method calls that haven't been answered are reported as failed.
+		let (batch_tx, _) = oneshot::channel::<Result<Vec<Result<JsonValue, Error>>, Error>>();
+		assert!(manager.insert_pending_batch(vec![7, 8], 7, batch_tx).is_ok());
+		assert!(manager.complete_pending_batch_call(8, Ok(JsonValue::from(8))).is_none());
+		let (_send_back, results) = manager.complete_pending_batch(7).unwrap();
+		assert!(matches!(results[0], Err(Error::Custom(_))));
+		assert_eq!(results[1].as_ref().unwrap(), &JsonValue::from(8));
+		assert!(manager.complete_pending_batch(7).is_none());
+		assert!(matches!(manager.request_status(&7), RequestStatus::Invalid));
+	}
+
+	#[test]
+	fn pending_batch_faulty() {
+		let (request_tx, _) = oneshot::channel::<Result<JsonValue, Error>>();
+		let (batch_tx1, _) = oneshot::channel::<Result<Vec<Result<JsonValue, Error>>, Error>>();
+		let (batch_tx2, _) = oneshot::channel::<Result<Vec<Result<JsonValue, Error>>, Error>>();
+		let (batch_tx3, _) = oneshot::channel::<Result<Vec<Result<JsonValue, Error>>, Error>>();
+
+		let mut manager = RequestManager::new();
+		assert!(manager.insert_pending_call(2, 2, request_tx).is_ok());
+		assert!(manager.insert_pending_batch(vec![1, 2], 1, batch_tx1).is_err());
+		assert!(manager.insert_pending_batch(vec![3, 3], 3, batch_tx2).is_err());
+		assert!(manager.insert_pending_batch(Vec::new(), 10, batch_tx3).is_err());
+		assert!(matches!(manager.request_status(&1), RequestStatus::Invalid));
+		assert!(manager.complete_pending_batch_call(2, Ok(JsonValue::Null)).is_none());
+		assert!(manager.complete_pending_call(2).is_some());
+	}
+
 	#[test]
 	fn pending_method_call_faulty() {
 		let (request_tx1, _) = oneshot::channel::<Result<JsonValue, Error>>();
 		let (request_tx2, _) = oneshot::channel::<Result<JsonValue, Error>>();
-		let (pending_sub_tx, _) = oneshot::channel::<Result<(mpsc::Receiver<JsonValue>, SubscriptionId), Error>>();
-		let (sub_tx, _) = mpsc::channel::<JsonValue>(1);
+		let (pending_sub_tx, _) =
+			oneshot::channel::<Result<(mpsc::Receiver<SubscriptionMessage>, SubscriptionId), Error>>();
+		let (sub_tx, _) = mpsc::channel::<SubscriptionMessage>(1);
 
 		let mut manager = RequestManager::new();
-		assert!(manager.insert_pending_call(0, request_tx1).is_ok());
-		assert!(manager.insert_pending_call(0, request_tx2).is_err());
-		assert!(manager.insert_pending_subscription(0, pending_sub_tx, "beef".to_string()).is_err());
-		assert!(manager.insert_subscription(0, SubscriptionId::Num(137), sub_tx, "bibimbap".to_string()).is_err());
+		assert!(manager.insert_pending_call(0, 0, request_tx1).is_ok());
+		assert!(manager.insert_pending_call(0, 0, request_tx2).is_err());
+		assert!(manager.insert_pending_subscription(0, pending_sub_tx, subscribe_request("beef")).is_err());
+		assert!(manager
+			.insert_subscription(0, SubscriptionId::Num(137), sub_tx, subscribe_request("bibimbap"))
+			.is_err());
 
 		assert!(manager.remove_subscription(0, SubscriptionId::Num(137)).is_none());
 		assert!(manager.complete_pending_subscription(0).is_none());
@@ -241,16 +531,20 @@ mod tests {
 	#[test]
 	fn pending_subscription_faulty() {
 		let (request_tx, _) = oneshot::channel::<Result<JsonValue, Error>>();
-		let (pending_sub_tx1, _) = oneshot::channel::<Result<(mpsc::Receiver<JsonValue>, SubscriptionId), Error>>();
-		let (pending_sub_tx2, _) = oneshot::channel::<Result<(mpsc::Receiver<JsonValue>, SubscriptionId), Error>>();
-		let (sub_tx, _) = mpsc::channel::<JsonValue>(1);
+		let (pending_sub_tx1, _) =
+			oneshot::channel::<Result<(mpsc::Receiver<SubscriptionMessage>, SubscriptionId), Error>>();
+		let (pending_sub_tx2, _) =
+			oneshot::channel::<Result<(mpsc::Receiver<SubscriptionMessage>, SubscriptionId), Error>>();
+		let (sub_tx, _) = mpsc::channel::<SubscriptionMessage>(1);
 
 		let mut manager = RequestManager::new();
-		assert!(manager.insert_pending_subscription(99, pending_sub_tx1, "beef".to_string()).is_ok());
-		assert!(manager.insert_pending_call(99, request_tx).is_err());
-		assert!(manager.insert_pending_subscription(99, pending_sub_tx2, "vegan".to_string()).is_err());
+		assert!(manager.insert_pending_subscription(99, pending_sub_tx1, subscribe_request("beef")).is_ok());
+		assert!(manager.insert_pending_call(99, 99, request_tx).is_err());
+		assert!(manager.insert_pending_subscription(99, pending_sub_tx2, subscribe_request("vegan")).is_err());
 
-		assert!(manager.insert_subscription(99, SubscriptionId::Num(0), sub_tx, "bibimbap".to_string()).is_err());
+		assert!(manager
+			.insert_subscription(99, SubscriptionId::Num(0), sub_tx, subscribe_request("bibimbap"))
+			.is_err());
 
 		assert!(manager.remove_subscription(99, SubscriptionId::Num(0)).is_none());
 		assert!(manager.complete_pending_call(99).is_none());
@@ -260,16 +554,19 @@ mod tests {
 	#[test]
 	fn active_subscriptions_faulty() {
 		let (request_tx, _) = oneshot::channel::<Result<JsonValue, Error>>();
-		let (pending_sub_tx, _) = oneshot::channel::<Result<(mpsc::Receiver<JsonValue>, SubscriptionId), Error>>();
-		let (sub_tx1, _) = mpsc::channel::<JsonValue>(1);
-		let (sub_tx2, _) = mpsc::channel::<JsonValue>(1);
+		let (pending_sub_tx, _) =
+			oneshot::channel::<Result<(mpsc::Receiver<SubscriptionMessage>, SubscriptionId), Error>>();
+		let (sub_tx1, _) = mpsc::channel::<SubscriptionMessage>(1);
+		let (sub_tx2, _) = mpsc::channel::<SubscriptionMessage>(1);
 
 		let mut manager = RequestManager::new();
 
-		assert!(manager.insert_subscription(3, SubscriptionId::Num(0), sub_tx1, "bibimbap".to_string()).is_ok());
-		assert!(manager.insert_subscription(3, SubscriptionId::Num(1), sub_tx2, "bibimbap".to_string()).is_err());
-		assert!(manager.insert_pending_subscription(3, pending_sub_tx, "beef".to_string()).is_err());
-		assert!(manager.insert_pending_call(3, request_tx).is_err());
+		assert!(manager.insert_subscription(3, SubscriptionId::Num(0), sub_tx1, subscribe_request("bibimbap")).is_ok());
+		assert!(manager
+			.insert_subscription(3, SubscriptionId::Num(1), sub_tx2, subscribe_request("bibimbap"))
+			.is_err());
+		assert!(manager.insert_pending_subscription(3, pending_sub_tx, subscribe_request("beef")).is_err());
+		assert!(manager.insert_pending_call(3, 3, request_tx).is_err());
 
 		assert!(manager.remove_subscription(3, SubscriptionId::Num(7)).is_none());
 		assert!(manager.complete_pending_call(3).is_none());
@@ -277,4 +574,88 @@ mod tests {
 		assert!(manager.remove_subscription(3, SubscriptionId::Num(1)).is_none());
 		assert!(manager.remove_subscription(3, SubscriptionId::Num(0)).is_some());
 	}
+
+	#[test]
+	fn reset_keeps_subscriptions() {
+		let (request_tx, request_rx) = oneshot::channel::<Result<JsonValue, Error>>();
+		let (pending_sub_tx, pending_sub_rx) =
+			oneshot::channel::<Result<(mpsc::Receiver<SubscriptionMessage>, SubscriptionId), Error>>();
+		let (batch_tx, batch_rx) = oneshot::channel::<Result<Vec<Result<JsonValue, Error>>, Error>>();
+		let (sub_tx1, _) = mpsc::channel::<SubscriptionMessage>(1);
+		let (sub_tx2, _) = mpsc::channel::<SubscriptionMessage>(1);
+
+		let mut manager = RequestManager::new();
+		assert!(manager.insert_pending_call(0, 0, request_tx).is_ok());
+		assert!(manager.insert_pending_subscription(1, pending_sub_tx, subscribe_request("beef")).is_ok());
+		assert!(manager.insert_pending_batch(vec![2, 3], 2, batch_tx).is_ok());
+		assert!(manager.insert_subscription(4, SubscriptionId::Num(0), sub_tx1, subscribe_request("bibimbap")).is_ok());
+		assert!(manager.insert_pending_resubscription(5, sub_tx2, subscribe_request("vegan")).is_ok());
+		assert!(matches!(manager.request_status(&5), RequestStatus::PendingResubscription));
+
+		let mut subscriptions: Vec<_> =
+			manager.reset().into_iter().map(|(_, request)| request.unsubscribe_method).collect();
+		subscriptions.sort();
+		assert_eq!(subscriptions, vec!["bibimbap".to_string(), "vegan".to_string()]);
+
+		assert!(matches!(futures::executor::block_on(request_rx), Ok(Err(Error::ConnectionLost))));
+		assert!(matches!(futures::executor::block_on(pending_sub_rx), Ok(Err(Error::ConnectionLost))));
+		assert!(matches!(futures::executor::block_on(batch_rx), Ok(Err(Error::ConnectionLost))));
+		for id in 0..6 {
+			assert!(matches!(manager.request_status(&id), RequestStatus::Invalid));
+		}
+		assert!(manager.get_request_id_by_subscription_id(&SubscriptionId::Num(0)).is_none());
+	}
+
+	#[test]
+	fn pending_resubscription_works() {
+		let (request_tx, _) = oneshot::channel::<Result<JsonValue, Error>>();
+		let (sub_tx1, _) = mpsc::channel::<SubscriptionMessage>(1);
+		let (sub_tx2, _) = mpsc::channel::<SubscriptionMessage>(1);
+
+		let mut manager = RequestManager::new();
+		assert!(manager.insert_pending_call(0, 0, request_tx).is_ok());
+		assert!(manager.insert_pending_resubscription(0, sub_tx1, subscribe_request("beef")).is_err());
+		assert!(manager.insert_pending_resubscription(1, sub_tx2, subscribe_request("beef")).is_ok());
+		assert!(manager.complete_pending_subscription(1).is_none());
+		assert!(manager.complete_pending_resubscription(0).is_none());
+		let (sink, request) = manager.complete_pending_resubscription(1).unwrap();
+		assert!(manager.insert_subscription(1, SubscriptionId::Num(3), sink, request).is_ok());
+		assert!(matches!(manager.request_status(&1), RequestStatus::Subscription));
+	}
+
+	#[test]
+	fn remove_cancelled_works() {
+		let (request_tx1, _request_rx1) = oneshot::channel::<Result<JsonValue, Error>>();
+		let (request_tx2, _request_rx2) = oneshot::channel::<Result<JsonValue, Error>>();
+		let (request_tx3, _request_rx3) = oneshot::channel::<Result<JsonValue, Error>>();
+		let (pending_sub_tx, _pending_sub_rx) =
+			oneshot::channel::<Result<(mpsc::Receiver<SubscriptionMessage>, SubscriptionId), Error>>();
+		let (batch_tx1, _batch_rx1) = oneshot::channel::<Result<Vec<Result<JsonValue, Error>>, Error>>();
+		let (batch_tx2, _batch_rx2) = oneshot::channel::<Result<Vec<Result<JsonValue, Error>>, Error>>();
+
+		let mut manager = RequestManager::new();
+		assert!(manager.insert_pending_call(0, 10, request_tx1).is_ok());
+		assert!(manager.insert_pending_call(1, 11, request_tx2).is_ok());
+		// Each key identifies a single method call or batch.
+		assert!(manager.insert_pending_call(6, 11, request_tx3).is_err());
+		assert!(manager.insert_pending_subscription(2, pending_sub_tx, subscribe_request("beef")).is_ok());
+		assert!(manager.insert_pending_batch(vec![3, 4], 12, batch_tx1).is_ok());
+		assert!(manager.insert_pending_batch(vec![5], 13, batch_tx2).is_ok());
+		assert_eq!(manager.remove_cancelled(99), 0);
+
+		assert_eq!(manager.remove_cancelled(10), 1);
+		assert_eq!(manager.remove_cancelled(12), 2);
+		assert_eq!(manager.remove_cancelled(12), 0);
+		for id in [0, 3, 4].iter() {
+			assert!(matches!(manager.request_status(id), RequestStatus::Invalid));
+		}
+		assert!(manager.complete_pending_batch(3).is_none());
+		assert!(manager.complete_pending_call(1).is_some());
+		// The server is told to unsubscribe once it has answered the subscription request.
+		assert!(manager.complete_pending_subscription(2).is_some());
+		assert!(manager.complete_pending_batch_call(5, Ok(JsonValue::Null)).is_some());
+		// The keys of the requests that have been answered don't cancel anything anymore.
+		assert_eq!(manager.remove_cancelled(11), 0);
+		assert_eq!(manager.remove_cancelled(13), 0);
+	}
 }
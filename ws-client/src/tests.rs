@@ -3,7 +3,7 @@
 use crate::client::{WsClient, WsConfig, WsSubscription};
 use jsonrpsee_test_utils::helpers::*;
 use jsonrpsee_test_utils::types::{Id, WebSocketTestServer};
-use jsonrpsee_types::{error::Error, jsonrpc};
+use jsonrpsee_types::{client::BatchEntry, error::Error, jsonrpc};
 
 fn assert_error_response(response: Result<jsonrpc::JsonValue, Error>, code: jsonrpc::ErrorCode, message: String) {
 	let expected = jsonrpc::Error { code, message, data: None };
@@ -120,3 +120,30 @@ async fn response_with_wrong_id() {
 	let err: Result<jsonrpc::JsonValue, Error> = client.request("say_hello", jsonrpc::Params::None).await;
 	assert!(matches!(err, Err(Error::TransportError(e)) if e.to_string().contains("background task closed")));
 }
+
+#[tokio::test]
+async fn batch_request_works() {
+	// The responses are out of order, one of the calls failed and one wasn't answered.
+	let response = format!(
+		"[{},{},{}]",
+		ok_response("three".into(), Id::Num(3)),
+		method_not_found(Id::Num(1)),
+		ok_response("zero".into(), Id::Num(0))
+	);
+	let server = WebSocketTestServer::with_hardcoded_response("127.0.0.1:0".parse().unwrap(), response).await;
+	let uri = to_ws_uri_string(server.local_addr());
+	let client = WsClient::new(&uri, WsConfig::default()).await.unwrap();
+	let batch = vec![
+		BatchEntry::call("say_hello", jsonrpc::Params::None),
+		BatchEntry::call("say_hello", jsonrpc::Params::None),
+		BatchEntry::notification("notif", jsonrpc::Params::None),
+		BatchEntry::call("say_hello", jsonrpc::Params::None),
+		BatchEntry::call("say_hello", jsonrpc::Params::None),
+	];
+	let mut results = client.batch_request(batch).await.unwrap().into_iter();
+	assert_eq!(results.next().unwrap().unwrap(), jsonrpc::JsonValue::String("zero".into()));
+	assert_error_response(results.next().unwrap(), jsonrpc::ErrorCode::MethodNotFound, METHOD_NOT_FOUND.into());
+	assert!(matches!(results.next().unwrap(), Err(Error::Custom(_))));
+	assert_eq!(results.next().unwrap().unwrap(), jsonrpc::JsonValue::String("three".into()));
+	assert!(results.next().is_none());
+}
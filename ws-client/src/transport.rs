@@ -31,7 +31,7 @@ use futures::prelude::*;
 use jsonrpsee_types::jsonrpc;
 use soketto::connection;
 use soketto::handshake::client::{Client as WsRawClient, ServerResponse};
-use std::{borrow::Cow, io, net::SocketAddr, time::Duration};
+use std::{borrow::Cow, convert::TryFrom as _, io, net::SocketAddr, sync::Arc, time::Duration};
 use thiserror::Error;
 
 type TlsOrPlain = crate::stream::EitherStream<TcpStream, TlsStream<TcpStream>>;
@@ -46,6 +46,15 @@ pub struct Receiver {
 	inner: connection::Receiver<BufReader<BufWriter<TlsOrPlain>>>,
 }
 
+/// Message received from the server.
+#[derive(Debug)]
+pub enum Incoming {
+	/// Response or notification.
+	Response(jsonrpc::Response),
+	/// Pong answering one of our pings.
+	Pong,
+}
+
 /// Builder for a [`WsTransportClient`].
 pub struct WsTransportClientBuilder<'a> {
 	/// IP address to try to connect to.
@@ -63,6 +72,9 @@ pub struct WsTransportClientBuilder<'a> {
 	/// `Origin` header to pass during the HTTP handshake. If `None`, no
 	/// `Origin` header is passed.
 	origin: Option<Cow<'a, str>>,
+	/// TLS configuration used in [`Mode::Tls`]. If `None`, the server certificates are checked
+	/// against the Mozilla root certificates.
+	tls_config: Option<Arc<rustls::ClientConfig>>,
 }
 
 /// Stream mode, either plain TCP or TLS.
@@ -156,11 +168,18 @@ pub fn builder<'a>(
 		url: From::from("/"),
 		timeout: Duration::from_secs(10),
 		origin: None,
+		tls_config: None,
 	}
 }
 
 /// Creates a new WebSocket connection from URL, represented as a Sender and Receiver pair.
-pub async fn websocket_connection(remote_addr: impl AsRef<str>) -> Result<(Sender, Receiver), WsNewDnsError> {
+///
+/// For `wss://` URLs, `tls_config` is used if provided. Otherwise, the server certificate is
+/// checked against the Mozilla root certificates.
+pub async fn websocket_connection(
+	remote_addr: impl AsRef<str>,
+	tls_config: Option<Arc<rustls::ClientConfig>>,
+) -> Result<(Sender, Receiver), WsNewDnsError> {
 	let url =
 		url::Url::parse(remote_addr.as_ref()).map_err(|e| WsNewDnsError::Url(format!("Invalid URL: {}", e).into()))?;
 	let mode = match url.scheme() {
@@ -177,7 +196,11 @@ pub async fn websocket_connection(remote_addr: impl AsRef<str>) -> Result<(Sende
 	let mut error = None;
 
 	for url in target.to_socket_addrs().await.map_err(WsNewDnsError::ResolutionFailed)? {
-		match builder(url, &target, host, mode).build().await {
+		let mut builder = builder(url, &target, host, mode);
+		if let Some(tls_config) = &tls_config {
+			builder = builder.with_tls_config(tls_config.clone());
+		}
+		match builder.build().await {
 			Ok(ws_raw_client) => return Ok(ws_raw_client),
 			Err(err) => error = Some(err),
 		}
@@ -200,16 +223,43 @@ impl Sender {
 		self.inner.flush().await?;
 		Ok(())
 	}
+
+	/// Sends out a ping. The server answers it with a pong.
+	pub async fn send_ping(&mut self) -> Result<(), WsConnectError> {
+		let ping = soketto::data::ByteSlice125::try_from(&[][..]).expect("empty payload; qed");
+		self.inner.send_ping(ping).await?;
+		self.inner.flush().await?;
+		Ok(())
+	}
+
+	/// Sends out a close frame and closes the connection.
+	pub async fn close(&mut self) -> Result<(), WsConnectError> {
+		self.inner.close().await?;
+		Ok(())
+	}
 }
 
 impl Receiver {
 	/// Returns a `Future` resolving when the server sent us something back.
+	///
+	/// Pongs are skipped.
 	pub async fn next_response(&mut self) -> Result<jsonrpc::Response, WsConnectError> {
+		loop {
+			if let Incoming::Response(response) = self.next_incoming().await? {
+				return Ok(response);
+			}
+		}
+	}
+
+	/// Returns a `Future` resolving when the server sent us something back, including pongs.
+	pub async fn next_incoming(&mut self) -> Result<Incoming, WsConnectError> {
 		let mut message = Vec::new();
-		self.inner.receive_data(&mut message).await?;
+		if let soketto::Incoming::Pong(_) = self.inner.receive(&mut message).await? {
+			return Ok(Incoming::Pong);
+		}
 		let response = jsonrpc::from_slice(&message).map_err(WsConnectError::ParseError)?;
 		log::debug!("recv: {}", response);
-		Ok(response)
+		Ok(Incoming::Response(response))
 	}
 }
 
@@ -230,6 +280,15 @@ impl<'a> WsTransportClientBuilder<'a> {
 		self
 	}
 
+	/// Sets the TLS configuration to use in [`Mode::Tls`], for example in order to trust
+	/// additional root certificates.
+	///
+	/// By default, the server certificates are checked against the Mozilla root certificates.
+	pub fn with_tls_config(mut self, config: Arc<rustls::ClientConfig>) -> Self {
+		self.tls_config = Some(config);
+		self
+	}
+
 	/// Sets the timeout to use when establishing the TCP connection.
 	///
 	/// The default timeout is 10 seconds.
@@ -250,7 +309,10 @@ impl<'a> WsTransportClientBuilder<'a> {
 				future::Either::Left((socket, _)) => match self.mode {
 					Mode::Plain => TlsOrPlain::Plain(socket?),
 					Mode::Tls => {
-						let connector = async_tls::TlsConnector::default();
+						let connector = match &self.tls_config {
+							Some(config) => async_tls::TlsConnector::from(config.clone()),
+							None => async_tls::TlsConnector::default(),
+						};
 						let dns_name = webpki::DNSNameRef::try_from_ascii_str(&self.dns_name)?;
 						let tls_stream = connector.connect(&dns_name.to_owned(), socket?).await?;
 						TlsOrPlain::Tls(tls_stream)
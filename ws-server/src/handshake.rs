@@ -0,0 +1,123 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+//
+// Permission is hereby granted, free of charge, to any
+// person obtaining a copy of this software and associated
+// documentation files (the "Software"), to deal in the
+// Software without restriction, including without
+// limitation the rights to use, copy, modify, merge,
+// publish, distribute, sublicense, and/or sell copies of
+// the Software, and to permit persons to whom the Software
+// is furnished to do so, subject to the following
+// conditions:
+//
+// The above copyright notice and this permission notice
+// shall be included in all copies or substantial portions
+// of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF
+// ANY KIND, EXPRESS OR IMPLIED, INCLUDING BUT NOT LIMITED
+// TO THE WARRANTIES OF MERCHANTABILITY, FITNESS FOR A
+// PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT
+// SHALL THE AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY
+// CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN AN ACTION
+// OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR
+// IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
+// DEALINGS IN THE SOFTWARE.
+
+//! Reading of the HTTP request that opens a WebSocket connection.
+//!
+//! `soketto` doesn't give access to the headers of the handshake request. We parse the request
+//! ourselves in order to check its `Host` and `Origin` headers, then replay the bytes we have
+//! read to `soketto`, which completes the handshake.
+
+use futures::io::{AsyncRead, AsyncReadExt as _, AsyncWrite};
+use std::{
+	io,
+	pin::Pin,
+	task::{Context, Poll},
+};
+
+/// Maximum size of the handshake request.
+const MAX_REQUEST_SIZE: usize = 16 * 1024;
+/// Maximum number of headers of the handshake request.
+const MAX_HEADERS: usize = 64;
+
+/// Reads the HTTP request that opens the WebSocket handshake from `socket`.
+///
+/// Returns the request, whose body is always empty, and the socket, which produces the bytes of
+/// the request again when read.
+pub(crate) async fn read_request<S>(mut socket: S) -> io::Result<(hyper::Request<hyper::Body>, Replay<S>)>
+where
+	S: AsyncRead + Unpin,
+{
+	let mut buffer = Vec::new();
+	loop {
+		if buffer.len() >= MAX_REQUEST_SIZE {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "handshake request too large"));
+		}
+		let mut chunk = [0; 1024];
+		let read = socket.read(&mut chunk).await?;
+		if read == 0 {
+			return Err(io::ErrorKind::UnexpectedEof.into());
+		}
+		buffer.extend_from_slice(&chunk[..read]);
+
+		let mut headers = [httparse::EMPTY_HEADER; MAX_HEADERS];
+		let mut parsed = httparse::Request::new(&mut headers);
+		match parsed.parse(&buffer) {
+			Ok(httparse::Status::Complete(_)) => {}
+			Ok(httparse::Status::Partial) => continue,
+			Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+		}
+
+		let mut request =
+			hyper::Request::builder().method(parsed.method.unwrap_or("GET")).uri(parsed.path.unwrap_or("/"));
+		for header in parsed.headers.iter() {
+			request = request.header(header.name, header.value);
+		}
+		let request =
+			request.body(hyper::Body::empty()).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+		return Ok((request, Replay { buffer, position: 0, socket }));
+	}
+}
+
+/// Socket that first produces bytes that have already been read from it.
+pub(crate) struct Replay<S> {
+	/// Bytes to produce before reading from `socket`.
+	buffer: Vec<u8>,
+	/// Number of bytes of `buffer` that have already been produced.
+	position: usize,
+	/// The underlying socket.
+	socket: S,
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Replay<S> {
+	fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+		let this = &mut *self;
+		if this.position < this.buffer.len() {
+			let len = buf.len().min(this.buffer.len() - this.position);
+			buf[..len].copy_from_slice(&this.buffer[this.position..this.position + len]);
+			this.position += len;
+			if this.position == this.buffer.len() {
+				this.buffer = Vec::new();
+				this.position = 0;
+			}
+			return Poll::Ready(Ok(len));
+		}
+		Pin::new(&mut this.socket).poll_read(cx, buf)
+	}
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Replay<S> {
+	fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+		Pin::new(&mut self.socket).poll_write(cx, buf)
+	}
+
+	fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.socket).poll_flush(cx)
+	}
+
+	fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+		Pin::new(&mut self.socket).poll_close(cx)
+	}
+}
@@ -26,6 +26,7 @@
 
 extern crate alloc;
 
+mod handshake;
 mod raw;
 mod server;
 mod transport;
@@ -33,6 +34,11 @@ mod transport;
 #[cfg(test)]
 mod tests;
 
+pub use jsonrpsee_types::ws::{KeepAliveConfig, WsServerConfig};
+pub use jsonrpsee_utils::http::access_control::{AccessControl, AccessControlBuilder};
+pub use jsonrpsee_utils::tls::{TlsConfigError, TlsServerConfig};
 pub use raw::{RawServer as RawWsServer, RawServerEvent as RawWsServerEvent, TypedResponder as WsTypedResponder};
-pub use server::{RegisteredMethod, RegisteredNotification, Server as WsServer};
+pub use server::{
+	Builder as WsServerBuilder, RegisteredMethod, RegisteredNotification, Server as WsServer, SubscriptionSink,
+};
 pub use transport::WsTransportServer;
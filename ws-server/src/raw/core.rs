@@ -28,12 +28,14 @@ use crate::transport::{TransportServerEvent, WsRequestId as RequestId, WsTranspo
 use jsonrpsee_types::{
 	jsonrpc::wrapped::{batches, Notification, Params},
 	jsonrpc::{self, JsonValue},
+	server::{ConnectionExtensions, Middleware, MiddlewareStack, RequestContext, ServerMetrics},
 };
 
-use alloc::{borrow::ToOwned as _, string::String, vec, vec::Vec};
+use alloc::{borrow::ToOwned as _, string::String, sync::Arc, vec, vec::Vec};
 use core::convert::TryFrom;
 use core::{fmt, hash::Hash, num::NonZeroUsize};
 use hashbrown::{hash_map::Entry, HashMap};
+use std::time::Instant;
 
 /// Wraps around a "raw server" and adds capabilities.
 ///
@@ -62,6 +64,14 @@ pub struct RawServer {
 	/// hashing algorithm. This incurs a performance cost that is theoretically avoidable (if `I`
 	/// is always local), but that should be negligible in practice.
 	num_subscriptions: HashMap<RequestId, NonZeroUsize>,
+
+	/// Middlewares called around every request.
+	middleware: MiddlewareStack,
+
+	/// For each request that has been returned by `next_event` and not answered yet, when it
+	/// has been returned. Entries are removed when the request is answered, which always happens
+	/// before its batch is removed from `batches`.
+	started: HashMap<batches::BatchesElemId, Instant, fnv::FnvBuildHasher>,
 }
 
 /// Identifier of a request within a `RawServer`.
@@ -106,6 +116,15 @@ pub struct RawServerRequest<'a> {
 
 	/// Reference to the corresponding field in `RawServer`.
 	num_subscriptions: &'a mut HashMap<RequestId, NonZeroUsize>,
+
+	/// Context of the connection this request has been received on.
+	context: RequestContext,
+
+	/// Reference to the corresponding field in `RawServer`.
+	middleware: &'a MiddlewareStack,
+
+	/// Reference to the corresponding field in `RawServer`.
+	started: &'a mut HashMap<batches::BatchesElemId, Instant, fnv::FnvBuildHasher>,
 }
 
 /// Active subscription of a client towards a server.
@@ -150,13 +169,28 @@ struct SubscriptionState<I> {
 impl RawServer {
 	/// Starts a [`RawServer`](crate::raw::RawServer) using the given raw server internally.
 	pub fn new(raw: WsTransportServer) -> RawServer {
+		// The metrics are the outermost middleware, so that they see every call.
+		let mut middleware = MiddlewareStack::new();
+		middleware.push(Arc::new(raw.metrics().clone()));
 		RawServer {
 			raw,
 			batches: batches::BatchesState::new(),
 			subscriptions: HashMap::with_capacity_and_hasher(8, Default::default()),
 			num_subscriptions: HashMap::with_capacity_and_hasher(8, Default::default()),
+			middleware,
+			started: HashMap::default(),
 		}
 	}
+
+	/// Returns the metrics of the server.
+	pub fn metrics(&self) -> &ServerMetrics {
+		self.raw.metrics()
+	}
+
+	/// Adds a middleware at the bottom of the stack of middlewares called around every request.
+	pub fn add_middleware(&mut self, middleware: Arc<dyn Middleware>) {
+		self.middleware.push(middleware);
+	}
 }
 
 impl RawServer {
@@ -169,9 +203,21 @@ impl RawServer {
 					return RawServerEvent::Notification(notification)
 				}
 				Some(batches::BatchesEvent::Request(inner)) => {
-					break RawServerRequestId { inner: inner.id() };
+					let request_id = RawServerRequestId { inner: inner.id() };
+					if self.start_request(&request_id) {
+						break request_id;
+					}
+					continue;
 				}
-				Some(batches::BatchesEvent::ReadyToSend { response, user_param: Some(raw_request_id) }) => {
+				Some(batches::BatchesEvent::ReadyToSend { response: None, user_param: Some(raw_request_id) }) => {
+					// The batch only contained notifications.
+					let _ = self.raw.finish(&raw_request_id, None).await;
+					continue;
+				}
+				Some(batches::BatchesEvent::ReadyToSend {
+					response: Some(response),
+					user_param: Some(raw_request_id),
+				}) => {
 					// If we have any active subscription, we only use `send` to not close the
 					// client request.
 					if self.num_subscriptions.contains_key(&raw_request_id) {
@@ -200,7 +246,12 @@ impl RawServer {
 			};
 
 			match self.raw.next_request().await {
-				TransportServerEvent::Request { id, request } => self.batches.inject(request, Some(id)),
+				TransportServerEvent::Request { id, request } => {
+					if let jsonrpc::Request::Batch(batch) = &request {
+						self.raw.metrics().on_batch(batch.len());
+					}
+					self.batches.inject(request, Some(id))
+				}
 				TransportServerEvent::Closed(raw_id) => {
 					// The client has a closed their connection. We eliminate all traces of the
 					// raw request ID from our state.
@@ -223,6 +274,7 @@ impl RawServer {
 						for id in &ids {
 							let _ = self.subscriptions.remove(&id.0);
 						}
+						self.raw.metrics().set_active_subscriptions(self.subscriptions.len());
 						return RawServerEvent::SubscriptionsClosed(SubscriptionsClosedIter(ids.into_iter()));
 					}
 				}
@@ -240,14 +292,84 @@ impl RawServer {
 	/// Returns `None` if the request ID is invalid or if the request has already been answered in
 	/// the past.
 	pub fn request_by_id<'a>(&'a mut self, id: &RawServerRequestId) -> Option<RawServerRequest<'a>> {
+		let mut inner = self.batches.request_by_id(id.inner)?;
+		let context = match inner.user_param() {
+			Some(raw_request_id) => self.raw.request_context(raw_request_id).cloned(),
+			None => None,
+		}
+		// The connection has been closed; it doesn't matter what context we return.
+		.unwrap_or_else(|| RequestContext::new(ConnectionExtensions::default()))
+		.with_id(inner.request_id().clone());
 		Some(RawServerRequest {
-			inner: self.batches.request_by_id(id.inner)?,
+			inner,
 			raw: &mut self.raw,
 			subscriptions: &mut self.subscriptions,
 			num_subscriptions: &mut self.num_subscriptions,
+			context,
+			middleware: &self.middleware,
+			started: &mut self.started,
 		})
 	}
 
+	/// Records the start of a request and passes it to the middlewares.
+	///
+	/// Returns false and answers the request if a middleware rejects it.
+	fn start_request(&mut self, id: &RawServerRequestId) -> bool {
+		let request = match self.request_by_id(id) {
+			Some(request) => request,
+			None => return false,
+		};
+		let started = Instant::now();
+
+		let rejection = {
+			let params: &jsonrpc::Params = request.params().into();
+			request.middleware.on_request(request.method(), params, request.context()).err()
+		};
+		match rejection {
+			// The middlewares have already been notified of the rejection.
+			Some(err) => {
+				request.respond(Err(err));
+				false
+			}
+			None => {
+				request.started.insert(id.inner, started);
+				true
+			}
+		}
+	}
+
+	/// Sends out the responses that are ready, then closes the underlying transport.
+	///
+	/// Requests that haven't been returned by [`next_event`](crate::raw::RawServer::next_event)
+	/// yet are answered with an error indicating that the server is shutting down. Requests that
+	/// are still waiting for an answer are destroyed.
+	pub async fn close(&mut self) {
+		loop {
+			match self.batches.next_event() {
+				None => break,
+				Some(batches::BatchesEvent::Notification { .. }) => {}
+				Some(batches::BatchesEvent::Request(inner)) => {
+					inner.set_response(Err(jsonrpc::Error::server_shutting_down()));
+				}
+				Some(batches::BatchesEvent::ReadyToSend { response, user_param: Some(raw_request_id) }) => {
+					match response {
+						Some(response) if self.num_subscriptions.contains_key(&raw_request_id) => {
+							let _ = self.raw.send(&raw_request_id, &response).await;
+						}
+						response => {
+							let _ = self.raw.finish(&raw_request_id, response.as_ref()).await;
+						}
+					}
+				}
+				Some(batches::BatchesEvent::ReadyToSend { response: _, user_param: None }) => {}
+			}
+		}
+
+		// The requests that are still waiting for an answer are destroyed.
+		self.started.clear();
+		self.raw.close().await;
+	}
+
 	/// Returns a subscription previously returned by
 	/// [`into_subscription`](crate::raw::server::RawServerRequest::into_subscription).
 	pub fn subscription_by_id(&mut self, id: RawServerSubscriptionId) -> Option<ServerSubscription> {
@@ -289,6 +411,11 @@ impl<'a> RawServerRequest<'a> {
 	pub fn params(&self) -> Params {
 		self.inner.params()
 	}
+
+	/// Returns information about the request and the connection it was received on.
+	pub fn context(&self) -> &RequestContext {
+		&self.context
+	}
 }
 
 impl<'a> RawServerRequest<'a> {
@@ -306,7 +433,8 @@ impl<'a> RawServerRequest<'a> {
 	/// >           method](crate::transport::TransportServer::finish) on the
 	/// >           [`TransportServer`](crate::transport::TransportServer) trait.
 	///
-	pub fn respond(self, response: Result<JsonValue, jsonrpc::Error>) {
+	pub fn respond(mut self, response: Result<JsonValue, jsonrpc::Error>) {
+		self.notify_middleware(&response);
 		self.inner.set_response(response);
 		//unimplemented!();
 		// TODO: actually send out response?
@@ -326,8 +454,8 @@ impl<'a> RawServerRequest<'a> {
 	/// the requests of that batch have to be processed before informing the client of the start
 	/// of the subscription.
 	///
-	/// Returns an error and doesn't do anything if the underlying server doesn't support
-	/// subscriptions, or if the connection has already been closed by the client.
+	/// Returns an error and answers the request with an "internal error" if the underlying server
+	/// doesn't support subscriptions, or if the connection has already been closed by the client.
 	///
 	/// > **Note**: Because of borrowing issues, we return a [`RawServerSubscriptionId`] rather than
 	/// >           a [`ServerSubscription`]. You will have to call
@@ -335,12 +463,17 @@ impl<'a> RawServerRequest<'a> {
 	/// >           subscription.
 	// TODO: solve the note
 	pub fn into_subscription(mut self) -> Result<RawServerSubscriptionId, IntoSubscriptionErr> {
+		// The request is answered anyway, so that its batch doesn't stay in memory forever.
 		let raw_request_id = match self.inner.user_param().clone() {
 			Some(id) => id,
-			None => return Err(IntoSubscriptionErr::Closed),
+			None => {
+				self.respond(Err(jsonrpc::Error::internal_error()));
+				return Err(IntoSubscriptionErr::Closed);
+			}
 		};
 
 		if !self.raw.supports_resuming(&raw_request_id).unwrap_or(false) {
+			self.respond(Err(jsonrpc::Error::internal_error()));
 			return Err(IntoSubscriptionErr::NotSupported);
 		}
 
@@ -363,14 +496,27 @@ impl<'a> RawServerRequest<'a> {
 					*e = NonZeroUsize::new(e.get() + 1).expect("we add 1 to an existing non-zero value; qed");
 				})
 				.or_insert_with(|| NonZeroUsize::new(1).expect("1 != 0"));
+			self.raw.metrics().set_active_subscriptions(self.subscriptions.len());
 
 			let subscr_id_string = bs58::encode(&new_subscr_id).into_string();
-			self.inner.set_response(Ok(subscr_id_string.into()));
+			let response = Ok(subscr_id_string.into());
+			self.notify_middleware(&response);
+			self.inner.set_response(response);
 			break Ok(RawServerSubscriptionId(new_subscr_id));
 		}
 	}
 }
 
+impl<'a> RawServerRequest<'a> {
+	/// Passes the answer to the request to the middlewares.
+	fn notify_middleware(&mut self, response: &Result<JsonValue, jsonrpc::Error>) {
+		if let Some(started) = self.started.remove(&self.inner.id()) {
+			let params: &jsonrpc::Params = self.params().into();
+			self.middleware.on_result(self.method(), params, &self.context, response, started.elapsed());
+		}
+	}
+}
+
 impl<'a> fmt::Debug for RawServerRequest<'a> {
 	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
 		f.debug_struct("RawServerRequest")
@@ -433,6 +579,7 @@ impl<'a> ServerSubscription<'a> {
 	pub async fn push(self, message: impl Into<JsonValue>) {
 		let subscription_state = self.server.subscriptions.get(&self.id).unwrap();
 		if subscription_state.pending {
+			self.server.raw.metrics().notification_dropped(&subscription_state.method);
 			return; // TODO: notify user with error
 		}
 
@@ -446,18 +593,46 @@ impl<'a> ServerSubscription<'a> {
 		};
 		let response = jsonrpc::Response::Notif(output);
 
-		let _ = self.server.raw.send(&subscription_state.raw_id, &response).await; // TODO: error handling?
+		if self.server.raw.send(&subscription_state.raw_id, &response).await.is_err() {
+			// TODO: error handling?
+			self.server.raw.metrics().notification_dropped(&subscription_state.method);
+		}
+	}
+
+	/// Notifies the client that the subscription has been closed by the server, then destroys
+	/// the subscription object.
+	///
+	/// If the confirmation of the subscription hasn't been sent to the client yet, no
+	/// notification is sent. See also [`close`](ServerSubscription::close).
+	pub async fn close_with_reason(self, reason: jsonrpc::SubscriptionClosedReason) {
+		let subscription_state = self.server.subscriptions.get(&self.id).unwrap();
+		if !subscription_state.pending {
+			let output = jsonrpc::SubscriptionClosed {
+				jsonrpc: jsonrpc::Version::V2,
+				method: subscription_state.method.clone(),
+				params: jsonrpc::SubscriptionClosedParams {
+					subscription: jsonrpc::SubscriptionId::Str(bs58::encode(&self.id).into_string()),
+					reason,
+				},
+			};
+			let response = jsonrpc::Response::SubscriptionClosed(output);
+			let _ = self.server.raw.send(&subscription_state.raw_id, &response).await;
+		}
+
+		self.close().await
 	}
 
 	/// Destroys the subscription object.
 	///
 	/// This does not send any message back to the client. Instead, this function is supposed to
-	/// be used in reaction to the client requesting to be unsubscribed.
+	/// be used in reaction to the client requesting to be unsubscribed. In order to notify the
+	/// client, use [`close_with_reason`](ServerSubscription::close_with_reason) instead.
 	///
 	/// If this was the last active subscription, also closes the connection ("raw request") with
 	/// the client.
 	pub async fn close(self) {
 		let subscription_state = self.server.subscriptions.remove(&self.id).unwrap();
+		self.server.raw.metrics().set_active_subscriptions(self.server.subscriptions.len());
 
 		// Check if we're the last subscription on this connection.
 		// Remove entry from `num_subscriptions` if so.
@@ -28,17 +28,32 @@ use crate::raw::{RawServer, RawServerEvent, RawServerRequestId, RawServerSubscri
 use crate::transport::WsTransportServer;
 use jsonrpsee_types::{
 	error::Error,
-	jsonrpc::{self, JsonValue},
+	jsonrpc::{self, DeserializeOwned, JsonValue, Serialize},
+	server::{
+		method_callback, method_callback_with_context, next_queued_request, poll_waiting_requests, MethodCallback,
+		MethodConfig, MethodQueue, MethodQueueRx, Middleware, Pushed, RequestContext, RpcModule, ServerMetrics,
+	},
+	ws::WsServerConfig,
+};
+use jsonrpsee_utils::{
+	http::access_control::AccessControl,
+	tls::{TlsServerConfig, ALPN_HTTP1},
 };
 
-use futures::{channel::mpsc, future::Either, pin_mut, prelude::*};
+use futures::{
+	channel::{mpsc, oneshot},
+	future::Either,
+	pin_mut,
+	prelude::*,
+};
 use parking_lot::Mutex;
 use std::{
 	collections::{HashMap, HashSet},
 	convert::TryFrom,
-	error,
+	error, mem,
 	net::SocketAddr,
 	sync::{atomic, Arc},
+	time::{Duration, Instant},
 };
 
 /// Server that can be cloned.
@@ -57,6 +72,20 @@ pub struct Server {
 	next_subscription_unique_id: Arc<atomic::AtomicUsize>,
 	/// Local socket address of the transport server.
 	local_addr: SocketAddr,
+	/// Metrics of the server.
+	metrics: ServerMetrics,
+}
+
+/// Builder for a [`Server`].
+pub struct Builder {
+	/// Address to listen on.
+	url: String,
+	/// Configuration of the server.
+	config: WsServerConfig,
+	/// TLS configuration of the server, if any.
+	tls: Option<rustls::ServerConfig>,
+	/// Allowed hosts and origins.
+	access_control: AccessControl,
 }
 
 /// Notification method that's been registered.
@@ -70,7 +99,7 @@ pub struct RegisteredMethod {
 	/// Clone of [`Server::to_back`].
 	to_back: mpsc::UnboundedSender<FrontToBack>,
 	/// Receives requests that the client sent to us.
-	queries_rx: mpsc::Receiver<(RawServerRequestId, jsonrpc::Params)>,
+	queries_rx: MethodQueueRx<RawServerRequestId>,
 }
 
 /// Pub-sub subscription that's been registered.
@@ -82,6 +111,20 @@ pub struct RegisteredSubscription {
 	unique_id: usize,
 }
 
+/// Sends notifications to a single client that subscribed through a handler registered with
+/// [`register_subscription_handler`](Server::register_subscription_handler).
+pub struct SubscriptionSink {
+	/// Clone of [`Server::to_back`].
+	to_back: mpsc::UnboundedSender<FrontToBack>,
+	/// Subscription of the client.
+	sub_id: RawServerSubscriptionId,
+	/// Canceled by the background task once the subscription is over.
+	closed: oneshot::Receiver<()>,
+}
+
+/// Subscriber dispatched by the background task to a subscription handler.
+type Subscriber = (RawServerSubscriptionId, jsonrpc::Params, oneshot::Receiver<()>);
+
 /// Active request that needs to be answered.
 pub struct IncomingRequest {
 	/// Clone of [`Server::to_back`].
@@ -90,6 +133,8 @@ pub struct IncomingRequest {
 	request_id: RawServerRequestId,
 	/// Parameters of the request.
 	params: jsonrpc::Params,
+	/// Information about the request and the connection it was received on.
+	context: RequestContext,
 }
 
 /// Message that the [`Server`] can send to the background task.
@@ -109,9 +154,12 @@ enum FrontToBack {
 		/// Name of the method.
 		name: String,
 		/// Where to send requests.
-		handler: mpsc::Sender<(RawServerRequestId, jsonrpc::Params)>,
+		queue: MethodQueue<RawServerRequestId>,
 	},
 
+	/// Adds a middleware at the bottom of the stack of middlewares.
+	AddMiddleware(Arc<dyn Middleware>),
+
 	/// Send a response to a request that a client made.
 	AnswerRequest {
 		/// Request to answer.
@@ -130,6 +178,9 @@ enum FrontToBack {
 		subscribe_method: String,
 		/// Name of the method that unregisters the subscription.
 		unsubscribe_method: String,
+		/// If `Some`, where to send each new subscriber along with the parameters of its
+		/// subscribe request.
+		handler: Option<mpsc::UnboundedSender<Subscriber>>,
 	},
 
 	/// Send out a notification to all the clients registered to a subscription.
@@ -139,31 +190,62 @@ enum FrontToBack {
 		/// Notification to send to the subscribed clients.
 		notification: JsonValue,
 	},
+
+	/// Send out a notification to a single subscribed client.
+	SendToSubscriber {
+		/// Subscription of the client.
+		sub_id: RawServerSubscriptionId,
+		/// Notification to send to the client.
+		notification: JsonValue,
+	},
+
+	/// Terminate the subscriptions of all the clients registered to a subscription.
+	CloseSubscribers {
+		/// The value that was passed in [`FrontToBack::RegisterSubscription::unique_id`] earlier.
+		unique_id: usize,
+		/// Reason sent to the clients.
+		reason: jsonrpc::SubscriptionClosedReason,
+	},
+
+	/// Terminate the subscription of a single client.
+	CloseSubscriber {
+		/// Subscription of the client.
+		sub_id: RawServerSubscriptionId,
+		/// Reason sent to the client.
+		reason: jsonrpc::SubscriptionClosedReason,
+	},
+
+	/// Starts shutting down the server. The background task stops accepting requests and
+	/// terminates once all the requests that were dispatched to the handlers have been answered.
+	Shutdown {
+		/// Notified when the background task has terminated.
+		done: oneshot::Sender<()>,
+		/// When to stop waiting for the connections to close.
+		deadline: Instant,
+	},
+
+	/// The shutdown deadline has expired; the background task must terminate without waiting for
+	/// the pending requests.
+	ForceShutdown,
 }
 
 impl Server {
 	/// Initializes a new server.
-	pub async fn new(url: impl AsRef<str>) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
-		let sockaddr: SocketAddr = url.as_ref().parse()?;
-		let transport_server = WsTransportServer::builder(sockaddr).build().await?;
-		let local_addr = *transport_server.local_addr();
-
-		// We use an unbounded channel because the only exchanged messages concern registering
-		// methods. The volume of messages is therefore very low and it doesn't make sense to have
-		// a backpressure mechanism.
-		// TODO: that's not true anymore ^
-		let (to_back, from_front) = mpsc::unbounded();
-
-		async_std::task::spawn(async move {
-			background_task(transport_server.into(), from_front).await;
-		});
+	pub async fn new(
+		url: impl AsRef<str>,
+		config: WsServerConfig,
+	) -> Result<Self, Box<dyn error::Error + Send + Sync>> {
+		Server::builder(url).config(config).build().await
+	}
 
-		Ok(Server {
-			to_back,
-			registered_methods: Arc::new(Mutex::new(Default::default())),
-			next_subscription_unique_id: Arc::new(atomic::AtomicUsize::new(0)),
-			local_addr,
-		})
+	/// Creates a new [`Builder`] that listens on the given address.
+	pub fn builder(url: impl AsRef<str>) -> Builder {
+		Builder {
+			url: url.as_ref().to_owned(),
+			config: WsServerConfig::default(),
+			tls: None,
+			access_control: AccessControl::default(),
+		}
 	}
 
 	/// Local socket address of the underlying transport server.
@@ -171,6 +253,52 @@ impl Server {
 		&self.local_addr
 	}
 
+	/// Returns the metrics of the server. See [`ServerMetrics::encode`] to expose them.
+	pub fn metrics(&self) -> &ServerMetrics {
+		&self.metrics
+	}
+
+	/// Stops the server gracefully.
+	///
+	/// Every active subscription receives a final notification telling that it has been closed
+	/// by the server, and no new requests are processed. The server then waits up to `deadline`
+	/// for the requests that are being handled to be answered. Afterwards, the responses are sent
+	/// out, every connection is closed with a close frame (status code 1000, "normal closure")
+	/// and the listening socket is released. The connections that aren't closed before the
+	/// deadline, for example because the client stopped reading, are dropped.
+	///
+	/// Returns an error if the server was already stopped.
+	pub async fn stop(&self, deadline: Duration) -> Result<(), Error> {
+		let (done_tx, mut done_rx) = oneshot::channel();
+		let shutdown = FrontToBack::Shutdown { done: done_tx, deadline: Instant::now() + deadline };
+		self.to_back.unbounded_send(shutdown).map_err(|_| Error::AlreadyStopped)?;
+
+		let done = match async_std::future::timeout(deadline, &mut done_rx).await {
+			Ok(done) => done,
+			Err(_) => {
+				log::warn!("[frontend]: pending requests not answered before the deadline; forcing shutdown");
+				let _ = self.to_back.unbounded_send(FrontToBack::ForceShutdown);
+				done_rx.await
+			}
+		};
+
+		// The background task drops `done` without notifying it if it was already shutting down.
+		done.map_err(|_| Error::AlreadyStopped)
+	}
+
+	/// Adds a middleware, called around every method call made to the server.
+	///
+	/// Middlewares are called in the order they have been added when a request is received, and
+	/// in the reverse order when it is answered. See [`Middleware`] for more information.
+	///
+	/// Requests received before the middleware has been added don't go through it.
+	pub fn add_middleware(&self, middleware: impl Middleware) -> Result<(), Error> {
+		log::trace!("[frontend]: add_middleware");
+		self.to_back
+			.unbounded_send(FrontToBack::AddMiddleware(Arc::new(middleware)))
+			.map_err(|e| Error::Internal(e.into_send_error()))
+	}
+
 	/// Registers a notification method name towards the server.
 	///
 	/// Clients will then be able to call this method.
@@ -207,19 +335,37 @@ impl Server {
 	///
 	/// Contrary to [`register_notifications`](Server::register_notifications), there is no
 	/// `allow_losses` parameter here. If the handler is too slow to process requests, then the
-	/// server automatically returns an "internal error" to the client.
+	/// server automatically returns a "server busy" error to the client. See also
+	/// [`register_method_with_config`](Server::register_method_with_config).
 	///
 	/// Returns an error if the method name was already registered.
 	pub fn register_method(&self, method_name: String) -> Result<RegisteredMethod, Error> {
+		self.register_method_with_config(method_name, MethodConfig::default())
+	}
+
+	/// Registers a method towards the server, with a custom configuration for its queue of
+	/// requests.
+	///
+	/// See [`register_method`](Server::register_method) for more information.
+	pub fn register_method_with_config(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+	) -> Result<RegisteredMethod, Error> {
 		if !self.registered_methods.lock().insert(method_name.clone()) {
 			return Err(Error::MethodAlreadyRegistered(method_name));
 		}
 
-		log::trace!("[frontend]: register_method={}", method_name);
-		let (tx, rx) = mpsc::channel(32);
+		self.register_reserved_method(method_name, config)
+	}
+
+	/// Registers a method whose name has already been inserted in `registered_methods`.
+	fn register_reserved_method(&self, method_name: String, config: MethodConfig) -> Result<RegisteredMethod, Error> {
+		log::trace!("[frontend]: register_method={}, config={:?}", method_name, config);
+		let (queue, rx) = MethodQueue::new(config);
 
 		self.to_back
-			.unbounded_send(FrontToBack::RegisterMethod { name: method_name, handler: tx })
+			.unbounded_send(FrontToBack::RegisterMethod { name: method_name, queue })
 			.map_err(|e| Error::Internal(e.into_send_error()))?;
 
 		Ok(RegisteredMethod { to_back: self.to_back.clone(), queries_rx: rx })
@@ -236,6 +382,52 @@ impl Server {
 		subscribe_method_name: String,
 		unsubscribe_method_name: String,
 	) -> Result<RegisteredSubscription, Error> {
+		let unique_id = self.register_subscription_inner(subscribe_method_name, unsubscribe_method_name, None)?;
+		Ok(RegisteredSubscription { to_back: self.to_back.clone(), unique_id })
+	}
+
+	/// Registers a subscription towards the server whose subscribers are each served by
+	/// `callback`.
+	///
+	/// Once the client has been told about the start of its subscription, `callback` is called in
+	/// a separate task with the parameters of the subscribe request and a [`SubscriptionSink`]
+	/// that sends notifications to that client only.
+	///
+	/// Returns an error if one of the method names was already registered.
+	pub fn register_subscription_handler<F, Fut>(
+		&self,
+		subscribe_method_name: String,
+		unsubscribe_method_name: String,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(jsonrpc::Params, SubscriptionSink) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = ()> + Send + 'static,
+	{
+		let (tx, mut rx) = mpsc::unbounded();
+		self.register_subscription_inner(subscribe_method_name, unsubscribe_method_name, Some(tx))?;
+
+		let to_back = self.to_back.clone();
+		async_std::task::spawn(async move {
+			// The loop ends when the background task shuts down and drops the sending side.
+			while let Some((sub_id, params, closed)) = rx.next().await {
+				let sink = SubscriptionSink { to_back: to_back.clone(), sub_id, closed };
+				async_std::task::spawn(callback(params, sink));
+			}
+		});
+
+		Ok(())
+	}
+
+	/// Reserves the method names of a subscription and registers it towards the background task.
+	///
+	/// Returns the unique identifier of the subscription.
+	fn register_subscription_inner(
+		&self,
+		subscribe_method_name: String,
+		unsubscribe_method_name: String,
+		handler: Option<mpsc::UnboundedSender<Subscriber>>,
+	) -> Result<usize, Error> {
 		{
 			let mut registered_methods = self.registered_methods.lock();
 
@@ -251,6 +443,19 @@ impl Server {
 			}
 		}
 
+		self.register_reserved_subscription(subscribe_method_name, unsubscribe_method_name, handler)
+	}
+
+	/// Registers a subscription whose method names have already been inserted in
+	/// `registered_methods`.
+	///
+	/// Returns the unique identifier of the subscription.
+	fn register_reserved_subscription(
+		&self,
+		subscribe_method_name: String,
+		unsubscribe_method_name: String,
+		handler: Option<mpsc::UnboundedSender<Subscriber>>,
+	) -> Result<usize, Error> {
 		log::trace!(
 			"[frontend]: server register subscription: subscribe_method={}, unsubscribe_method={}",
 			subscribe_method_name,
@@ -263,10 +468,199 @@ impl Server {
 				unique_id,
 				subscribe_method: subscribe_method_name,
 				unsubscribe_method: unsubscribe_method_name,
+				handler,
 			})
 			.map_err(|e| Error::Internal(e.into_send_error()))?;
 
-		Ok(RegisteredSubscription { to_back: self.to_back.clone(), unique_id })
+		Ok(unique_id)
+	}
+
+	/// Registers a method towards the server and lets the server drive its handler.
+	///
+	/// Each incoming request is processed in a separate task. The parameters of the request are
+	/// deserialized into `P` and passed to `callback`, then the `Result` that the returned
+	/// `Future` resolves to is serialized and sent back to the client.
+	///
+	/// If the parameters can't be deserialized into `P`, the client receives an "invalid params"
+	/// error and `callback` isn't called.
+	///
+	/// Returns an error if the method name was already registered.
+	pub fn register_async_method<F, Fut, P, T, E>(&self, method_name: String, callback: F) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.register_async_method_with_config(method_name, MethodConfig::default(), callback)
+	}
+
+	/// Registers a method towards the server and lets the server drive its handler, with a custom
+	/// configuration for its queue of requests.
+	///
+	/// See [`register_async_method`](Server::register_async_method) for more information.
+	pub fn register_async_method_with_config<F, Fut, P, T, E>(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.register_method_callback(method_name, config, method_callback(callback))
+	}
+
+	/// Same as [`register_async_method`](Server::register_async_method), except that `callback`
+	/// is also passed the [`RequestContext`] of each request.
+	pub fn register_async_method_with_context<F, Fut, P, T, E>(
+		&self,
+		method_name: String,
+		callback: F,
+	) -> Result<(), Error>
+	where
+		F: Fn(P, RequestContext) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<T, E>> + Send + 'static,
+		P: DeserializeOwned + Send + 'static,
+		T: Serialize,
+		E: Into<jsonrpc::Error>,
+	{
+		self.register_method_callback(method_name, MethodConfig::default(), method_callback_with_context(callback))
+	}
+
+	/// Registers all the methods and subscriptions of `module` towards the server.
+	///
+	/// Returns an error and doesn't register anything if one of the method names was already
+	/// registered.
+	pub fn register_module(&self, module: &RpcModule) -> Result<(), Error> {
+		// All the names are reserved at once, so that a concurrent registration can't make this
+		// one fail halfway.
+		{
+			let mut registered_methods = self.registered_methods.lock();
+			if let Some(name) = module.method_names().find(|name| registered_methods.contains(*name)) {
+				return Err(Error::MethodAlreadyRegistered(name.to_owned()));
+			}
+			registered_methods.extend(module.method_names().map(str::to_owned));
+		}
+
+		for (name, method) in module.methods() {
+			let registered = self.register_reserved_method(name.to_owned(), method.config())?;
+			Self::spawn_method_handler(registered, method.callback());
+		}
+
+		for (subscribe_method, unsubscribe_method, subscription) in module.subscriptions() {
+			let unique_id =
+				self.register_reserved_subscription(subscribe_method.to_owned(), unsubscribe_method.to_owned(), None)?;
+			let mut registered = RegisteredSubscription { to_back: self.to_back.clone(), unique_id };
+			let mut notifications = subscription.notifications();
+			async_std::task::spawn(async move {
+				while let Some(value) = notifications.next().await {
+					if registered.send(value).await.is_err() {
+						break;
+					}
+				}
+			});
+		}
+
+		Ok(())
+	}
+
+	/// Registers a method whose requests are each answered by `callback` in a separate task.
+	fn register_method_callback(
+		&self,
+		method_name: String,
+		config: MethodConfig,
+		callback: MethodCallback,
+	) -> Result<(), Error> {
+		let registered = self.register_method_with_config(method_name, config)?;
+		Self::spawn_method_handler(registered, callback);
+		Ok(())
+	}
+
+	/// Spawns a task that answers each request of `method` with `callback` in a separate task.
+	fn spawn_method_handler(method: RegisteredMethod, callback: MethodCallback) {
+		let RegisteredMethod { to_back, queries_rx } = method;
+		async_std::task::spawn(async move {
+			// The loop ends when the background task shuts down and drops the sending side.
+			while let Some((request_id, params, context)) = next_queued_request(&queries_rx).await {
+				let request = IncomingRequest { to_back: to_back.clone(), request_id, params, context };
+				let callback = callback.clone();
+				async_std::task::spawn(async move {
+					let answer = callback(request.params.clone(), request.context.clone()).await;
+					if let Err(err) = request.respond(answer).await {
+						log::error!("[frontend]: failed to respond to request: {:?}", err);
+					}
+				});
+			}
+		});
+	}
+}
+
+impl Builder {
+	/// Sets the configuration of the server.
+	pub fn config(mut self, config: WsServerConfig) -> Self {
+		self.config = config;
+		self
+	}
+
+	/// Serves `wss://` instead of `ws://`, using the given TLS configuration.
+	pub fn tls(self, config: TlsServerConfig) -> Self {
+		self.tls_rustls(config.server_config(&[ALPN_HTTP1]))
+	}
+
+	/// Serves `wss://` instead of `ws://`, using the given `rustls` configuration.
+	///
+	/// Prefer [`tls`](Builder::tls), unless the configuration needs settings that
+	/// [`TlsServerConfig`] doesn't offer. WebSocket connections are upgraded from HTTP/1.1, so the
+	/// configuration shouldn't offer any other ALPN protocol.
+	pub fn tls_rustls(mut self, config: rustls::ServerConfig) -> Self {
+		self.tls = Some(config);
+		self
+	}
+
+	/// Sets the hosts and origins allowed to connect to the server.
+	///
+	/// Clients whose `Host` or `Origin` header isn't allowed are rejected with `403 Forbidden`
+	/// during the handshake. By default, any host and any origin are allowed.
+	pub fn access_control(mut self, access_control: AccessControl) -> Self {
+		self.access_control = access_control;
+		self
+	}
+
+	/// Starts the server.
+	pub async fn build(self) -> Result<Server, Box<dyn error::Error + Send + Sync>> {
+		let sockaddr: SocketAddr = self.url.parse()?;
+		let mut transport_server =
+			WsTransportServer::builder(sockaddr, self.config).access_control(self.access_control);
+		if let Some(tls) = self.tls {
+			transport_server = transport_server.tls(tls);
+		}
+		let transport_server = transport_server.build().await?;
+		let local_addr = *transport_server.local_addr();
+		let metrics = transport_server.metrics().clone();
+
+		// We use an unbounded channel because the only exchanged messages concern registering
+		// methods. The volume of messages is therefore very low and it doesn't make sense to have
+		// a backpressure mechanism.
+		// TODO: that's not true anymore ^
+		let (to_back, from_front) = mpsc::unbounded();
+
+		async_std::task::spawn(async move {
+			background_task(transport_server.into(), from_front).await;
+		});
+
+		Ok(Server {
+			to_back,
+			registered_methods: Arc::new(Mutex::new(Default::default())),
+			next_subscription_unique_id: Arc::new(atomic::AtomicUsize::new(0)),
+			local_addr,
+			metrics,
+		})
 	}
 }
 
@@ -285,13 +679,13 @@ impl RegisteredNotification {
 impl RegisteredMethod {
 	/// Returns the next request.
 	pub async fn next(&mut self) -> IncomingRequest {
-		let (request_id, params) = loop {
-			match self.queries_rx.next().await {
+		let (request_id, params, context) = loop {
+			match next_queued_request(&self.queries_rx).await {
 				Some(v) => break v,
 				None => futures::pending!(),
 			}
 		};
-		IncomingRequest { to_back: self.to_back.clone(), request_id, params }
+		IncomingRequest { to_back: self.to_back.clone(), request_id, params, context }
 	}
 }
 
@@ -303,6 +697,52 @@ impl RegisteredSubscription {
 			.await
 			.map_err(Error::Internal)
 	}
+
+	/// Terminates the subscription of all the subscribing clients, notifying them with `reason`.
+	///
+	/// The clients can subscribe again afterwards.
+	pub async fn close_all(&mut self, reason: jsonrpc::SubscriptionClosedReason) -> Result<(), Error> {
+		self.to_back
+			.send(FrontToBack::CloseSubscribers { unique_id: self.unique_id, reason })
+			.await
+			.map_err(Error::Internal)
+	}
+}
+
+impl SubscriptionSink {
+	/// Sends out a value to the subscribed client.
+	///
+	/// Returns [`Error::SubscriptionClosed`] if the client has unsubscribed or disconnected.
+	pub async fn send(&mut self, value: JsonValue) -> Result<(), Error> {
+		if self.is_closed() {
+			return Err(Error::SubscriptionClosed);
+		}
+		self.to_back
+			.send(FrontToBack::SendToSubscriber { sub_id: self.sub_id, notification: value })
+			.await
+			.map_err(Error::Internal)
+	}
+
+	/// Terminates the subscription, notifying the client with `reason`.
+	///
+	/// Returns [`Error::SubscriptionClosed`] if the client has already unsubscribed or
+	/// disconnected.
+	pub async fn close(mut self, reason: jsonrpc::SubscriptionClosedReason) -> Result<(), Error> {
+		if self.is_closed() {
+			return Err(Error::SubscriptionClosed);
+		}
+		self.to_back.send(FrontToBack::CloseSubscriber { sub_id: self.sub_id, reason }).await.map_err(Error::Internal)
+	}
+
+	/// Returns true if the client has unsubscribed or disconnected.
+	pub fn is_closed(&mut self) -> bool {
+		self.closed.try_recv().is_err()
+	}
+
+	/// Returns a `Future` resolving once the client has unsubscribed or disconnected.
+	pub async fn closed(&mut self) {
+		let _ = (&mut self.closed).await;
+	}
 }
 
 impl IncomingRequest {
@@ -311,6 +751,11 @@ impl IncomingRequest {
 		&self.params
 	}
 
+	/// Returns information about the request and the connection it was received on.
+	pub fn context(&self) -> &RequestContext {
+		&self.context
+	}
+
 	/// Respond to the request.
 	pub async fn respond(mut self, response: impl Into<Result<JsonValue, jsonrpc::Error>>) -> Result<(), Error> {
 		self.to_back
@@ -327,7 +772,7 @@ async fn background_task(mut server: RawServer, mut from_front: mpsc::UnboundedR
 	let mut registered_notifications: HashMap<String, (mpsc::Sender<_>, bool)> = HashMap::new();
 	// List of methods that the user has registered, and the channels to dispatch incoming
 	// requests.
-	let mut registered_methods: HashMap<String, mpsc::Sender<_>> = HashMap::new();
+	let mut registered_methods: HashMap<String, MethodQueue<RawServerRequestId>> = HashMap::new();
 	// For each registered subscription, a subscribe method linked to a unique identifier for
 	// that subscription.
 	let mut subscribe_methods: HashMap<String, usize> = HashMap::new();
@@ -338,12 +783,49 @@ async fn background_task(mut server: RawServer, mut from_front: mpsc::UnboundedR
 	let mut subscribed_clients: HashMap<usize, Vec<RawServerSubscriptionId>> = HashMap::new();
 	// Reversed mapping of `subscribed_clients`. Must always be in sync.
 	let mut active_subscriptions: HashMap<RawServerSubscriptionId, usize> = HashMap::new();
+	// For each subscription registered with a handler, where to send its new subscribers.
+	let mut subscription_handlers: HashMap<usize, mpsc::UnboundedSender<Subscriber>> = HashMap::new();
+	// Subscribers of a handler whose subscription hasn't been confirmed to the client yet, with
+	// the parameters of their subscribe request.
+	let mut pending_subscribers: HashMap<RawServerSubscriptionId, (usize, jsonrpc::Params)> = HashMap::new();
+	// Subscribers that have been dispatched to a handler. Dropping the sender notifies the
+	// corresponding `SubscriptionSink` that the subscription is over.
+	let mut subscription_sinks: HashMap<RawServerSubscriptionId, oneshot::Sender<()>> = HashMap::new();
+	// Number of requests that have been dispatched to a handler and not answered yet.
+	let mut pending_requests: usize = 0;
+	// If the server is shutting down, where to notify that the background task has terminated,
+	// and the shutdown deadline.
+	let mut shutdown: Option<(oneshot::Sender<()>, Instant)> = None;
 
 	loop {
+		if let (Some((_, deadline)), 0) = (&shutdown, pending_requests) {
+			// The connections still open once the deadline has expired are dropped along with
+			// `server`.
+			let remaining = deadline.saturating_duration_since(Instant::now());
+			if async_std::future::timeout(remaining, server.close()).await.is_err() {
+				log::warn!("[backend]: connections not closed before the deadline; dropping them");
+			}
+			log::trace!("[backend]: background_task terminated");
+			let (done, _) = shutdown.take().expect("checked above; qed");
+			let _ = done.send(());
+			return;
+		}
+
 		// We need to do a little transformation in order to destroy the borrow to `client`
 		// and `from_front`.
 		let outcome = {
-			let next_message = from_front.next();
+			// A request waiting for room in the queue of its method is answered like the other
+			// requests if it can't be queued.
+			let next_message = future::select(
+				from_front.next(),
+				future::poll_fn(|cx| poll_waiting_requests(registered_methods.values_mut(), cx)),
+			)
+			.map(|outcome| match outcome {
+				Either::Left((message, _)) => message,
+				Either::Right((request_id, _)) => {
+					Some(FrontToBack::AnswerRequest { request_id, answer: Err(jsonrpc::Error::internal_error()) })
+				}
+			});
 			let next_event = server.next_event();
 			pin_mut!(next_message);
 			pin_mut!(next_event);
@@ -355,25 +837,56 @@ async fn background_task(mut server: RawServer, mut from_front: mpsc::UnboundedR
 
 		match outcome {
 			Either::Left(None) => {
+				// Every handle to the server has been dropped; close the connections properly.
+				server.close().await;
 				log::trace!("[backend]: background_task terminated");
 				return;
 			}
 			Either::Left(Some(FrontToBack::AnswerRequest { request_id, answer })) => {
 				log::trace!("[backend]: answer_request: {:?} id: {:?}", answer, request_id);
-				server.request_by_id(&request_id).unwrap().respond(answer);
+				pending_requests = pending_requests.saturating_sub(1);
+				if let Some(request) = server.request_by_id(&request_id) {
+					request.respond(answer);
+				}
+			}
+			Either::Left(Some(FrontToBack::Shutdown { done, deadline })) => {
+				// If the server is already shutting down, `done` is dropped and the caller gets
+				// notified that the server was already stopped.
+				if shutdown.is_none() {
+					log::trace!("[backend]: shutting down; {} pending request(s)", pending_requests);
+					for (sub_id, _) in active_subscriptions.drain() {
+						if let Some(sub) = server.subscription_by_id(sub_id) {
+							let reason =
+								jsonrpc::SubscriptionClosedReason::Error(jsonrpc::Error::server_shutting_down());
+							sub.close_with_reason(reason).await;
+						}
+					}
+					subscribed_clients.values_mut().for_each(Vec::clear);
+					pending_subscribers.clear();
+					subscription_sinks.clear();
+					shutdown = Some((done, deadline));
+				}
+			}
+			Either::Left(Some(FrontToBack::ForceShutdown)) => {
+				pending_requests = 0;
 			}
 			Either::Left(Some(FrontToBack::RegisterNotifications { name, handler, allow_losses })) => {
 				log::trace!("[backend]: register_notification: {:?}", name);
 				registered_notifications.insert(name, (handler, allow_losses));
 			}
-			Either::Left(Some(FrontToBack::RegisterMethod { name, handler })) => {
+			Either::Left(Some(FrontToBack::RegisterMethod { name, queue })) => {
 				log::trace!("[backend]: register_method: {:?}", name);
-				registered_methods.insert(name, handler);
+				registered_methods.insert(name, queue);
+			}
+			Either::Left(Some(FrontToBack::AddMiddleware(middleware))) => {
+				log::trace!("[backend]: add_middleware");
+				server.add_middleware(middleware);
 			}
 			Either::Left(Some(FrontToBack::RegisterSubscription {
 				unique_id,
 				subscribe_method,
 				unsubscribe_method,
+				handler,
 			})) => {
 				log::trace!(
 					"[backend]: register subscription=id={:?}, subscribe_method:{}, unsubscribe_method={}",
@@ -394,6 +907,9 @@ async fn background_task(mut server: RawServer, mut from_front: mpsc::UnboundedR
 				subscribe_methods.insert(subscribe_method, unique_id);
 				unsubscribe_methods.insert(unsubscribe_method, unique_id);
 				subscribed_clients.insert(unique_id, Vec::new());
+				if let Some(handler) = handler {
+					subscription_handlers.insert(unique_id, handler);
+				}
 			}
 			Either::Left(Some(FrontToBack::SendOutNotif { unique_id, notification })) => {
 				log::trace!("[backend]: preparing response to subscription={:?}", unique_id);
@@ -415,6 +931,45 @@ async fn background_task(mut server: RawServer, mut from_front: mpsc::UnboundedR
 					log::warn!("[backend]: server received invalid subscription={:?}", unique_id);
 				}
 			}
+			Either::Left(Some(FrontToBack::SendToSubscriber { sub_id, notification })) => {
+				log::trace!("[backend]: preparing notification to subscriber={:?}", sub_id);
+				// The subscriber might have gone away since the notification was sent.
+				if subscription_sinks.contains_key(&sub_id) {
+					if let Some(sub) = server.subscription_by_id(sub_id) {
+						sub.push(notification).await;
+					}
+				}
+			}
+			Either::Left(Some(FrontToBack::CloseSubscribers { unique_id, reason })) => {
+				log::trace!("[backend]: closing the subscribers of subscription={:?}: {:?}", unique_id, reason);
+				let clients = subscribed_clients.get_mut(&unique_id).map(mem::take).unwrap_or_default();
+				for sub_id in clients {
+					active_subscriptions.remove(&sub_id);
+					pending_subscribers.remove(&sub_id);
+					subscription_sinks.remove(&sub_id);
+					if let Some(sub) = server.subscription_by_id(sub_id) {
+						sub.close_with_reason(reason.clone()).await;
+					}
+				}
+			}
+			Either::Left(Some(FrontToBack::CloseSubscriber { sub_id, reason })) => {
+				log::trace!("[backend]: closing subscriber={:?}: {:?}", sub_id, reason);
+				if subscription_sinks.remove(&sub_id).is_some() {
+					if let Some(unique_id) = active_subscriptions.remove(&sub_id) {
+						if let Some(clients) = subscribed_clients.get_mut(&unique_id) {
+							clients.retain(|c| *c != sub_id);
+						}
+					}
+					if let Some(sub) = server.subscription_by_id(sub_id) {
+						sub.close_with_reason(reason).await;
+					}
+				}
+			}
+			Either::Right(RawServerEvent::Notification(_)) if shutdown.is_some() => {}
+			Either::Right(RawServerEvent::Request(request)) if shutdown.is_some() => {
+				log::trace!("[backend]: refusing request while shutting down: {:?}", request);
+				request.respond(Err(jsonrpc::Error::server_shutting_down()));
+			}
 			Either::Right(RawServerEvent::Notification(notification)) => {
 				log::trace!("[backend]: received notification: {:?}", notification);
 				if let Some((handler, allow_losses)) = registered_notifications.get_mut(notification.method()) {
@@ -422,25 +977,39 @@ async fn background_task(mut server: RawServer, mut from_front: mpsc::UnboundedR
 					// Note: we just ignore errors. It doesn't make sense logically speaking to
 					// unregister the notification here.
 					if *allow_losses {
-						let _ = handler.send(params.clone()).now_or_never();
+						if !matches!(handler.send(params.clone()).now_or_never(), Some(Ok(()))) {
+							server.metrics().notification_dropped(notification.method());
+						}
 					} else {
 						let _ = handler.send(params.clone()).await;
 					}
 				}
 			}
 			Either::Right(RawServerEvent::Request(request)) => {
-				if let Some(handler) = registered_methods.get_mut(request.method()) {
+				if let Some(queue) = registered_methods.get_mut(request.method()) {
 					log::trace!("[backend]: received request: {:?}", request);
 					let params: &jsonrpc::Params = request.params().into();
-					match handler.send((request.id(), params.clone())).now_or_never() {
-						Some(Ok(())) => {}
-						Some(Err(_)) | None => {
-							request.respond(Err(From::from(jsonrpc::ErrorCode::ServerError(0))));
+					match queue.push((request.id(), params.clone(), request.context().clone())) {
+						Pushed::Queued => pending_requests += 1,
+						Pushed::Refused(err) => request.respond(Err(err)),
+						Pushed::ShedOldest(oldest_id) => {
+							// From here on, `request` can't be used anymore as we need to borrow
+							// `server` in order to answer the oldest request.
+							log::debug!("[backend]: queue full; shedding request {:?}", oldest_id);
+							if let Some(oldest) = server.request_by_id(&oldest_id) {
+								oldest.respond(Err(jsonrpc::Error::server_busy()));
+							}
 						}
 					}
 				} else if let Some(sub_unique_id) = subscribe_methods.get(request.method()) {
 					log::trace!("[backend]: received subscription: {:?}", request);
+					let params: &jsonrpc::Params = request.params().into();
+					let params = params.clone();
 					if let Ok(sub_id) = request.into_subscription() {
+						if subscription_handlers.contains_key(sub_unique_id) {
+							pending_subscribers.insert(sub_id, (*sub_unique_id, params));
+						}
+
 						debug_assert!(subscribed_clients.contains_key(&sub_unique_id));
 						if let Some(clients) = subscribed_clients.get_mut(&sub_unique_id) {
 							debug_assert!(clients.iter().all(|c| *c != sub_id));
@@ -467,6 +1036,8 @@ async fn background_task(mut server: RawServer, mut from_front: mpsc::UnboundedR
 									debug_assert_eq!(s_u_id, *sub_unique_id);
 								}
 							}
+							pending_subscribers.remove(&sub_id);
+							subscription_sinks.remove(&sub_id);
 						}
 						Err(_) => log::error!("Unsubscription of method=\"{}\" failed; The subscription ID must passed as the first argument of Array or \"subscription\" name of Object, got={:?}", request.method(), request.params()),
 					}
@@ -476,14 +1047,26 @@ async fn background_task(mut server: RawServer, mut from_front: mpsc::UnboundedR
 					request.respond(Err(From::from(jsonrpc::ErrorCode::MethodNotFound)));
 				}
 			}
-			Either::Right(RawServerEvent::SubscriptionsReady(_)) => {
-				// We don't really care whether subscriptions are now ready.
+			Either::Right(RawServerEvent::SubscriptionsReady(subscriptions)) => {
+				// Subscribers of a handler are dispatched only now, so that the notifications
+				// they are sent aren't dropped.
+				for sub_id in subscriptions {
+					if let Some((unique_id, params)) = pending_subscribers.remove(&sub_id) {
+						let (closed_tx, closed_rx) = oneshot::channel();
+						let handler = subscription_handlers.get(&unique_id).expect("only inserted for handlers; qed");
+						if handler.unbounded_send((sub_id, params, closed_rx)).is_ok() {
+							subscription_sinks.insert(sub_id, closed_tx);
+						}
+					}
+				}
 			}
 			Either::Right(RawServerEvent::SubscriptionsClosed(subscriptions)) => {
 				log::trace!("[backend]: close subscriptions: {:?}", subscriptions);
 				// Remove all the subscriptions from `active_subscriptions` and
 				// `subscribed_clients`.
 				for sub_id in subscriptions {
+					pending_subscribers.remove(&sub_id);
+					subscription_sinks.remove(&sub_id);
 					if let Some(unique_id) = active_subscriptions.remove(&sub_id) {
 						debug_assert!(subscribed_clients.contains_key(&unique_id));
 						if let Some(clients) = subscribed_clients.get_mut(&unique_id) {
@@ -1,18 +1,28 @@
 #![cfg(test)]
 
-use crate::WsServer;
+use crate::{AccessControlBuilder, KeepAliveConfig, WsServer, WsServerConfig};
 use futures::channel::oneshot::{self, Sender};
 use futures::future::FutureExt;
+use futures::stream::StreamExt;
 use futures::{pin_mut, select};
 use jsonrpsee_test_utils::helpers::*;
+use jsonrpsee_test_utils::tls::{client_config, LOCALHOST_CERT, LOCALHOST_KEY};
 use jsonrpsee_test_utils::types::{Id, WebSocketTestClient};
-use jsonrpsee_types::{error::Error, jsonrpc::JsonValue};
+use jsonrpsee_types::{
+	error::Error,
+	jsonrpc::{self, JsonValue},
+	server::{MethodConfig, Middleware, OverflowPolicy, RequestContext},
+};
+use jsonrpsee_utils::tls::{TlsServerConfig, ALPN_HTTP1};
+use jsonrpsee_ws_client::{WsClient, WsConfig};
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 /// Spawns a dummy `JSONRPC v2 WebSocket`
 /// It has two hardcoded methods "say_hello" and "add", one hardcoded notification "notif"
 pub async fn server(server_started: Sender<SocketAddr>) {
-	let server = WsServer::new("127.0.0.1:0").await.unwrap();
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
 	let mut hello = server.register_method("say_hello".to_owned()).unwrap();
 	let mut add = server.register_method("add".to_owned()).unwrap();
 	let mut notif = server.register_notification("notif".to_owned(), false).unwrap();
@@ -50,6 +60,46 @@ pub async fn server(server_started: Sender<SocketAddr>) {
 	}
 }
 
+/// Method, outcome and duration of a call seen by a [`Recorder`].
+type RecordedCall = (String, Result<JsonValue, jsonrpc::ErrorCode>, Duration);
+
+/// Middleware that records the answered calls and rejects the calls to `secret`.
+#[derive(Clone, Default)]
+struct Recorder {
+	calls: Arc<Mutex<Vec<RecordedCall>>>,
+}
+
+impl Middleware for Recorder {
+	fn on_request(&self, method: &str, _: &jsonrpc::Params, _: &RequestContext) -> Result<(), jsonrpc::Error> {
+		if method == "secret" {
+			return Err(jsonrpc::Error::invalid_request());
+		}
+		Ok(())
+	}
+
+	fn on_response(
+		&self,
+		method: &str,
+		_: &jsonrpc::Params,
+		_: &RequestContext,
+		result: &JsonValue,
+		elapsed: Duration,
+	) {
+		self.calls.lock().unwrap().push((method.to_owned(), Ok(result.clone()), elapsed));
+	}
+
+	fn on_error(
+		&self,
+		method: &str,
+		_: &jsonrpc::Params,
+		_: &RequestContext,
+		error: &jsonrpc::Error,
+		elapsed: Duration,
+	) {
+		self.calls.lock().unwrap().push((method.to_owned(), Err(error.code.clone()), elapsed));
+	}
+}
+
 #[tokio::test]
 async fn single_method_call_works() {
 	let (server_started_tx, server_started_rx) = oneshot::channel::<SocketAddr>();
@@ -126,7 +176,7 @@ async fn invalid_request_object() {
 
 #[tokio::test]
 async fn register_methods_works() {
-	let server = WsServer::new("127.0.0.1:0").await.unwrap();
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
 	assert!(server.register_method("say_hello".to_owned()).is_ok());
 	assert!(server.register_method("say_hello".to_owned()).is_err());
 	assert!(server.register_notification("notif".to_owned(), false).is_ok());
@@ -141,7 +191,7 @@ async fn register_methods_works() {
 
 #[tokio::test]
 async fn register_same_subscribe_unsubscribe_is_err() {
-	let server = WsServer::new("127.0.0.1:0").await.unwrap();
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
 	assert!(matches!(
 		server.register_subscription("subscribe_hello".to_owned(), "subscribe_hello".to_owned()),
 		Err(Error::MethodAlreadyRegistered(_))
@@ -177,3 +227,508 @@ async fn invalid_request_should_not_close_connection() {
 	let response = client.send_request_text(request).await.unwrap();
 	assert_eq!(response, ok_response(JsonValue::String("hello".to_owned()), Id::Num(33)));
 }
+
+#[tokio::test]
+async fn async_method_call_works() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	server
+		.register_async_method("add".to_owned(), |(a, b): (u64, u64)| async move { Ok::<_, jsonrpc::Error>(a + b) })
+		.unwrap();
+	server
+		.register_async_method(
+			"fail".to_owned(),
+			|_: ()| async move { Err::<(), _>(jsonrpc::ErrorCode::InternalError) },
+		)
+		.unwrap();
+	let mut client = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+
+	let req = r#"{"jsonrpc":"2.0","method":"add","params":[1, 2],"id":1}"#;
+	let response = client.send_request_text(req).await.unwrap();
+	assert_eq!(response, ok_response(JsonValue::Number(3.into()), Id::Num(1)));
+
+	let req = r#"{"jsonrpc":"2.0","method":"fail","id":2}"#;
+	let response = client.send_request_text(req).await.unwrap();
+	assert_eq!(response, internal_error(Id::Num(2)));
+
+	let req = r#"{"jsonrpc":"2.0","method":"add","params":["1", 2],"id":3}"#;
+	let response = client.send_request_text(req).await.unwrap();
+	assert_eq!(
+		response,
+		r#"{"jsonrpc":"2.0","error":{"code":-32602,"message":"Invalid params: invalid type: string \"1\", expected u64."},"id":3}"#
+	);
+}
+
+#[tokio::test]
+async fn request_context_works() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	server
+		.register_async_method_with_context("context".to_owned(), |_: (), context: RequestContext| async move {
+			// Counts the requests made on the connection.
+			let count = context.extensions().get::<u64>().unwrap_or(0) + 1;
+			context.extensions().insert(count);
+			Ok::<_, jsonrpc::Error>((
+				context.id().clone(),
+				context.remote_addr().map(|addr| addr.ip().to_string()),
+				context.connection_id(),
+				count,
+			))
+		})
+		.unwrap();
+	let mut first = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+	let mut second = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+
+	let req = r#"{"jsonrpc":"2.0","method":"context","id":1}"#;
+	let response: JsonValue = serde_json::from_str(&first.send_request_text(req).await.unwrap()).unwrap();
+	assert_eq!(response["result"][0], 1);
+	assert_eq!(response["result"][1], "127.0.0.1");
+	assert_eq!(response["result"][3], 1);
+	let first_connection_id = response["result"][2].clone();
+	assert!(first_connection_id.is_u64());
+
+	let req = r#"{"jsonrpc":"2.0","method":"context","id":2}"#;
+	let response: JsonValue = serde_json::from_str(&first.send_request_text(req).await.unwrap()).unwrap();
+	assert_eq!(response["result"][2], first_connection_id);
+	assert_eq!(response["result"][3], 2);
+
+	let response: JsonValue = serde_json::from_str(&second.send_request_text(req).await.unwrap()).unwrap();
+	assert_ne!(response["result"][2], first_connection_id);
+	assert_eq!(response["result"][3], 1);
+}
+
+#[tokio::test]
+async fn middleware_works() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	let recorder = Recorder::default();
+	server.add_middleware(recorder.clone()).unwrap();
+	server
+		.register_async_method("sleep".to_owned(), |(ms,): (u64,)| async move {
+			async_std::task::sleep(Duration::from_millis(ms)).await;
+			Ok::<_, jsonrpc::Error>(ms)
+		})
+		.unwrap();
+	server.register_async_method("secret".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>(42) }).unwrap();
+	let mut client = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+
+	let response =
+		client.send_request_text(r#"{"jsonrpc":"2.0","method":"sleep","params":[100],"id":1}"#).await.unwrap();
+	assert_eq!(response, ok_response(JsonValue::Number(100.into()), Id::Num(1)));
+	let response = client.send_request_text(r#"{"jsonrpc":"2.0","method":"secret","id":2}"#).await.unwrap();
+	assert_eq!(response, invalid_request(Id::Num(2)));
+	let response = client.send_request_text(r#"{"jsonrpc":"2.0","method":"unknown","id":3}"#).await.unwrap();
+	assert_eq!(response, method_not_found(Id::Num(3)));
+
+	let calls = recorder.calls.lock().unwrap().clone();
+	assert_eq!(calls.len(), 3);
+	assert_eq!((calls[0].0.as_str(), &calls[0].1), ("sleep", &Ok(JsonValue::Number(100.into()))));
+	assert!(calls[0].2 >= Duration::from_millis(100));
+	assert_eq!((calls[1].0.as_str(), &calls[1].1), ("secret", &Err(jsonrpc::ErrorCode::InvalidRequest)));
+	assert_eq!((calls[2].0.as_str(), &calls[2].1), ("unknown", &Err(jsonrpc::ErrorCode::MethodNotFound)));
+}
+
+#[tokio::test]
+async fn tls_works() {
+	let tls = TlsServerConfig::from_pem(LOCALHOST_CERT, LOCALHOST_KEY).unwrap();
+	let server = WsServer::builder("127.0.0.1:0").tls(tls).build().await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let port = server.local_addr().port();
+
+	let url = format!("wss://localhost:{}", port);
+	let client = WsClient::with_tls_config(&url, WsConfig::default(), client_config(&[])).await.unwrap();
+	let response: String = client.request("say_hello", jsonrpc::Params::None).await.unwrap();
+	assert_eq!(response, "hello");
+
+	// The self-signed certificate isn't trusted by default.
+	assert!(WsClient::new(&url, WsConfig::default()).await.is_err());
+	// Plain text connections are rejected.
+	let client = WsClient::new(format!("ws://127.0.0.1:{}", port), WsConfig::default()).await;
+	assert!(client.is_err());
+}
+
+#[tokio::test]
+async fn tls_rustls_works() {
+	let tls = TlsServerConfig::from_pem(LOCALHOST_CERT, LOCALHOST_KEY).unwrap().server_config(&[ALPN_HTTP1]);
+	let server = WsServer::builder("127.0.0.1:0").tls_rustls(tls).build().await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+
+	let url = format!("wss://localhost:{}", server.local_addr().port());
+	let client = WsClient::with_tls_config(&url, WsConfig::default(), client_config(&[])).await.unwrap();
+	let response: String = client.request("say_hello", jsonrpc::Params::None).await.unwrap();
+	assert_eq!(response, "hello");
+}
+
+#[tokio::test]
+async fn access_control_works() {
+	let access_control = AccessControlBuilder::new()
+		.allow_host("localhost:*".into())
+		.cors_allow_origin("http://allowed.com".into())
+		.build();
+	let server = WsServer::builder("127.0.0.1:0").access_control(access_control).build().await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let addr = *server.local_addr();
+	let req = r#"{"jsonrpc":"2.0","method":"say_hello","id":1}"#;
+
+	// Allowed host and origin, and allowed host without origin.
+	for origin in &[Some("http://allowed.com"), None] {
+		let mut client = WebSocketTestClient::with_host_and_origin(addr, "localhost:1234", *origin).await.unwrap();
+		let response = client.send_request_text(req).await.unwrap();
+		assert_eq!(response, ok_response(JsonValue::String("hello".to_owned()), Id::Num(1)));
+	}
+
+	// Disallowed origin or host.
+	for (host, origin) in &[("localhost:1234", Some("http://denied.com")), ("example.com", None)] {
+		let err = WebSocketTestClient::with_host_and_origin(addr, host, *origin).await.err().unwrap();
+		assert!(err.to_string().contains("403"), "unexpected error: {}", err);
+	}
+}
+
+#[tokio::test]
+async fn metrics_work() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let _sub = server.register_subscription("subscribe_hello".to_owned(), "unsubscribe_hello".to_owned()).unwrap();
+	let mut client = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+
+	let req = r#"[{"jsonrpc":"2.0","method":"say_hello","id":1},{"jsonrpc":"2.0","method":"unknown","id":2}]"#;
+	client.send_request_text(req).await.unwrap();
+	let req = r#"{"jsonrpc":"2.0","method":"subscribe_hello","id":3}"#;
+	client.send_request_text(req).await.unwrap();
+
+	let encoded = server.metrics().encode();
+	for line in &[
+		"jsonrpsee_ws_requests_total{method=\"say_hello\"} 1",
+		"jsonrpsee_ws_requests_total{method=\"subscribe_hello\"} 1",
+		"jsonrpsee_ws_errors_total{method=\"unknown\",code=\"-32601\"} 1",
+		"jsonrpsee_ws_request_duration_seconds_count{method=\"say_hello\"} 1",
+		"jsonrpsee_ws_batch_size_count 1",
+		"jsonrpsee_ws_batch_size_sum 2",
+		"jsonrpsee_ws_connections 1",
+		"jsonrpsee_ws_subscriptions 1",
+	] {
+		assert!(encoded.lines().any(|l| l == *line), "missing {:?} in:\n{}", line, encoded);
+	}
+}
+
+#[tokio::test]
+async fn subscription_handler_works() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	let (closed_tx, mut closed_rx) = futures::channel::mpsc::unbounded();
+	server
+		.register_subscription_handler(
+			"subscribe_counter".to_owned(),
+			"unsubscribe_counter".to_owned(),
+			move |params, mut sink| {
+				let closed_tx = closed_tx.clone();
+				async move {
+					let [start]: [u64; 1] = params.parse().unwrap();
+					for n in start.. {
+						if sink.send(n.into()).await.is_err() {
+							break;
+						}
+						async_std::task::sleep(Duration::from_millis(10)).await;
+					}
+					assert!(sink.is_closed());
+					closed_tx.unbounded_send(start).unwrap();
+				}
+			},
+		)
+		.unwrap();
+	let addr = *server.local_addr();
+
+	// Each subscriber receives the notifications built from its own parameters.
+	let mut clients = Vec::new();
+	for start in &[0u64, 100] {
+		let mut client = WebSocketTestClient::new(addr).await.unwrap();
+		let req = format!(r#"{{"jsonrpc":"2.0","method":"subscribe_counter","params":[{}],"id":1}}"#, start);
+		let response: JsonValue = serde_json::from_str(&client.send_request_text(req).await.unwrap()).unwrap();
+		let sub_id = response["result"].as_str().unwrap().to_owned();
+		for n in *start..*start + 3 {
+			let notif: JsonValue = serde_json::from_str(&client.receive().await.unwrap()).unwrap();
+			assert_eq!(notif["method"], "subscribe_counter");
+			assert_eq!(notif["params"]["subscription"], sub_id.as_str());
+			assert_eq!(notif["params"]["result"], n);
+		}
+		clients.push((client, sub_id));
+	}
+
+	// The sink is closed once its client unsubscribes or disconnects.
+	let (mut first, sub_id) = clients.remove(0);
+	let req = format!(r#"{{"jsonrpc":"2.0","method":"unsubscribe_counter","params":["{}"],"id":2}}"#, sub_id);
+	first.send(&req).await.unwrap();
+	assert_eq!(closed_rx.next().await, Some(0));
+
+	drop(clients);
+	assert_eq!(closed_rx.next().await, Some(100));
+}
+
+#[tokio::test]
+async fn close_all_subscribers_works() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	let mut sub = server.register_subscription("subscribe_hello".to_owned(), "unsubscribe_hello".to_owned()).unwrap();
+	let mut client = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+
+	let req = r#"{"jsonrpc":"2.0","method":"subscribe_hello","id":1}"#;
+	let response: JsonValue = serde_json::from_str(&client.send_request_text(req).await.unwrap()).unwrap();
+	let sub_id = response["result"].as_str().unwrap().to_owned();
+
+	sub.close_all(jsonrpc::SubscriptionClosedReason::Completed).await.unwrap();
+	assert_eq!(
+		client.receive().await.unwrap(),
+		format!(
+			r#"{{"jsonrpc":"2.0","method":"subscribe_hello","params":{{"subscription":"{}","reason":"completed"}}}}"#,
+			sub_id
+		)
+	);
+
+	// The closed subscription doesn't receive notifications anymore, but the client can
+	// subscribe again.
+	sub.send(JsonValue::Null).await.unwrap();
+	let response: JsonValue = serde_json::from_str(&client.send_request_text(req).await.unwrap()).unwrap();
+	let sub_id = response["result"].as_str().unwrap().to_owned();
+	sub.send(JsonValue::Null).await.unwrap();
+	let notif: JsonValue = serde_json::from_str(&client.receive().await.unwrap()).unwrap();
+	assert_eq!(notif["params"]["subscription"], JsonValue::String(sub_id));
+}
+
+#[tokio::test]
+async fn stop_works() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	let _sub = server.register_subscription("subscribe_hello".to_owned(), "unsubscribe_hello".to_owned()).unwrap();
+	let addr = *server.local_addr();
+	let mut client = WebSocketTestClient::new(addr).await.unwrap();
+
+	let req = r#"{"jsonrpc":"2.0","method":"subscribe_hello","id":1}"#;
+	let response: JsonValue = serde_json::from_str(&client.send_request_text(req).await.unwrap()).unwrap();
+	let sub_id = response["result"].as_str().unwrap().to_owned();
+
+	server.stop(Duration::from_secs(1)).await.unwrap();
+
+	let closed = client.receive().await.unwrap();
+	assert_eq!(
+		closed,
+		format!(
+			r#"{{"jsonrpc":"2.0","method":"subscribe_hello","params":{{"subscription":"{}","reason":{{"error":{{"code":-32001,"message":"Server is shutting down"}}}}}}}}"#,
+			sub_id
+		)
+	);
+	// The connection is closed with a close frame and not just dropped.
+	let err = client.receive().await.unwrap_err();
+	assert!(matches!(err.downcast_ref::<soketto::connection::Error>(), Some(soketto::connection::Error::Closed)));
+
+	assert!(matches!(server.stop(Duration::from_secs(1)).await, Err(Error::AlreadyStopped)));
+	assert!(WebSocketTestClient::new(addr).await.is_err());
+	assert!(std::net::TcpListener::bind(addr).is_ok());
+}
+
+#[tokio::test]
+async fn stop_drops_unresponsive_connections() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	server
+		.register_async_method("big".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("a".repeat(1 << 20)) })
+		.unwrap();
+	let addr = *server.local_addr();
+
+	// The client never reads the responses, so that the server ends up blocked writing to it.
+	let mut client = WebSocketTestClient::new(addr).await.unwrap();
+	for id in 0..32 {
+		client.send(format!(r#"{{"jsonrpc":"2.0","method":"big","id":{}}}"#, id)).await.unwrap();
+	}
+	tokio::time::sleep(Duration::from_millis(200)).await;
+
+	let stop = tokio::time::timeout(Duration::from_secs(5), server.stop(Duration::from_millis(500)));
+	assert!(stop.await.unwrap().is_ok());
+	let drained = tokio::time::timeout(Duration::from_secs(5), async { while client.receive().await.is_ok() {} });
+	assert!(drained.await.is_ok());
+	assert!(std::net::TcpListener::bind(addr).is_ok());
+}
+
+#[tokio::test]
+async fn stop_aborts_pending_handshakes() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	// The client never sends its handshake request.
+	let _idle = std::net::TcpStream::connect(server.local_addr()).unwrap();
+	tokio::time::sleep(Duration::from_millis(50)).await;
+
+	let stop = tokio::time::timeout(Duration::from_secs(1), server.stop(Duration::from_secs(10)));
+	assert!(stop.await.unwrap().is_ok());
+}
+
+#[tokio::test]
+async fn stop_waits_for_in_flight_requests() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	server
+		.register_async_method("sleep".to_owned(), |ms: [u64; 1]| async move {
+			async_std::task::sleep(Duration::from_millis(ms[0])).await;
+			Ok::<_, jsonrpc::Error>(ms[0])
+		})
+		.unwrap();
+	let mut fast_client = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+	let mut slow_client = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+
+	let fast = fast_client.send_request_text(r#"{"jsonrpc":"2.0","method":"sleep","params":[200],"id":1}"#);
+	let slow = slow_client.send_request_text(r#"{"jsonrpc":"2.0","method":"sleep","params":[60000],"id":2}"#);
+	let stop = async {
+		async_std::task::sleep(Duration::from_millis(50)).await;
+		server.stop(Duration::from_secs(1)).await
+	};
+	let (fast, slow, stop) = futures::join!(fast, slow, stop);
+
+	assert_eq!(fast.unwrap(), ok_response(JsonValue::Number(200.into()), Id::Num(1)));
+	assert!(slow.is_err());
+	assert!(stop.is_ok());
+}
+
+#[tokio::test]
+async fn method_queue_overflow_policies() {
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig::default()).await.unwrap();
+	let config = |overflow_policy| MethodConfig { queue_capacity: 1, overflow_policy };
+	let mut reject = server.register_method_with_config("reject".to_owned(), config(OverflowPolicy::Reject)).unwrap();
+	let mut shed = server.register_method_with_config("shed".to_owned(), config(OverflowPolicy::DropOldest)).unwrap();
+	let mut wait = server.register_method_with_config("wait".to_owned(), config(OverflowPolicy::Wait)).unwrap();
+	let addr = *server.local_addr();
+	let call = |method: &str, id: u64| {
+		let req = format!(r#"{{"jsonrpc":"2.0","method":"{}","id":{}}}"#, method, id);
+		tokio::spawn(async move {
+			let mut client = WebSocketTestClient::new(addr).await.unwrap();
+			client.send_request_text(req).await.unwrap()
+		})
+	};
+	let busy = |id: u64| {
+		format!(
+			r#"{{"jsonrpc":"2.0","error":{{"code":-32002,"message":"Server is busy, try again later"}},"id":{}}}"#,
+			id
+		)
+	};
+
+	// The second request is rejected because the first one fills the queue.
+	let first = call("reject", 1);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	assert_eq!(call("reject", 2).await.unwrap(), busy(2));
+	reject.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(first.await.unwrap(), ok_response(JsonValue::Null, Id::Num(1)));
+
+	// The first request is shed to make room for the second one.
+	let first = call("shed", 1);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	let second = call("shed", 2);
+	assert_eq!(first.await.unwrap(), busy(1));
+	shed.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(second.await.unwrap(), ok_response(JsonValue::Null, Id::Num(2)));
+
+	// Both requests are processed, and the other methods are served while the second one waits.
+	let first = call("wait", 1);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	let second = call("wait", 2);
+	tokio::time::sleep(Duration::from_millis(50)).await;
+	let other = call("reject", 3);
+	reject.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(other.await.unwrap(), ok_response(JsonValue::Null, Id::Num(3)));
+	wait.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	wait.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(first.await.unwrap(), ok_response(JsonValue::Null, Id::Num(1)));
+	assert_eq!(second.await.unwrap(), ok_response(JsonValue::Null, Id::Num(2)));
+}
+
+#[tokio::test]
+async fn max_request_body_size_works() {
+	let config = WsServerConfig { max_request_body_size: 100, ..Default::default() };
+	let server = WsServer::new("127.0.0.1:0", config).await.unwrap();
+	server
+		.register_async_method("echo".to_owned(), |s: [String; 1]| async move { Ok::<_, jsonrpc::Error>(s) })
+		.unwrap();
+	let mut client = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+
+	let response = client.send_request_text(r#"{"jsonrpc":"2.0","method":"echo","params":["a"],"id":1}"#).await;
+	assert_eq!(response.unwrap(), ok_response(JsonValue::Array(vec!["a".into()]), Id::Num(1)));
+
+	let req = format!(r#"{{"jsonrpc":"2.0","method":"echo","params":["{}"],"id":2}}"#, "a".repeat(100));
+	assert_eq!(
+		client.send_request_text(req).await.unwrap(),
+		r#"{"jsonrpc":"2.0","error":{"code":-32003,"message":"Request is too large"},"id":null}"#
+	);
+	let err = client.receive().await.unwrap_err();
+	assert!(matches!(err.downcast_ref::<soketto::connection::Error>(), Some(soketto::connection::Error::Closed)));
+}
+
+#[tokio::test]
+async fn max_connections_works() {
+	let config = WsServerConfig { max_connections: 1, ..Default::default() };
+	let server = WsServer::new("127.0.0.1:0", config).await.unwrap();
+	let addr = *server.local_addr();
+
+	let mut client = WebSocketTestClient::new(addr).await.unwrap();
+	let err = WebSocketTestClient::new(addr).await.err().unwrap();
+	assert!(err.to_string().contains("503"), "unexpected error: {}", err);
+
+	// The slot is released once the first client is gone.
+	client.close().await.unwrap();
+	tokio::time::sleep(Duration::from_millis(100)).await;
+	assert!(WebSocketTestClient::new(addr).await.is_ok());
+}
+
+#[tokio::test]
+async fn max_in_flight_requests_works() {
+	let config = WsServerConfig { max_in_flight_requests: 1, ..Default::default() };
+	let server = WsServer::new("127.0.0.1:0", config).await.unwrap();
+	let mut block = server.register_method("block".to_owned()).unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let _sub = server.register_subscription("subscribe_hello".to_owned(), "unsubscribe_hello".to_owned()).unwrap();
+	let mut client = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+	let busy = r#"{"code":-32002,"message":"Server is busy, try again later"}"#;
+
+	client.send(r#"{"jsonrpc":"2.0","method":"block","id":1}"#).await.unwrap();
+	let response = client.send_request_text(r#"{"jsonrpc":"2.0","method":"say_hello","id":2}"#).await.unwrap();
+	assert_eq!(response, format!(r#"{{"jsonrpc":"2.0","error":{},"id":2}}"#, busy));
+	let batch = r#"[{"jsonrpc":"2.0","method":"say_hello","id":3},{"jsonrpc":"2.0","method":"say_hello"}]"#;
+	let response = client.send_request_text(batch).await.unwrap();
+	assert_eq!(response, format!(r#"[{{"jsonrpc":"2.0","error":{},"id":3}}]"#, busy));
+
+	block.next().await.respond(Ok(JsonValue::Null)).await.unwrap();
+	assert_eq!(client.receive().await.unwrap(), ok_response(JsonValue::Null, Id::Num(1)));
+
+	// Active subscriptions don't count as requests in flight.
+	let response = client.send_request_text(r#"{"jsonrpc":"2.0","method":"subscribe_hello","id":4}"#).await.unwrap();
+	assert!(response.contains(r#""result""#), "unexpected response: {}", response);
+	let response = client.send_request_text(r#"{"jsonrpc":"2.0","method":"say_hello","id":5}"#).await.unwrap();
+	assert_eq!(response, ok_response(JsonValue::String("hello".to_owned()), Id::Num(5)));
+}
+
+#[tokio::test]
+async fn keep_alive_works() {
+	let keep_alive = KeepAliveConfig {
+		ping_interval: Some(Duration::from_millis(50)),
+		pong_timeout: Duration::from_millis(100),
+		idle_timeout: None,
+	};
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig { keep_alive, ..Default::default() }).await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let addr = *server.local_addr();
+
+	// This client answers the pings in the background.
+	let client = WsClient::new(format!("ws://{}", addr), WsConfig::default()).await.unwrap();
+	// This one doesn't read from the connection, hence doesn't answer the pings.
+	let mut dead_client = WebSocketTestClient::new(addr).await.unwrap();
+	tokio::time::sleep(Duration::from_millis(300)).await;
+
+	let response: String = client.request("say_hello", jsonrpc::Params::None).await.unwrap();
+	assert_eq!(response, "hello");
+	assert!(dead_client.send_request_text(r#"{"jsonrpc":"2.0","method":"say_hello","id":1}"#).await.is_err());
+}
+
+#[tokio::test]
+async fn idle_timeout_works() {
+	let keep_alive = KeepAliveConfig { idle_timeout: Some(Duration::from_millis(200)), ..Default::default() };
+	let server = WsServer::new("127.0.0.1:0", WsServerConfig { keep_alive, ..Default::default() }).await.unwrap();
+	server.register_async_method("say_hello".to_owned(), |_: ()| async { Ok::<_, jsonrpc::Error>("hello") }).unwrap();
+	let mut client = WebSocketTestClient::new(*server.local_addr()).await.unwrap();
+
+	// Exchanging messages keeps the connection open.
+	for id in 0..3 {
+		tokio::time::sleep(Duration::from_millis(100)).await;
+		let req = format!(r#"{{"jsonrpc":"2.0","method":"say_hello","id":{}}}"#, id);
+		let response = client.send_request_text(req).await.unwrap();
+		assert_eq!(response, ok_response(JsonValue::String("hello".to_owned()), Id::Num(id)));
+	}
+
+	let err = client.receive().await.unwrap_err();
+	assert!(matches!(err.downcast_ref::<soketto::connection::Error>(), Some(soketto::connection::Error::Closed)));
+}
@@ -24,19 +24,35 @@
 // IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER
 // DEALINGS IN THE SOFTWARE.
 
-use jsonrpsee_types::jsonrpc;
+use crate::handshake;
+use jsonrpsee_types::{
+	jsonrpc,
+	server::{ConnectionExtensions, RequestContext, ServerMetrics},
+	ws::{KeepAlive, KeepAliveEvent, WsServerConfig},
+};
+use jsonrpsee_utils::http::access_control::AccessControl;
 
 use async_std::net::{TcpListener, TcpStream};
-use futures::{channel::mpsc, prelude::*};
+use futures::{
+	channel::{mpsc, oneshot},
+	prelude::*,
+};
 use soketto::handshake::{server::Response, Server};
 use std::{
 	collections::HashMap,
+	convert::TryFrom as _,
 	fmt, io,
 	net::SocketAddr,
 	pin::Pin,
 	sync::{atomic, Arc},
+	time::{Duration, Instant},
 };
 
+/// Time after which a TLS or WebSocket handshake that hasn't completed is aborted.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+/// Time during which a connection closed because of a too large message is kept open.
+const CLOSE_LINGER: Duration = Duration::from_secs(1);
+
 /// Event that the [`TransportServer`] can generate.
 #[derive(Debug, PartialEq)]
 pub enum TransportServerEvent<T> {
@@ -73,37 +89,69 @@ pub enum TransportServerEvent<T> {
 //
 // If a task finishes, it must return the list of requests that were assigned to it so that they
 // get removed from [`WsTransportServer::to_connections`].
+//
+// When the server is closed, the tasks are notified through [`WsTransportServer::stop_rx`]. They
+// then send out the messages that are already queued, send a close frame to the client and
+// finish.
 pub struct WsTransportServer {
 	/// Local socket address.
 	local_addr: SocketAddr,
+	/// Configuration of the server.
+	config: WsServerConfig,
 	/// List of events to for `next_request` to immediately produce.
 	pending_events: Vec<TransportServerEvent<WsRequestId>>,
-	/// Endpoint for incoming TCP sockets.
-	listener: TcpListener,
+	/// Endpoint for incoming TCP sockets. `None` if the server has been closed.
+	listener: Option<TcpListener>,
+	/// Performs the TLS handshake on the incoming sockets. `None` if the server doesn't use TLS.
+	tls_acceptor: Option<async_tls::TlsAcceptor>,
+	/// Allowed hosts and origins. Cloned in each member of
+	/// [`WsTransportServer::connections_tasks`].
+	access_control: AccessControl,
 	/// Next identifier to assign to a request. Shared amongst all the tasks in the server so that
 	/// they all assign from the same pool.
 	next_request_id: Arc<atomic::AtomicU64>,
+	/// Next identifier to assign to a connection.
+	next_connection_id: u64,
 	/// Events received from connections.
 	from_connections: mpsc::Receiver<BackToFront>,
 	/// Sending side of [`WsTransportServer::from_connections`]. Cloned in each member of
 	/// [`WsTransportServer::connections_tasks`].
 	to_front: mpsc::Sender<BackToFront>,
-	/// List of connections, and senders to send them messages.
-	to_connections: HashMap<WsRequestId, mpsc::Sender<FrontToBack>>,
+	/// List of connections, and senders to send them messages. Each request is also associated
+	/// with its context, and with whether something has already been sent back on it.
+	to_connections: HashMap<WsRequestId, (mpsc::Sender<FrontToBack>, RequestContext, bool)>,
 	/// List of connections. Must be processed for the system to work. When a task finishes, it
 	/// returns the list of pending requests that should now be closed.
 	connections_tasks: stream::FuturesUnordered<Pin<Box<dyn Future<Output = Vec<WsRequestId>> + Send>>>,
+	/// Sending side of [`WsTransportServer::stop_rx`]. Taken when the server is closed.
+	stop_tx: Option<oneshot::Sender<()>>,
+	/// Resolves when the server is closed. Cloned in each member of
+	/// [`WsTransportServer::connections_tasks`].
+	stop_rx: future::Shared<oneshot::Receiver<()>>,
+	/// Metrics of the server.
+	metrics: ServerMetrics,
 }
 
 /// Message sent from a per-connection task to the main frontend.
 enum BackToFront {
-	NewRequest { id: WsRequestId, body: jsonrpc::Request, sender: mpsc::Sender<FrontToBack> },
+	NewRequest {
+		id: WsRequestId,
+		body: jsonrpc::Request,
+		sender: mpsc::Sender<FrontToBack>,
+		/// Information about the connection the request was received on.
+		context: RequestContext,
+	},
 }
 
 /// Message sent from the main frontend to a per-connection task.
 enum FrontToBack {
 	/// Send a payload to the client.
-	Send(String),
+	Send {
+		payload: String,
+		/// Request that the payload answers, if it is the first payload sent back on it. From then
+		/// on, the request no longer counts towards [`WsServerConfig::max_in_flight_requests`].
+		answered: Option<WsRequestId>,
+	},
 	/// No more data concerning that request will be sent.
 	Finished(WsRequestId),
 }
@@ -116,18 +164,37 @@ pub struct WsRequestId(u64);
 pub struct WsTransportServerBuilder {
 	/// IP address to try to bind to.
 	bind: SocketAddr,
+	/// Configuration of the server.
+	config: WsServerConfig,
+	/// TLS configuration of the server, if any.
+	tls: Option<rustls::ServerConfig>,
+	/// Allowed hosts and origins.
+	access_control: AccessControl,
 }
 
 impl WsTransportServer {
-	/// Creates a new [`WsTransportServerBuilder`] containing the given address and hostname.
-	pub fn builder(bind: SocketAddr) -> WsTransportServerBuilder {
-		WsTransportServerBuilder { bind }
+	/// Creates a new [`WsTransportServerBuilder`] containing the given address and configuration.
+	pub fn builder(bind: SocketAddr, config: WsServerConfig) -> WsTransportServerBuilder {
+		WsTransportServerBuilder { bind, config, tls: None, access_control: AccessControl::default() }
 	}
 
 	/// Local socket address.
 	pub fn local_addr(&self) -> &SocketAddr {
 		&self.local_addr
 	}
+
+	/// Returns the context of a request that hasn't been finished yet.
+	///
+	/// The id of the returned context is always `Null`, as a single message might contain a batch
+	/// of JSON-RPC requests.
+	pub fn request_context(&self, request_id: &WsRequestId) -> Option<&RequestContext> {
+		self.to_connections.get(request_id).map(|(_, context, _)| context)
+	}
+
+	/// Returns the metrics of the server.
+	pub fn metrics(&self) -> &ServerMetrics {
+		&self.metrics
+	}
 }
 
 // former `trait TransportServer` impl.
@@ -146,7 +213,7 @@ impl WsTransportServer {
 
 				enum Event {
 					TaskFinished(Vec<WsRequestId>),
-					NewConnection(TcpStream),
+					NewConnection(TcpStream, SocketAddr),
 					Event(BackToFront),
 				}
 
@@ -154,9 +221,14 @@ impl WsTransportServer {
 					let next_connection = {
 						let listener = &self.listener;
 						async move {
+							let listener = match listener {
+								Some(listener) => listener,
+								// The server has been closed; no more connections are accepted.
+								None => future::pending().await,
+							};
 							loop {
-								if let Ok((connec, _)) = listener.accept().await {
-									break Event::NewConnection(connec);
+								if let Ok((connec, remote_addr)) = listener.accept().await {
+									break Event::NewConnection(connec, remote_addr);
 								}
 							}
 						}
@@ -181,19 +253,68 @@ impl WsTransportServer {
 				};
 
 				match next {
-					Event::NewConnection(connec) => {
+					Event::NewConnection(connec, remote_addr) => {
 						log::trace!("{:?}: new connection", self.next_request_id);
-						self.connections_tasks.push(
-							per_connection_task(connec, self.next_request_id.clone(), self.to_front.clone()).boxed(),
-						);
+						let context = RequestContext::new(ConnectionExtensions::default())
+							.with_remote_addr(remote_addr)
+							.with_connection_id(self.next_connection_id);
+						self.next_connection_id = self.next_connection_id.wrapping_add(1);
+						self.metrics.connection_opened();
+						let next_request_id = self.next_request_id.clone();
+						let to_front = self.to_front.clone();
+						let stop = self.stop_rx.clone();
+						let access_control = self.access_control.clone();
+						let config = self.config;
+						// The dummy future of `connections_tasks` doesn't count as a connection.
+						let over_capacity = self.connections_tasks.len() > config.max_connections as usize;
+						if over_capacity {
+							log::debug!("Too many connections, rejecting {:?}", remote_addr);
+						}
+						let task = match &self.tls_acceptor {
+							None if over_capacity => reject_connection(connec, stop).boxed(),
+							None => per_connection_task(
+								connec,
+								context,
+								access_control,
+								config,
+								next_request_id,
+								to_front,
+								stop,
+							)
+							.boxed(),
+							Some(acceptor) => {
+								let handshake = tls_handshake(acceptor.accept(connec), stop.clone());
+								async move {
+									match handshake.await {
+										Some(socket) if over_capacity => reject_connection(socket, stop).await,
+										Some(socket) => {
+											per_connection_task(
+												socket,
+												context,
+												access_control,
+												config,
+												next_request_id,
+												to_front,
+												stop,
+											)
+											.await
+										}
+										None => Vec::new(),
+									}
+								}
+								.boxed()
+							}
+						};
+						self.connections_tasks.push(task);
 					}
-					Event::Event(BackToFront::NewRequest { id, body, sender }) => {
+					Event::Event(BackToFront::NewRequest { id, body, sender, context }) => {
 						log::trace!("{:?}: new request", self.next_request_id);
-						let _was_in = self.to_connections.insert(id.clone(), sender);
+						let _was_in = self.to_connections.insert(id.clone(), (sender, context, false));
 						debug_assert!(_was_in.is_none());
 						return TransportServerEvent::Request { id, request: body };
 					}
 					Event::TaskFinished(list) => {
+						self.metrics.connection_closed();
 						for rq_id in list {
 							let was_in = self.to_connections.remove(&rq_id);
 
@@ -237,9 +358,12 @@ impl WsTransportServer {
 		response: Option<&'a jsonrpc::Response>,
 	) -> Pin<Box<dyn Future<Output = Result<(), ()>> + Send + 'a>> {
 		Box::pin(async move {
-			if let Some(mut sender) = self.to_connections.remove(request_id) {
-				let serialized = serde_json::to_string(&response).map_err(|_| ())?;
-				sender.send(FrontToBack::Send(serialized)).await.map_err(|_| ())?;
+			if let Some((mut sender, _, _)) = self.to_connections.remove(request_id) {
+				if let Some(response) = response {
+					let serialized = serde_json::to_string(response).map_err(|_| ())?;
+					let message = FrontToBack::Send { payload: serialized, answered: Some(*request_id) };
+					sender.send(message).await.map_err(|_| ())?;
+				}
 				sender.send(FrontToBack::Finished(*request_id)).await.map_err(|_| ())?;
 				Ok(())
 			} else {
@@ -272,13 +396,56 @@ impl WsTransportServer {
 		response: &'a jsonrpc::Response,
 	) -> Pin<Box<dyn Future<Output = Result<(), ()>> + Send + 'a>> {
 		Box::pin(async move {
-			if let Some(sender) = self.to_connections.get_mut(request_id) {
+			if let Some((sender, _, answered)) = self.to_connections.get_mut(request_id) {
 				let serialized = serde_json::to_string(&response).map_err(|_| ())?;
-				sender.send(FrontToBack::Send(serialized)).await.map_err(|_| ())?;
+				let answered = if *answered {
+					None
+				} else {
+					*answered = true;
+					Some(*request_id)
+				};
+				sender.send(FrontToBack::Send { payload: serialized, answered }).await.map_err(|_| ())?;
 			}
 			Ok(())
 		})
 	}
+
+	/// Closes the server.
+	///
+	/// Stops accepting new connections and releases the listening socket. Every open connection
+	/// sends out the messages that are already queued, then gets closed with a close frame
+	/// (status code 1000, "normal closure"). The returned `Future` resolves once all the
+	/// connections have been closed.
+	///
+	/// All the pending requests are destroyed. Calling this method multiple times is harmless.
+	/// The connections that are still open when the server is dropped are dropped as well,
+	/// without a close frame.
+	pub fn close<'a>(&'a mut self) -> Pin<Box<dyn Future<Output = ()> + Send + 'a>> {
+		Box::pin(async move {
+			self.listener = None;
+			if let Some(stop_tx) = self.stop_tx.take() {
+				let _ = stop_tx.send(());
+			}
+
+			// The dummy future pushed in `connections_tasks` never finishes.
+			while self.connections_tasks.len() > 1 {
+				let _ = self.connections_tasks.next().await;
+				self.metrics.connection_closed();
+			}
+
+			self.to_connections.clear();
+			self.pending_events.clear();
+		})
+	}
+}
+
+impl Drop for WsTransportServer {
+	fn drop(&mut self) {
+		// The dummy future pushed in `connections_tasks` isn't a connection.
+		for _ in 1..self.connections_tasks.len() {
+			self.metrics.connection_closed();
+		}
+	}
 }
 
 impl fmt::Debug for WsTransportServer {
@@ -288,6 +455,23 @@ impl fmt::Debug for WsTransportServer {
 }
 
 impl WsTransportServerBuilder {
+	/// Serves `wss://` by performing a TLS handshake on every incoming connection.
+	///
+	/// By default, the connections are in plain text.
+	pub fn tls(mut self, config: rustls::ServerConfig) -> Self {
+		self.tls = Some(config);
+		self
+	}
+
+	/// Sets the hosts and origins allowed to open a connection.
+	///
+	/// The `Host` and `Origin` headers of the handshake request are checked, and disallowed
+	/// clients are answered with `403 Forbidden`. By default, any host and any origin are allowed.
+	pub fn access_control(mut self, access_control: AccessControl) -> Self {
+		self.access_control = access_control;
+		self
+	}
+
 	/// Try establish the connection.
 	pub async fn build(self) -> Result<WsTransportServer, io::Error> {
 		let listener = TcpListener::bind(self.bind).await?;
@@ -308,54 +492,159 @@ impl WsTransportServerBuilder {
 		};
 
 		let (to_front, from_connections) = mpsc::channel(256);
+		let (stop_tx, stop_rx) = oneshot::channel();
 
 		Ok(WsTransportServer {
 			local_addr,
+			config: self.config,
 			pending_events: Vec::new(),
-			listener,
+			listener: Some(listener),
+			tls_acceptor: self.tls.map(async_tls::TlsAcceptor::from),
+			access_control: self.access_control,
 			next_request_id: Arc::new(atomic::AtomicU64::new(1)),
+			next_connection_id: 0,
 			connections_tasks,
 			to_front,
 			from_connections,
 			to_connections: HashMap::new(),
+			stop_tx: Some(stop_tx),
+			stop_rx: stop_rx.shared(),
+			metrics: ServerMetrics::with_connections("jsonrpsee_ws"),
 		})
 	}
 }
 
-/// Processes a single connection.
-//
-// TODO: document this function it is quite hard to understand the outcome when it returns `Vec<WsRequestId>`
-// both when an error or if the actual connection was terminated.
-async fn per_connection_task(
-	socket: TcpStream,
-	next_request_id: Arc<atomic::AtomicU64>,
-	mut to_front: mpsc::Sender<BackToFront>,
+/// Completes the TLS handshake of a new connection.
+///
+/// Returns `None` if the handshake fails, doesn't complete within [`HANDSHAKE_TIMEOUT`], or if
+/// the server is closed in the meantime.
+async fn tls_handshake(
+	handshake: async_tls::Accept<TcpStream>,
+	stop: future::Shared<oneshot::Receiver<()>>,
+) -> Option<async_tls::server::TlsStream<TcpStream>> {
+	let handshake = async_std::future::timeout(HANDSHAKE_TIMEOUT, handshake);
+	futures::pin_mut!(handshake);
+	match future::select(handshake, stop).await {
+		future::Either::Left((Ok(Ok(socket)), _)) => Some(socket),
+		future::Either::Left((Ok(Err(err)), _)) => {
+			log::debug!("TLS handshake failed: {:?}", err);
+			None
+		}
+		future::Either::Left((Err(_), _)) => {
+			log::debug!("TLS handshake timed out");
+			None
+		}
+		future::Either::Right(_) => None,
+	}
+}
+
+/// Runs `handshake`, the WebSocket handshake of a new connection.
+///
+/// Returns `None` if the handshake doesn't complete within [`HANDSHAKE_TIMEOUT`], or if the
+/// server is closed in the meantime.
+async fn with_handshake_timeout<T>(
+	handshake: impl Future<Output = T>,
+	stop: &mut future::Shared<oneshot::Receiver<()>>,
+) -> Option<T> {
+	let handshake = async_std::future::timeout(HANDSHAKE_TIMEOUT, handshake);
+	futures::pin_mut!(handshake);
+	match future::select(handshake, stop).await {
+		future::Either::Left((Ok(output), _)) => Some(output),
+		future::Either::Left((Err(_), _)) => {
+			log::debug!("WebSocket handshake timed out");
+			None
+		}
+		future::Either::Right(_) => None,
+	}
+}
+
+/// Answers the handshake of a connection that exceeds [`WsServerConfig::max_connections`] with
+/// `503 Service Unavailable`.
+async fn reject_connection(
+	socket: impl AsyncRead + AsyncWrite + Unpin,
+	mut stop: future::Shared<oneshot::Receiver<()>>,
 ) -> Vec<WsRequestId> {
 	let mut server = Server::new(socket);
+	let reject = async {
+		if server.receive_request().await.is_ok() {
+			let _ = server.send_response(&Response::Reject { status_code: 503 }).await;
+		}
+	};
+	with_handshake_timeout(reject, &mut stop).await;
+	Vec::new()
+}
+
+/// Reads the handshake request of a new connection and answers it.
+///
+/// Returns `None` if the handshake fails or if the host or origin of the client isn't allowed,
+/// in which case the connection must be closed.
+async fn accept_connection<S: AsyncRead + AsyncWrite + Unpin>(
+	socket: S,
+	context: &RequestContext,
+	access_control: &AccessControl,
+) -> Option<Server<'static, handshake::Replay<S>>> {
+	// Read the handshake request ourselves, as `soketto` doesn't expose its headers.
+	let (request, socket) = match handshake::read_request(socket).await {
+		Ok(res) => res,
+		Err(err) => {
+			log::debug!("Failed to read WebSocket handshake request: {:?}", err);
+			return None;
+		}
+	};
+	let mut server = Server::new(socket);
 
 	// Process the handshake from the client.
 	let websocket_key = match server.receive_request().await {
 		Ok(req) => req.into_key(),
-		Err(_) => return Vec::new(),
+		Err(_) => return None,
 	};
 
-	// Accept the client unconditionally.
-	{
-		let res = server.send_response(&{ Response::Accept { key: &websocket_key, protocol: None } }).await;
-		if res.is_err() {
-			return Vec::new();
-		}
+	// Reject the clients whose host or origin isn't allowed.
+	if access_control.deny_host(&request) || access_control.deny_cors_origin(&request) {
+		log::debug!("Rejected WebSocket connection from {:?}", context.remote_addr());
+		let _ = server.send_response(&Response::Reject { status_code: 403 }).await;
+		return None;
 	}
 
-	let (mut sender, receiver) = server.into_builder().finish();
+	server.send_response(&{ Response::Accept { key: &websocket_key, protocol: None } }).await.ok()?;
+	Some(server)
+}
+
+/// Processes a single connection.
+//
+// TODO: document this function it is quite hard to understand the outcome when it returns `Vec<WsRequestId>`
+// both when an error or if the actual connection was terminated.
+async fn per_connection_task(
+	socket: impl AsyncRead + AsyncWrite + Unpin,
+	context: RequestContext,
+	access_control: AccessControl,
+	config: WsServerConfig,
+	next_request_id: Arc<atomic::AtomicU64>,
+	mut to_front: mpsc::Sender<BackToFront>,
+	mut stop: future::Shared<oneshot::Receiver<()>>,
+) -> Vec<WsRequestId> {
+	let server = match with_handshake_timeout(accept_connection(socket, &context, &access_control), &mut stop).await {
+		Some(Some(server)) => server,
+		Some(None) | None => return Vec::new(),
+	};
+
+	let mut builder = server.into_builder();
+	builder.set_max_message_size(config.max_request_body_size as usize);
+	let (mut sender, receiver) = builder.finish();
 	let mut pending_requests = Vec::new();
+	// Subset of `pending_requests` that haven't been answered yet.
+	let mut in_flight_requests = Vec::new();
 	let (to_connec, mut from_front) = mpsc::channel(16);
 
+	let mut keep_alive = KeepAlive::new(config.keep_alive, Instant::now());
+
+	// Produces `None` when a pong is received.
 	let socket_packets = stream::unfold(receiver, move |mut receiver| async {
 		let mut buf = Vec::new();
-		let ret = match receiver.receive_data(&mut buf).await {
+		let ret = match receiver.receive(&mut buf).await {
 			// data is text or binary.
-			Ok(_) => Ok(buf),
+			Ok(soketto::Incoming::Data(_)) => Ok(Some(buf)),
+			Ok(soketto::Incoming::Pong(_)) => Ok(None),
 			Err(err) => Err(err),
 		};
 		Some((ret, receiver))
@@ -365,14 +654,77 @@ async fn per_connection_task(
 	loop {
 		let next_from_front = from_front.next();
 		let next_socket_packet = socket_packets.next();
-		futures::pin_mut!(next_socket_packet, next_from_front);
-		match future::select(next_socket_packet, next_from_front).await {
+		let keep_alive_deadline = sleep_until(keep_alive.deadline());
+		futures::pin_mut!(next_socket_packet, next_from_front, keep_alive_deadline);
+		let next = future::select(future::select(next_socket_packet, next_from_front), keep_alive_deadline);
+		let next = match future::select(next, &mut stop).await {
+			future::Either::Left((future::Either::Left((next, _)), _)) => next,
+			future::Either::Left((future::Either::Right(_), _)) => {
+				match keep_alive.poll(Instant::now()) {
+					Some(KeepAliveEvent::SendPing) => {
+						let ping = soketto::data::ByteSlice125::try_from(&[][..]).expect("empty payload; qed");
+						if let Err(err) = sender.send_ping(ping).await {
+							log::warn!("{:?}: failed to send ping: {:?}", next_request_id, err);
+							return pending_requests;
+						}
+					}
+					Some(KeepAliveEvent::PongTimeout) => {
+						log::debug!("{:?}: no pong received in time, dropping the connection", next_request_id);
+						return pending_requests;
+					}
+					Some(KeepAliveEvent::IdleTimeout) => {
+						log::debug!("{:?}: connection idle, closing it", next_request_id);
+						let _ = sender.close().await;
+						return pending_requests;
+					}
+					None => {}
+				}
+				continue;
+			}
+			// The server is being closed.
+			future::Either::Right(_) => {
+				// Send out what the server has already queued for this connection.
+				while let Ok(message) = from_front.try_recv() {
+					if let FrontToBack::Send { payload: to_send, .. } = message {
+						log::debug!("send: {}", to_send);
+						if sender.send_text(&to_send).await.is_err() {
+							return pending_requests;
+						}
+					}
+				}
+				if let Err(err) = sender.close().await {
+					log::warn!("{:?}: failed to close WebSocket connection: {:?}", next_request_id, err);
+				}
+				return pending_requests;
+			}
+		};
+
+		match next {
 			future::Either::Left((socket_packet, _)) => {
 				let socket_packet = match socket_packet {
-					Some(Ok(pq)) => {
+					Some(Ok(Some(pq))) => {
 						log::trace!("{:?}: received data from WebSocket: {:?}", next_request_id, pq);
+						keep_alive.on_message(Instant::now());
 						pq
 					}
+					Some(Ok(None)) => {
+						keep_alive.on_pong();
+						continue;
+					}
+					Some(Err(soketto::connection::Error::MessageTooLarge { maximum, .. })) => {
+						log::warn!("{:?}: received a message larger than {} bytes", next_request_id, maximum);
+						// The rest of the message hasn't been read, hence the connection can't be
+						// used anymore and is closed.
+						let response =
+							jsonrpc::Response::from(jsonrpc::Error::request_too_large(), jsonrpc::Version::V2);
+						let response = serde_json::to_string(&response).expect("valid JSON; qed");
+						if sender.send_text(&response).await.is_ok() && sender.close().await.is_ok() {
+							// Dropping the socket while the rest of the message is unread resets the
+							// connection. Give the client some time to read the response first.
+							async_std::task::sleep(CLOSE_LINGER).await;
+						}
+						return pending_requests;
+					}
 					Some(Err(err)) => {
 						log::error!("{:?}: failed to receive data from WebSocket: {:?}", next_request_id, err);
 						return pending_requests;
@@ -410,6 +762,18 @@ async fn per_connection_task(
 					}
 				};
 
+				if in_flight_requests.len() >= config.max_in_flight_requests as usize {
+					log::warn!("{:?}: too many requests in flight, rejecting {}", next_request_id, request);
+					if let Some(response) = reject_request(&request, jsonrpc::Error::server_busy()) {
+						let response = serde_json::to_string(&response).expect("valid JSON; qed");
+						if let Err(err) = sender.send_text(&response).await {
+							log::warn!("failed to send: {:?} over WebSocket transport with error: {:?}", response, err);
+							return pending_requests;
+						}
+					}
+					continue;
+				}
+
 				let request_id = WsRequestId(next_request_id.fetch_add(1, atomic::Ordering::Relaxed));
 				debug_assert_ne!(request_id.0, u64::max_value());
 				log::debug!("recv: {}", request);
@@ -425,12 +789,20 @@ async fn per_connection_task(
 				// The channel is normally large enough for this to never happen unless the server
 				// is considerably slowed down or subject to a DoS attack.
 				let result = to_front
-					.send(BackToFront::NewRequest { id: request_id, body: request, sender: to_connec.clone() })
+					.send(BackToFront::NewRequest {
+						id: request_id,
+						body: request,
+						sender: to_connec.clone(),
+						context: context.clone(),
+					})
 					.now_or_never();
 
 				match result {
 					// Request was succesfully transmitted to the frontend.
-					Some(Ok(_)) => pending_requests.push(request_id),
+					Some(Ok(_)) => {
+						pending_requests.push(request_id);
+						in_flight_requests.push(request_id);
+					}
 					// The channel is down or full.
 					Some(Err(e)) => {
 						log::error!(
@@ -453,8 +825,13 @@ async fn per_connection_task(
 			}
 
 			// Received data to send on the connection.
-			future::Either::Right((Some(FrontToBack::Send(to_send)), _)) => {
+			future::Either::Right((Some(FrontToBack::Send { payload: to_send, answered }), _)) => {
 				log::debug!("send: {}", to_send);
+				keep_alive.on_message(Instant::now());
+				// Done before sending, so that the client can't send a new request in the meantime.
+				if let Some(rq_id) = answered {
+					in_flight_requests.retain(|r| *r != rq_id);
+				}
 				if let Err(err) = sender.send_text(&to_send).await {
 					log::warn!("failed to send: {:?} over WebSocket transport with error: {:?}", to_send, err);
 					return pending_requests;
@@ -466,6 +843,7 @@ async fn per_connection_task(
 				log::trace!("finished request_id={:?}", rq_id);
 				let pos = pending_requests.iter().position(|r| *r == rq_id).unwrap();
 				pending_requests.remove(pos);
+				in_flight_requests.retain(|r| *r != rq_id);
 			}
 
 			// Channel to main WS server struct has closed. Let's close the task.
@@ -473,3 +851,36 @@ async fn per_connection_task(
 		}
 	}
 }
+
+/// Resolves once `deadline` is reached, or never if `deadline` is `None`.
+async fn sleep_until(deadline: Option<Instant>) {
+	match deadline {
+		Some(deadline) => async_std::task::sleep(deadline.saturating_duration_since(Instant::now())).await,
+		None => future::pending().await,
+	}
+}
+
+/// Builds the response to a request that is rejected as a whole with `error`.
+///
+/// Returns `None` if the request only contains notifications, which aren't answered.
+fn reject_request(request: &jsonrpc::Request, error: jsonrpc::Error) -> Option<jsonrpc::Response> {
+	let failure = |call: &jsonrpc::Call| {
+		let (jsonrpc, id) = match call {
+			jsonrpc::Call::MethodCall(call) => (call.jsonrpc, call.id.clone()),
+			jsonrpc::Call::Notification(_) => return None,
+			jsonrpc::Call::Invalid { id } => (jsonrpc::Version::V2, id.clone()),
+		};
+		Some(jsonrpc::Output::Failure(jsonrpc::Failure { jsonrpc, id, error: error.clone() }))
+	};
+	match request {
+		jsonrpc::Request::Single(call) => failure(call).map(jsonrpc::Response::Single),
+		jsonrpc::Request::Batch(calls) => {
+			let outputs: Vec<_> = calls.iter().filter_map(failure).collect();
+			if outputs.is_empty() {
+				None
+			} else {
+				Some(jsonrpc::Response::Batch(outputs))
+			}
+		}
+	}
+}